@@ -0,0 +1,90 @@
+//! Allocations made while matching urls that are already lowercase and
+//! normalized, which should be none until a rule matches and the result
+//! list is created. A compiled matcher reads the filters of its rules once,
+//! so rules whose filters turn a request away don't allocate either.
+//!
+//! `cargo bench --bench alloc`
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use whistle_proxy_rule_parser::matcher::{MatchOptions, Request};
+use whistle_proxy_rule_parser::pattern::Pattern;
+use whistle_proxy_rule_parser::rule_file::RuleFile;
+
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const ROUNDS: usize = 100_000;
+
+/// Run `f` `ROUNDS` times and print the allocations and time per call.
+fn measure(name: &str, mut f: impl FnMut(usize) -> usize) {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    let hits: usize = (0..ROUNDS).map(&mut f).sum();
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    println!(
+        "{name:<28} {:>6.2} allocs/call  {:>8.1?}/call  ({hits} hits)",
+        allocations as f64 / ROUNDS as f64,
+        elapsed / ROUNDS as u32,
+    );
+}
+
+fn main() {
+    let patterns: Vec<Pattern> = ["www.a.com/api", "*.b.com", "**.c.com/v1/", ".d.com", "api-*.e.com:8080"]
+        .into_iter()
+        .map(|p| Pattern::parse(p).unwrap())
+        .collect();
+    let urls = [
+        "http://www.a.com/api/users?id=1",
+        "https://x.b.com/",
+        "http://a.b.c.com/v1/x",
+        "https://d.com/index.html",
+        "http://api-v2.e.com:8080/",
+        "http://www.z.com/api",
+    ];
+    let normalizing = MatchOptions { normalize_path: true, case_insensitive_path: true };
+
+    let patterns = &patterns;
+    let matches = |options: MatchOptions| {
+        move |i: usize| {
+            let url = urls[i % urls.len()];
+            patterns.iter().filter(|p| p.matches_with(url, &options)).count()
+        }
+    };
+    measure("pattern, default", matches(MatchOptions::default()));
+    measure("pattern, normalizing", matches(normalizing));
+
+    let rules = RuleFile::parse("www.a.com/api file:///mock\n*.b.com 127.0.0.1\n.d.com resCors://*\n").rule_set();
+    let matcher = rules.compile();
+    measure("compiled, no match", |i| matcher.match_url(urls[4 + i % 2]).len());
+    measure("compiled, match", |i| matcher.match_url(urls[i % 2]).len());
+    measure("rule set, no match", |i| rules.resolve(&Request::new(urls[4 + i % 2])).len());
+
+    let filtered = RuleFile::parse(
+        "www.a.com/api file:///mock includeFilter://m:post\n*.b.com 127.0.0.1 excludeFilter://h:x-skip\n",
+    )
+    .rule_set()
+    .compile();
+    let requests = [
+        Request { method: Some("GET"), ..Request::new(urls[0]) },
+        Request { headers: vec![("X-Skip", "1")], ..Request::new(urls[1]) },
+    ];
+    measure("compiled, filtered out", |i| filtered.match_request(&requests[i % 2]).len());
+}
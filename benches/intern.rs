@@ -0,0 +1,55 @@
+//! Heap used by a generated rule set, owned and interned.
+//!
+//! `cargo bench --bench intern`
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use whistle_proxy_rule_parser::intern::InternedRuleSet;
+use whistle_proxy_rule_parser::rule_file::RuleFile;
+
+struct Counting;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn measure<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    let value = f();
+    (value, ALLOCATED.load(Ordering::Relaxed) - before)
+}
+
+fn main() {
+    let protocols = ["file", "reqHeaders", "resHeaders", "statusCode", "resCors"];
+    let text: String = (0..20_000)
+        .map(|i| {
+            let protocol = protocols[i % protocols.len()];
+            format!("www.host{}.example.com/api/v1 {protocol}://{{v{}}} proxy://10.0.0.1:8080\n", i % 50, i % 7)
+        })
+        .collect();
+    let file = RuleFile::parse(text);
+
+    let (rules, owned) = measure(|| file.rule_set());
+    let start = Instant::now();
+    let (interned, shared) = measure(|| InternedRuleSet::new(&rules));
+    let elapsed = start.elapsed();
+
+    println!("rules:    {}", rules.len());
+    println!("owned:    {owned} bytes");
+    println!("interned: {shared} bytes ({} distinct strings, built in {elapsed:?})", interned.interner().len());
+    println!("saved:    {:.0}%", 100.0 * (1.0 - shared as f64 / owned as f64));
+}
@@ -0,0 +1,30 @@
+//! Parse time of template-heavy lines, eager and with lazy values.
+//!
+//! `cargo bench --bench lazy`
+use std::time::Instant;
+
+use whistle_proxy_rule_parser::lazy::LazyProxyRule;
+use whistle_proxy_rule_parser::parse_proxy_rule;
+
+fn main() {
+    let lines: Vec<String> = (0..20_000)
+        .map(|i| {
+            format!(
+                "www.host{i}.example.com/api file://`/mock/${{name}}/${{id}}-{i}.json` resHeaders://`x-id=${{id}}&n=${{n}}` reqHeaders://(a={i})"
+            )
+        })
+        .collect();
+
+    let start = Instant::now();
+    let eager: usize = lines.iter().map(|line| parse_proxy_rule(line).unwrap().1.rules.len()).sum();
+    let eager_time = start.elapsed();
+
+    let start = Instant::now();
+    let lazy: usize = lines.iter().map(|line| LazyProxyRule::parse(line).unwrap().rules.len()).sum();
+    let lazy_time = start.elapsed();
+
+    assert_eq!(eager, lazy);
+    println!("lines: {}", lines.len());
+    println!("eager: {eager_time:?}");
+    println!("lazy:  {lazy_time:?}");
+}
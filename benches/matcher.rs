@@ -0,0 +1,85 @@
+//! Throughput of a `CompiledMatcher`, as the rule set grows and as threads
+//! share one. With no locking on the hot path requests per second should
+//! grow with the threads until the cores run out. A quarter of the rules
+//! are wildcards, which every request checks, and another quarter share one
+//! host, so here the time of a match grows with the rule set.
+//!
+//! `cargo bench --bench matcher`
+use std::hint::black_box;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use whistle_proxy_rule_parser::compiled::CompiledMatcher;
+use whistle_proxy_rule_parser::rule_file::RuleFile;
+
+fn matcher(rules: usize) -> CompiledMatcher {
+    let text: String = (0..rules)
+        .map(|i| match i % 4 {
+            0 => format!("host{i}.example.com file:///mock/{i}\n"),
+            1 => format!("*.wild{i}.example.com 127.0.0.1\n"),
+            2 => format!("api.example.com/v{i} resHeaders://(x-v={i})\n"),
+            _ => format!("host{i}.example.com/api excludeFilter://m:post ua://bench\n"),
+        })
+        .collect();
+    RuleFile::parse(text).rule_set().compile()
+}
+
+fn urls(rules: usize) -> Vec<String> {
+    (0..rules.min(250))
+        .map(|i| match i % 3 {
+            0 => format!("http://host{}.example.com/api/x", i * 4),
+            1 => format!("https://a.wild{}.example.com/", i * 4 + 1),
+            _ => format!("http://api.example.com/v{}/users", i * 4 + 2),
+        })
+        .collect()
+}
+
+fn rule_set_size(c: &mut Criterion) {
+    let mut group = c.benchmark_group("rule_set_size");
+    for rules in [100, 1_000, 10_000] {
+        let (matcher, urls) = (matcher(rules), urls(rules));
+        group.throughput(Throughput::Elements(urls.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(rules), &rules, |b, _| {
+            b.iter(|| urls.iter().map(|url| matcher.match_url(url).len()).sum::<usize>())
+        });
+    }
+    group.finish();
+}
+
+fn threads(c: &mut Criterion) {
+    const REQUESTS_PER_THREAD: u64 = 10_000;
+    let (matcher, urls) = (matcher(1_000), urls(1_000));
+    let cores = thread::available_parallelism().map_or(4, |n| n.get());
+    let mut group = c.benchmark_group("threads");
+    let mut threads = 1;
+    while threads <= cores {
+        group.throughput(Throughput::Elements(threads as u64 * REQUESTS_PER_THREAD));
+        group.bench_with_input(BenchmarkId::from_parameter(threads), &threads, |b, &threads| {
+            b.iter_custom(|iters| {
+                let mut elapsed = Duration::ZERO;
+                for _ in 0..iters {
+                    let start = Instant::now();
+                    thread::scope(|scope| {
+                        for t in 0..threads {
+                            let (matcher, urls) = (&matcher, &urls);
+                            scope.spawn(move || {
+                                let matched = (0..REQUESTS_PER_THREAD as usize)
+                                    .map(|i| matcher.match_url(&urls[(i + t) % urls.len()]).len())
+                                    .sum::<usize>();
+                                black_box(matched)
+                            });
+                        }
+                    });
+                    elapsed += start.elapsed();
+                }
+                elapsed
+            })
+        });
+        threads *= 2;
+    }
+    group.finish();
+}
+
+criterion_group!(benches, rule_set_size, threads);
+criterion_main!(benches);
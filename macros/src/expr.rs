@@ -0,0 +1,116 @@
+//! Rust expressions constructing a parsed rule, field by field, in terms of
+//! the `whistle_proxy_rule_parser` types the core ones mirror.
+use whistle_proxy_rule_parser_core::origin::Origin;
+use whistle_proxy_rule_parser_core::pattern::{Pattern, PortMatcher, SchemeMatcher};
+use whistle_proxy_rule_parser_core::rule_file::{Line, LineItem};
+use whistle_proxy_rule_parser_core::{OpValue, ProxyRule, Rule, Span, TemplatePart, Uri};
+
+const CRATE: &str = "::whistle_proxy_rule_parser";
+
+pub fn proxy_rule(rule: &ProxyRule) -> String {
+    format!(
+        "{CRATE}::ProxyRule {{ source: {}, target: {}, rules: {}, origin: {} }}",
+        uri(&rule.source),
+        uri(&rule.target),
+        vec(&rule.rules, op),
+        option(&rule.origin, origin),
+    )
+}
+
+/// The lines of a file without invalid ones.
+pub fn lines(lines: &[Line]) -> String {
+    vec(lines, |line| {
+        let item = match &line.item {
+            LineItem::Rule(rule) => format!("Rule({})", proxy_rule(rule)),
+            LineItem::PatternOnly(pattern) => format!("PatternOnly({})", self::pattern(pattern)),
+            LineItem::Disabled(rule) => format!("Disabled({})", proxy_rule(rule)),
+            LineItem::Comment(text) => format!("Comment({})", string(text)),
+            LineItem::Blank => "Blank".to_string(),
+            LineItem::Include(url) => format!("Include({})", uri(url)),
+            LineItem::ValueBlock { key, body } => {
+                format!("ValueBlock {{ key: {}, body: {} }}", string(key), string(body))
+            }
+            LineItem::Invalid { .. } => unreachable!("invalid lines are compile errors"),
+        };
+        format!(
+            "{CRATE}::rule_file::Line {{ number: {}, span: {}, item: {CRATE}::rule_file::LineItem::{item} }}",
+            line.number,
+            span(line.span),
+        )
+    })
+}
+
+fn uri(uri: &Uri) -> String {
+    format!(
+        "{CRATE}::Uri {{ scheme: {}, host: {}, path: {}, query: {} }}",
+        string(&uri.scheme),
+        string(&uri.host),
+        string(&uri.path),
+        string(&uri.query),
+    )
+}
+
+fn op(op: &Rule) -> String {
+    let value = match &op.value {
+        OpValue::Inline(text) => format!("Inline({})", string(text)),
+        OpValue::Value(key) => format!("Value({})", string(key)),
+        OpValue::Raw(text) => format!("Raw({})", string(text)),
+        OpValue::TemplateString(template) => {
+            let parts = vec(&template.parts, |part| match part {
+                TemplatePart::RawString(text) => format!("{CRATE}::TemplatePart::RawString({})", string(text)),
+                TemplatePart::Value(key) => format!("{CRATE}::TemplatePart::Value({})", string(key)),
+                TemplatePart::Call { name, args } => format!(
+                    "{CRATE}::TemplatePart::Call {{ name: {}, args: {} }}",
+                    string(name),
+                    vec(args, |arg| string(arg)),
+                ),
+            });
+            format!("TemplateString({CRATE}::TemplateString {{ parts: {parts} }})")
+        }
+    };
+    format!("{CRATE}::Rule {{ name: {}, value: {CRATE}::OpValue::{value} }}", string(&op.name))
+}
+
+fn pattern(pattern: &Pattern) -> String {
+    let scheme = match &pattern.scheme {
+        SchemeMatcher::Any => "Any".to_string(),
+        SchemeMatcher::Exact(scheme) => format!("Exact({})", string(scheme)),
+    };
+    let port = match pattern.port {
+        PortMatcher::Any => "Any".to_string(),
+        PortMatcher::Exact(port) => format!("Exact({port})"),
+        PortMatcher::Range(start, end) => format!("Range({start}, {end})"),
+        PortMatcher::Never => "Never".to_string(),
+    };
+    format!(
+        "{CRATE}::pattern::Pattern {{ raw: {}, uri: {}, scheme: {CRATE}::pattern::SchemeMatcher::{scheme}, \
+         port: {CRATE}::pattern::PortMatcher::{port} }}",
+        string(&pattern.raw),
+        uri(&pattern.uri),
+    )
+}
+
+fn origin(origin: &Origin) -> String {
+    format!("{CRATE}::origin::Origin {{ source: {}, line: {} }}", string(&origin.source), origin.line)
+}
+
+fn span(span: Span) -> String {
+    format!("{CRATE}::Span {{ start: {}, end: {} }}", span.start, span.end)
+}
+
+fn string(text: &str) -> String {
+    // `Debug` escapes a str the way a Rust literal does
+    format!("::std::string::String::from({text:?})")
+}
+
+fn option<T>(value: &Option<T>, f: impl Fn(&T) -> String) -> String {
+    match value {
+        Some(value) => format!("::std::option::Option::Some({})", f(value)),
+        None => "::std::option::Option::None".to_string(),
+    }
+}
+
+fn vec<T>(items: &[T], f: impl Fn(&T) -> String) -> String {
+    let items: Vec<String> = items.iter().map(f).collect();
+    format!("::std::vec![{}]", items.join(", "))
+}
@@ -0,0 +1,135 @@
+//! `proxy_rule!` and `rules!`, rule literals whose syntax is checked when
+//! the crate using them is built.
+//!
+//! The parser runs inside the macro, so a typo in a test fixture or an
+//! embedded default is a compile error pointing at the literal instead of a
+//! panic at runtime. The expansion builds the parsed rule field by field,
+//! without parsing it again.
+//!
+//! The macros parse with `whistle-proxy-rule-parser-core`, the parser's
+//! sources without its features, so `whistle-proxy-rule-parser` re-exports
+//! them with its `macros` feature.
+//!
+//! ```compile_fail
+//! let rule = whistle_proxy_rule_parser::proxy_rule!("a.com file://x oops");
+//! ```
+use proc_macro::{Delimiter, Group, Literal, Span, TokenStream, TokenTree};
+
+use whistle_proxy_rule_parser_core::rule_file::{LineItem, RuleFile};
+use whistle_proxy_rule_parser_core::{parse_proxy_rule_complete, ParserOptions};
+
+mod expr;
+
+/// A [`ProxyRule`] from one rule line.
+///
+/// [`ProxyRule`]: https://docs.rs/whistle-proxy-rule-parser/latest/whistle_proxy_rule_parser/struct.ProxyRule.html
+#[proc_macro]
+pub fn proxy_rule(input: TokenStream) -> TokenStream {
+    let (literal, text) = match string_literal(input) {
+        Ok(found) => found,
+        Err(error) => return error,
+    };
+    match parse_proxy_rule_complete(&text, &ParserOptions::default()) {
+        Ok(rule) => expand(&expr::proxy_rule(&rule)),
+        Err(error) => compile_error(&error.to_string(), literal.span()),
+    }
+}
+
+/// A `RuleFile` from a whole rule document, which must not have invalid
+/// lines.
+#[proc_macro]
+pub fn rules(input: TokenStream) -> TokenStream {
+    let (literal, text) = match string_literal(input) {
+        Ok(found) => found,
+        Err(error) => return error,
+    };
+    let file = RuleFile::parse(&text);
+    for line in file.lines() {
+        if let LineItem::Invalid { error, .. } = &line.item {
+            let message = format!("line {}: {}", line.number + 1, error);
+            return compile_error(&message, literal.span());
+        }
+    }
+    expand(&format!(
+        "::whistle_proxy_rule_parser::rule_file::RuleFile::__from_lines({text:?}, {})",
+        expr::lines(file.lines()),
+    ))
+}
+
+fn expand(code: &str) -> TokenStream {
+    code.parse().expect("the expansion is valid Rust")
+}
+
+/// The literal of a macro called with one string literal, and its value.
+fn string_literal(input: TokenStream) -> Result<(Literal, String), TokenStream> {
+    let mut tokens = input.into_iter();
+    let (first, rest) = (tokens.next(), tokens.next());
+    let literal = match (first, rest) {
+        // macro_rules! wrappers pass `$lit:literal` in an invisible group
+        (Some(TokenTree::Group(group)), None) if group.delimiter() == Delimiter::None => {
+            return string_literal(group.stream());
+        }
+        (Some(TokenTree::Literal(literal)), None) => literal,
+        (first, _) => {
+            let span = first.map_or_else(Span::call_site, |token| token.span());
+            return Err(compile_error("expected a string literal", span));
+        }
+    };
+    match unquote(&literal.to_string()) {
+        Some(text) => Ok((literal, text)),
+        None => Err(compile_error("expected a string literal", literal.span())),
+    }
+}
+
+/// The value of a `"…"` or `r#"…"#` literal as written in source.
+fn unquote(source: &str) -> Option<String> {
+    if let Some(raw) = source.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        let body = raw.get(hashes..raw.len() - hashes)?;
+        return Some(body.strip_prefix('"')?.strip_suffix('"')?.to_string());
+    }
+    let body = source.strip_prefix('"')?.strip_suffix('"')?;
+    let mut out = String::with_capacity(body.len());
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => out.push('\n'),
+            'r' => out.push('\r'),
+            't' => out.push('\t'),
+            '0' => out.push('\0'),
+            '\\' => out.push('\\'),
+            '\'' => out.push('\''),
+            '"' => out.push('"'),
+            'x' => {
+                let hex: String = chars.by_ref().take(2).collect();
+                out.push(char::from(u8::from_str_radix(&hex, 16).ok()?));
+            }
+            'u' => {
+                let hex: String = chars.by_ref().skip(1).take_while(|c| *c != '}').collect();
+                out.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+            }
+            // a `\` at the end of a line skips the newline and the indentation after it
+            '\n' => {
+                let rest = chars.as_str().trim_start();
+                chars = rest.chars();
+            }
+            _ => return None,
+        }
+    }
+    Some(out)
+}
+
+fn compile_error(message: &str, span: Span) -> TokenStream {
+    let mut tokens: TokenStream = "::core::compile_error!".parse().unwrap_or_default();
+    let mut literal = Literal::string(message);
+    literal.set_span(span);
+    let args = TokenStream::from(TokenTree::Literal(literal));
+    let mut group = Group::new(Delimiter::Parenthesis, args);
+    group.set_span(span);
+    tokens.extend([TokenTree::Group(group)]);
+    tokens
+}
@@ -0,0 +1,40 @@
+use whistle_proxy_rule_parser::rule_file::{LineItem, RuleFile};
+use whistle_proxy_rule_parser::{parse_proxy_rule, proxy_rule, rules, OpValue};
+
+#[test]
+fn test_proxy_rule() {
+    let rule = proxy_rule!("*.example.com file://\x2Fmock resHeaders://{headers}");
+    assert_eq!(rule, parse_proxy_rule("*.example.com file:///mock resHeaders://{headers}").unwrap().1);
+    assert_eq!(rule.rules[0].value, OpValue::Value("headers".into()));
+
+    let template = proxy_rule!("a.com tpl://`${now()}\"\\n${x}` reqHeaders://(a=1)");
+    assert_eq!(template, parse_proxy_rule("a.com tpl://`${now()}\"\\n${x}` reqHeaders://(a=1)").unwrap().1);
+}
+
+#[test]
+fn test_rules() {
+    let text = r#"# defaults
+a.com 127.0.0.1
+#off b.com file://x
+c.com:8000-8999
+@https://example.com/rules.txt
+``` mock
+{"ok": true}
+```
+"#;
+    let file = rules!(
+        r#"# defaults
+a.com 127.0.0.1
+#off b.com file://x
+c.com:8000-8999
+@https://example.com/rules.txt
+``` mock
+{"ok": true}
+```
+"#
+    );
+    assert_eq!(file.rules().count(), 2);
+    assert!(!file.lines().iter().any(|line| matches!(line.item, LineItem::Invalid { .. })));
+    assert_eq!(file.lines(), RuleFile::parse(text).lines());
+    assert_eq!(file.text(), text);
+}
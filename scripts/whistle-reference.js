@@ -0,0 +1,96 @@
+#!/usr/bin/env node
+// Whistle's own reading of the rules on stdin, printed as the JSON that
+// `differential::NodeReference` compares with:
+//
+//   [{"pattern": "www.a.com", "ops": [["file", "/mock"], ["reqHeaders", "{h}"]]}]
+//
+// one object per enabled rule in document order, with each operation as
+// `[protocol, value]` and a target without a scheme as `["host", value]`.
+//
+// Whistle is loaded from `WHISTLE_PATH`, a checkout or installed package,
+// or else from wherever `require` finds it:
+//
+//   npm install whistle
+//   WHISTLE_PATH=node_modules/whistle node scripts/whistle-reference.js < rules.txt
+'use strict';
+
+const path = require('path');
+
+function loadRules() {
+  const base = process.env.WHISTLE_PATH
+    ? path.resolve(process.env.WHISTLE_PATH)
+    : path.dirname(require.resolve('whistle/package.json'));
+  return require(path.join(base, 'lib', 'rules', 'rules'));
+}
+
+// `protocol://value` as whistle keeps it, or a bare host
+function operation(matcher) {
+  const at = matcher.indexOf('://');
+  if (at === -1 || !/^[\w.-]+$/.test(matcher.slice(0, at))) {
+    return ['host', matcher];
+  }
+  return [matcher.slice(0, at), matcher.slice(at + 3)];
+}
+
+// Whistle files every rule under its protocol instead of its line, so each
+// line is parsed apart and its operations put back in the order they were
+// written.
+function readLine(Rules, line) {
+  const rules = new Rules();
+  rules.parse(line);
+  const entries = [];
+  Object.keys(rules._rules || {}).forEach((protocol) => {
+    const list = rules._rules[protocol];
+    if (!Array.isArray(list)) {
+      return;
+    }
+    list.forEach((rule) => {
+      if (typeof rule.matcher !== 'string' || typeof rule.rawPattern !== 'string') {
+        throw new Error('unexpected rule shape from whistle: ' + JSON.stringify(Object.keys(rule)));
+      }
+      entries.push(rule);
+    });
+  });
+  const used = [];
+  const at = (text) => {
+    let from = 0;
+    let found = line.indexOf(text, from);
+    while (found !== -1 && used.includes(found)) {
+      from = found + 1;
+      found = line.indexOf(text, from);
+    }
+    used.push(found);
+    return found === -1 ? line.length : found;
+  };
+  const byPattern = new Map();
+  entries
+    .map((rule) => ({ rule, at: at(rule.matcher) }))
+    .sort((a, b) => a.at - b.at)
+    .forEach(({ rule }) => {
+      if (!byPattern.has(rule.rawPattern)) {
+        byPattern.set(rule.rawPattern, { pattern: rule.rawPattern, at: line.indexOf(rule.rawPattern), ops: [] });
+      }
+      byPattern.get(rule.rawPattern).ops.push(operation(rule.matcher));
+    });
+  return [...byPattern.values()]
+    .sort((a, b) => a.at - b.at)
+    .map(({ pattern, ops }) => ({ pattern, ops }));
+}
+
+function main() {
+  const Rules = loadRules();
+  const chunks = [];
+  process.stdin.on('data', (chunk) => chunks.push(chunk));
+  process.stdin.on('end', () => {
+    const text = Buffer.concat(chunks).toString('utf8');
+    const rules = [];
+    text.split(/\r\n|\r|\n/).forEach((line) => {
+      if (line.trim() && !line.trim().startsWith('#')) {
+        rules.push(...readLine(Rules, line));
+      }
+    });
+    process.stdout.write(JSON.stringify(rules) + '\n');
+  });
+}
+
+main();
@@ -0,0 +1,82 @@
+//! Shorthand protocol names, `mock://` for `file://`, for teams that wrap
+//! whistle with their own conventions. Aliases are set on
+//! [`ParserOptions::aliases`](crate::ParserOptions::aliases) and replaced
+//! by the protocol they stand for while parsing, so everything after the
+//! parser only sees whistle's own names.
+use std::collections::BTreeMap;
+
+use crate::{ProxyRule, Uri};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProtocolAliases {
+    aliases: BTreeMap<String, String>,
+}
+
+impl ProtocolAliases {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read `alias://` as `protocol://`. An alias isn't expanded again, so
+    /// `protocol` should be one of whistle's names.
+    pub fn insert(&mut self, alias: impl Into<String>, protocol: impl Into<String>) -> &mut Self {
+        self.aliases.insert(alias.into(), protocol.into());
+        self
+    }
+
+    pub fn with(mut self, alias: impl Into<String>, protocol: impl Into<String>) -> Self {
+        self.insert(alias, protocol);
+        self
+    }
+
+    /// The protocol `alias` stands for.
+    pub fn get(&self, alias: &str) -> Option<&str> {
+        self.aliases.get(alias).map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.aliases.is_empty()
+    }
+
+    /// Each alias with its protocol, sorted by alias.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.aliases.iter().map(|(alias, protocol)| (alias.as_str(), protocol.as_str()))
+    }
+
+    /// Replace aliases in the operations of `rule` and in the scheme of an
+    /// operation-first pattern or target.
+    pub(crate) fn expand(&self, rule: &mut ProxyRule) {
+        if self.is_empty() {
+            return;
+        }
+        let expand_uri = |uri: &mut Uri| {
+            if let Some(protocol) = self.get(&uri.scheme) {
+                uri.scheme = protocol.to_string();
+            }
+        };
+        expand_uri(&mut rule.source);
+        expand_uri(&mut rule.target);
+        for op in &mut rule.rules {
+            if let Some(protocol) = self.get(&op.name) {
+                op.name = protocol.to_string();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{parse_proxy_rule_complete, ParserOptions};
+
+    #[test]
+    fn test_expand_aliases() {
+        let options = ParserOptions::default().with_alias("mock", "file").with_alias("hdr", "reqHeaders");
+        let rule = parse_proxy_rule_complete("a.com mock://x hdr://{h} resCors://*", &options).unwrap();
+        assert_eq!(rule.to_string(), "a.com file://x reqHeaders://{h} resCors://*");
+
+        let rule = parse_proxy_rule_complete("mock://x a.com", &options).unwrap();
+        assert_eq!(rule.source.scheme, "file");
+        let rule = parse_proxy_rule_complete("a.com mock://x", &ParserOptions::default()).unwrap();
+        assert_eq!(rule.target.scheme, "mock");
+    }
+}
@@ -0,0 +1,81 @@
+//! `attachment://` values, which make the browser download the response.
+
+/// The file name of an `attachment://` operation, already safe to put in a
+/// `Content-Disposition` header.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct AttachmentOp {
+    /// `None` for an empty `attachment://`, which names the download after
+    /// the request path.
+    pub filename: Option<String>,
+}
+
+impl AttachmentOp {
+    pub fn parse(value: &str) -> AttachmentOp {
+        AttachmentOp { filename: sanitize_filename(value) }
+    }
+
+    /// The `Content-Disposition` header for a request to `request_url`.
+    pub fn content_disposition(&self, request_url: &str) -> String {
+        let filename = self.filename.clone().or_else(|| {
+            let path = request_url.split(['?', '#']).next().unwrap_or_default();
+            let path = path.split_once("://").map_or(path, |(_, rest)| rest.split_once('/').map_or("", |(_, p)| p));
+            sanitize_filename(&crate::form::percent_decode(path))
+        });
+        let Some(filename) = filename else {
+            return "attachment".to_string();
+        };
+        if filename.is_ascii() {
+            return format!("attachment; filename=\"{filename}\"");
+        }
+        // RFC 6266: a plain fallback for old clients, then the UTF-8 name
+        let fallback: String = filename.chars().map(|c| if c.is_ascii() { c } else { '_' }).collect();
+        let mut encoded = String::new();
+        for b in filename.bytes() {
+            match b {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'!' | b'#' | b'$' | b'&' | b'+' | b'-' | b'.'
+                | b'^' | b'_' | b'`' | b'|' | b'~' => encoded.push(b as char),
+                _ => encoded.push_str(&format!("%{b:02X}")),
+            }
+        }
+        format!("attachment; filename=\"{fallback}\"; filename*=UTF-8''{encoded}")
+    }
+}
+
+/// Keep the last path segment of `name` and drop the characters a header or
+/// a file system would choke on. `None` when nothing usable is left.
+pub fn sanitize_filename(name: &str) -> Option<String> {
+    let name = name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let name: String = name
+        .chars()
+        .filter(|c| !c.is_control() && !matches!(c, '"' | ':' | '*' | '?' | '<' | '>' | '|'))
+        .collect();
+    let name = name.trim_matches(|c: char| c == ' ' || c == '.');
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_filename() {
+        assert_eq!(sanitize_filename("report.pdf").as_deref(), Some("report.pdf"));
+        assert_eq!(sanitize_filename("../../etc/passwd").as_deref(), Some("passwd"));
+        assert_eq!(sanitize_filename(r#"C:\tmp\a"b?.txt"#).as_deref(), Some("ab.txt"));
+        assert_eq!(sanitize_filename(" .. "), None);
+        assert_eq!(sanitize_filename("a\r\nSet-Cookie: x").as_deref(), Some("aSet-Cookie x"));
+    }
+
+    #[test]
+    fn test_content_disposition() {
+        let op = AttachmentOp::parse("data.json");
+        assert_eq!(op.content_disposition("http://a.com/x"), "attachment; filename=\"data.json\"");
+        assert_eq!(
+            AttachmentOp::parse("报告.txt").content_disposition(""),
+            "attachment; filename=\"__.txt\"; filename*=UTF-8''%E6%8A%A5%E5%91%8A.txt"
+        );
+        let op = AttachmentOp::parse("");
+        assert_eq!(op.content_disposition("http://a.com/files/a%20b.zip?x=1"), "attachment; filename=\"a b.zip\"");
+        assert_eq!(op.content_disposition("http://a.com/"), "attachment");
+    }
+}
@@ -0,0 +1,163 @@
+//! Flag rules a reviewer of a shared rule file should look at twice.
+use std::fmt;
+use std::net::IpAddr;
+
+use crate::cors::{CorsOp, CorsOrigin};
+use crate::json::JsonValue;
+use crate::registry::PLUGIN_PREFIX;
+use crate::rule_file::{LineItem, RuleFile, RuleId};
+use crate::{OpValue, ProxyRule, Span};
+
+/// Protocols that run code from the rule file or a plugin. Plugin
+/// protocols, `whistle.name://`, are flagged as well.
+pub const SCRIPT_PROTOCOLS: &[&str] = &["rulesFile", "rulesScript", "reqScript", "resScript", "frameScript", "pipe"];
+
+/// Protocols whose value is a proxy server.
+pub const PROXY_PROTOCOLS: &[&str] = &["proxy", "http-proxy", "https-proxy", "socks", "host"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FindingKind {
+    /// `disable://intercept` and friends turn off HTTPS interception, so
+    /// the proxy no longer sees or checks the traffic.
+    DisablesInterception,
+    /// CORS opened to every origin.
+    PermissiveCors,
+    /// Traffic sent to an address outside private and loopback ranges.
+    PublicAddress(IpAddr),
+    /// A script or plugin protocol.
+    Script(String),
+}
+
+impl fmt::Display for FindingKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FindingKind::DisablesInterception => write!(f, "disables HTTPS interception"),
+            FindingKind::PermissiveCors => write!(f, "allows cross-origin requests from any origin"),
+            FindingKind::PublicAddress(ip) => write!(f, "sends traffic to public address {ip}"),
+            FindingKind::Script(protocol) => write!(f, "runs a script with `{protocol}://`"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecurityFinding {
+    pub rule: RuleId,
+    /// The line of the rule.
+    pub span: Span,
+    pub kind: FindingKind,
+}
+
+/// Every finding in the enabled rules of `file`, in document order.
+pub fn audit(file: &RuleFile) -> Vec<SecurityFinding> {
+    let mut findings = vec![];
+    for line in file.lines() {
+        if let LineItem::Rule(rule) = &line.item {
+            findings.extend(audit_rule(rule).into_iter().map(|kind| SecurityFinding {
+                rule: RuleId(line.number),
+                span: line.span,
+                kind,
+            }));
+        }
+    }
+    findings
+}
+
+pub fn audit_rule(rule: &ProxyRule) -> Vec<FindingKind> {
+    let mut findings = vec![];
+    if rule.target.scheme.is_empty() {
+        findings.extend(public_address(&rule.target.host).map(FindingKind::PublicAddress));
+    }
+    for op in rule.target_rule().iter().chain(&rule.rules) {
+        let name = op.name.as_str();
+        let text = match &op.value {
+            OpValue::Inline(s) | OpValue::Raw(s) => s.as_str(),
+            _ => "",
+        };
+        match name {
+            "disable" if text.split('|').any(|v| matches!(v, "intercept" | "capture")) => {
+                findings.push(FindingKind::DisablesInterception)
+            }
+            "resCors" if allows_any_origin(&op.value) => {
+                findings.push(FindingKind::PermissiveCors)
+            }
+            "resHeaders" if json_field_is_star(&op.value, "access-control-allow-origin") => {
+                findings.push(FindingKind::PermissiveCors)
+            }
+            _ if PROXY_PROTOCOLS.contains(&name) => {
+                findings.extend(public_address(text).map(FindingKind::PublicAddress))
+            }
+            _ if SCRIPT_PROTOCOLS.contains(&name) || name.starts_with(PLUGIN_PREFIX) => {
+                findings.push(FindingKind::Script(op.name.clone()))
+            }
+            _ => {}
+        }
+    }
+    findings
+}
+
+fn allows_any_origin(value: &OpValue) -> bool {
+    let cors = match value {
+        OpValue::Raw(s) | OpValue::Inline(s) => CorsOp::parse(s),
+        _ => None,
+    };
+    cors.is_some_and(|cors| cors.origin == CorsOrigin::Any)
+}
+
+fn json_field_is_star(value: &OpValue, key: &str) -> bool {
+    match value.as_json() {
+        Ok(JsonValue::Object(fields)) => fields
+            .iter()
+            .any(|(k, v)| k.eq_ignore_ascii_case(key) && v.as_str() == Some("*")),
+        _ => false,
+    }
+}
+
+/// The address of an `ip`, `ip:port` or `[ipv6]:port` host when it is public.
+fn public_address(host: &str) -> Option<IpAddr> {
+    let ip = match host.strip_prefix('[') {
+        Some(rest) => rest.split(']').next()?.parse().ok()?,
+        None => host
+            .parse()
+            .ok()
+            .or_else(|| host.rsplit_once(':').and_then(|(ip, _)| ip.parse().ok()))?,
+    };
+    let private = match ip {
+        IpAddr::V4(v4) => v4.is_private() || v4.is_loopback() || v4.is_link_local() || v4.is_unspecified(),
+        IpAddr::V6(v6) => v6.is_loopback() || v6.is_unspecified() || (v6.segments()[0] & 0xfe00) == 0xfc00,
+    };
+    (!private).then_some(ip)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_audit() {
+        let file = RuleFile::parse(
+            "a.com 127.0.0.1:8080 proxy://10.0.0.1:8888\n\
+             b.com 8.8.8.8 disable://intercept|cache\n\
+             #off c.com reqScript://{s}\n\
+             d.com resCors://* resHeaders://({\"Access-Control-Allow-Origin\":\"*\"}) socks://[2001:db8::1]:1080\n\
+             e.com resScript://{s}\n\
+             f.com whistle.inspect://x",
+        );
+        let findings: Vec<_> = audit(&file).into_iter().map(|f| (f.rule.0, f.kind)).collect();
+        assert_eq!(
+            findings,
+            vec![
+                (1, FindingKind::PublicAddress("8.8.8.8".parse().unwrap())),
+                (1, FindingKind::DisablesInterception),
+                (3, FindingKind::PermissiveCors),
+                (3, FindingKind::PermissiveCors),
+                (3, FindingKind::PublicAddress("2001:db8::1".parse().unwrap())),
+                (4, FindingKind::Script("resScript".into())),
+                (5, FindingKind::Script("whistle.inspect".into())),
+            ]
+        );
+        assert_eq!(
+            FindingKind::Script("resScript".into()).to_string(),
+            "runs a script with `resScript://`"
+        );
+    }
+}
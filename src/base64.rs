@@ -0,0 +1,69 @@
+//! Standard-alphabet base64, enough for inline payloads and binary value
+//! blocks without pulling in a dependency.
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encode `bytes` with padding, on one line.
+pub fn encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Decode `input`, ignoring ASCII whitespace and accepting missing padding.
+/// Returns the offset of the first invalid character on error.
+pub fn decode(input: &str) -> Result<Vec<u8>, usize> {
+    let mut out = Vec::with_capacity(input.len() / 4 * 3);
+    let mut buf = 0u32;
+    let mut bits = 0;
+    let mut padding = false;
+    for (i, c) in input.bytes().enumerate() {
+        if c.is_ascii_whitespace() {
+            continue;
+        }
+        if c == b'=' {
+            padding = true;
+            continue;
+        }
+        let n = match ALPHABET.iter().position(|&a| a == c) {
+            Some(n) if !padding => n as u32,
+            _ => return Err(i),
+        };
+        buf = (buf << 6) | n;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buf >> bits) as u8);
+            buf &= (1 << bits) - 1;
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(decode(""), Ok(vec![]));
+        assert_eq!(decode("Zm9vYmFy"), Ok(b"foobar".to_vec()));
+        assert_eq!(decode("Zm9vYmE="), Ok(b"fooba".to_vec()));
+        assert_eq!(decode("Zm9vYg"), Ok(b"foob".to_vec()));
+        assert_eq!(decode("Zm9v!"), Err(4));
+        assert_eq!(decode("Zg==Zg"), Err(4));
+        for s in ["", "f", "fo", "foo", "foob", "fooba", "foobar"] {
+            assert_eq!(decode(&encode(s.as_bytes())), Ok(s.as_bytes().to_vec()));
+        }
+        assert_eq!(encode(b"fooba"), "Zm9vYmE=");
+    }
+}
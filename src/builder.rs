@@ -0,0 +1,308 @@
+//! Build rules in code instead of formatting a line and parsing it back.
+//!
+//! ```
+//! use whistle_proxy_rule_parser::ProxyRule;
+//!
+//! let rule = ProxyRule::builder()
+//!     .pattern("*.example.com")
+//!     .op("file", "/mock")
+//!     .op("resHeaders", "{headers}")
+//!     .build()
+//!     .unwrap();
+//! assert_eq!(rule.to_string(), "*.example.com file:///mock resHeaders://{headers}");
+//! ```
+use std::fmt;
+
+use nom::combinator::{all_consuming, map_parser};
+use nom::IResult;
+
+use crate::validate::validate_uri;
+use crate::{parse_rule_value, parse_uri, OpValue, ProxyRule, Rule, Uri, WhistleParseError};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildError {
+    MissingPattern,
+    /// A rule needs a target or at least one operation.
+    MissingTarget,
+    /// Part of the rule doesn't parse; spans are relative to that part.
+    Invalid { part: String, error: WhistleParseError },
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::MissingPattern => f.write_str("missing pattern"),
+            BuildError::MissingTarget => f.write_str("missing target or operation"),
+            BuildError::Invalid { part, error } => write!(f, "invalid `{part}`: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// Parse `text` as one token with `parser`, rejecting anything left over.
+fn parse_part<'a, T>(
+    text: &'a str,
+    parser: impl FnMut(&'a str) -> IResult<&'a str, T>,
+) -> Result<T, BuildError> {
+    all_consuming(map_parser(crate::token, all_consuming(parser)))(text)
+        .map(|(_, value)| value)
+        .map_err(|e| BuildError::Invalid {
+            part: text.to_string(),
+            error: WhistleParseError::from_nom(text, e),
+        })
+}
+
+fn checked_uri(text: &str, is_pattern: bool) -> Result<Uri, BuildError> {
+    let uri = parse_part(text, parse_uri)?;
+    validate_uri(&uri, 0, is_pattern).map_err(|error| BuildError::Invalid { part: text.to_string(), error })?;
+    Ok(uri)
+}
+
+/// The value of an operation as it would be written after `name://`. Text
+/// with whitespace can only be a raw value, quoted when displayed.
+fn op_value(text: &str) -> Result<OpValue, BuildError> {
+    if text.contains(char::is_whitespace) {
+        return Ok(OpValue::Raw(text.to_string()));
+    }
+    if text.is_empty() {
+        return Ok(OpValue::Raw(String::new()));
+    }
+    parse_part(text, parse_rule_value)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct UriBuilder {
+    scheme: String,
+    host: String,
+    port: Option<u16>,
+    path: String,
+    query: String,
+}
+
+impl UriBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn scheme(mut self, scheme: impl Into<String>) -> Self {
+        self.scheme = scheme.into();
+        self
+    }
+
+    pub fn host(mut self, host: impl Into<String>) -> Self {
+        self.host = host.into();
+        self
+    }
+
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    /// A path without the leading `/` gets one.
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.path = path.into();
+        self
+    }
+
+    /// A query without the leading `?` gets one.
+    pub fn query(mut self, query: impl Into<String>) -> Self {
+        self.query = query.into();
+        self
+    }
+
+    /// The uri, with its scheme and host checked like a
+    /// [strict](crate::ParserOptions::strict_uri) parse would.
+    pub fn build(self) -> Result<Uri, BuildError> {
+        let mut host = self.host;
+        if let Some(port) = self.port {
+            host = format!("{host}:{port}");
+        }
+        let mut path = self.path;
+        if !path.is_empty() && !path.starts_with('/') {
+            path.insert(0, '/');
+        }
+        let mut query = self.query;
+        if !query.is_empty() && !query.starts_with('?') {
+            query.insert(0, '?');
+        }
+        let uri = Uri { scheme: self.scheme, host, path, query };
+        validate_uri(&uri, 0, false).map_err(|error| BuildError::Invalid { part: uri.to_string(), error })?;
+        Ok(uri)
+    }
+}
+
+impl Uri {
+    pub fn builder() -> UriBuilder {
+        UriBuilder::new()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ProxyRuleBuilder {
+    pattern: Option<String>,
+    target: Option<String>,
+    ops: Vec<(String, String)>,
+    rules: Vec<Rule>,
+}
+
+impl ProxyRuleBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The pattern as written in a rule, `*.example.com` or `/api/`.
+    pub fn pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.pattern = Some(pattern.into());
+        self
+    }
+
+    /// The target as written in a rule, `127.0.0.1:8080` or
+    /// `file:///mock`. Without one the first operation becomes the target.
+    pub fn target(mut self, target: impl Into<String>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+
+    /// `name://value`, with `value` read the way the parser reads it:
+    /// `{key}`, `(inline)` and `` `template` `` get their variants.
+    pub fn op(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.ops.push((name.into(), value.into()));
+        self
+    }
+
+    /// An operation that is already built, added after those from
+    /// [`op`](Self::op).
+    pub fn rule(mut self, rule: Rule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    pub fn build(self) -> Result<ProxyRule, BuildError> {
+        let pattern = self.pattern.ok_or(BuildError::MissingPattern)?;
+        let source = checked_uri(&pattern, true)?;
+
+        let mut ops = self.ops.into_iter();
+        let target = match self.target {
+            Some(target) => checked_uri(&target, false)?,
+            None => match ops.next() {
+                Some((name, value)) if value.contains(char::is_whitespace) => {
+                    Uri { scheme: name, host: String::new(), path: value, query: String::new() }
+                }
+                Some((name, value)) => checked_uri(&format!("{name}://{value}"), false)?,
+                None => return Err(BuildError::MissingTarget),
+            },
+        };
+
+        let mut rules = vec![];
+        for (name, value) in ops {
+            let valid_name = !name.is_empty() && name.chars().all(crate::is_protocol_char);
+            if !valid_name {
+                return Err(BuildError::Invalid {
+                    part: format!("{name}://{value}"),
+                    error: WhistleParseError::InvalidScheme {
+                        scheme: name.clone(),
+                        span: crate::Span::new(0, name.len()),
+                    },
+                });
+            }
+            rules.push(Rule { name, value: op_value(&value)? });
+        }
+        rules.extend(self.rules);
+        Ok(ProxyRule { source, target, rules, origin: None })
+    }
+}
+
+impl ProxyRule {
+    pub fn builder() -> ProxyRuleBuilder {
+        ProxyRuleBuilder::new()
+    }
+
+    /// Add `rule` after the other operations.
+    pub fn push_op(&mut self, rule: Rule) {
+        self.rules.push(rule);
+    }
+
+    /// Remove the operations after the target named `name`, returning how
+    /// many went. The target stays, it is replaced with
+    /// [`set_target`](Self::set_target).
+    pub fn remove_ops_by_name(&mut self, name: &str) -> usize {
+        let before = self.rules.len();
+        self.rules.retain(|rule| rule.name != name);
+        before - self.rules.len()
+    }
+
+    /// Replace the pattern with `pattern` as written in a rule.
+    pub fn set_pattern(&mut self, pattern: &str) -> Result<(), BuildError> {
+        self.source = checked_uri(pattern, true)?;
+        Ok(())
+    }
+
+    /// Replace the target with `target` as written in a rule.
+    pub fn set_target(&mut self, target: &str) -> Result<(), BuildError> {
+        self.target = checked_uri(target, false)?;
+        Ok(())
+    }
+}
+
+impl Rule {
+    /// Replace the value, returning the old one.
+    pub fn set_value(&mut self, value: OpValue) -> OpValue {
+        std::mem::replace(&mut self.value, value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse_proxy_rule;
+
+    #[test]
+    fn test_proxy_rule_builder() {
+        let rule = ProxyRule::builder()
+            .pattern("*.example.com")
+            .op("file", "/mock")
+            .op("reqHeaders", "{headers}")
+            .op("urlParams", "(a=1)")
+            .op("resBody", "hello world")
+            .build()
+            .unwrap();
+        let parsed = parse_proxy_rule(r#"*.example.com file:///mock reqHeaders://{headers} urlParams://(a=1) resBody://"hello world""#)
+            .unwrap()
+            .1;
+        assert_eq!(rule, parsed);
+
+        let rule = ProxyRule::builder().pattern("a.com").target("127.0.0.1:8080").build().unwrap();
+        assert_eq!(rule.to_string(), "a.com 127.0.0.1:8080");
+
+        assert_eq!(ProxyRule::builder().op("file", "/x").build(), Err(BuildError::MissingPattern));
+        assert_eq!(ProxyRule::builder().pattern("a.com").build(), Err(BuildError::MissingTarget));
+        assert!(matches!(
+            ProxyRule::builder().pattern("a.com").op("file", "/x").op("bad name", "1").build(),
+            Err(BuildError::Invalid { .. })
+        ));
+        assert!(matches!(
+            ProxyRule::builder().pattern("a..com").op("file", "/x").build(),
+            Err(BuildError::Invalid { error: WhistleParseError::InvalidHost { .. }, .. })
+        ));
+    }
+
+    #[test]
+    fn test_uri_builder() {
+        let uri = Uri::builder().scheme("https").host("a.com").port(8443).path("api").query("x=1").build().unwrap();
+        assert_eq!(uri.to_string(), "https://a.com:8443/api?x=1");
+        assert!(Uri::builder().scheme("https").path("/x").build().is_err());
+    }
+
+    #[test]
+    fn test_mutate_rule() {
+        let mut rule = parse_proxy_rule("a.com file:///x log://1 ua://x log://2").unwrap().1;
+        assert_eq!(rule.remove_ops_by_name("log"), 2);
+        rule.set_pattern("*.b.com").unwrap();
+        assert!(rule.set_pattern("a b").is_err());
+        rule.rules[0].set_value(OpValue::Value("ua".into()));
+        rule.push_op(crate::parse_rule("method://post").unwrap().1);
+        assert_eq!(rule.to_string(), "*.b.com file:///x ua://{ua} method://post");
+    }
+}
@@ -0,0 +1,43 @@
+//! `cache://` values, the `Cache-Control` of the response.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheOp {
+    /// `no` or `no-cache`.
+    NoCache,
+    NoStore,
+    /// A number of seconds.
+    MaxAge(u64),
+}
+
+impl CacheOp {
+    pub fn parse(value: &str) -> Option<CacheOp> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "no" | "no-cache" => Some(CacheOp::NoCache),
+            "no-store" => Some(CacheOp::NoStore),
+            seconds => seconds.parse().ok().map(CacheOp::MaxAge),
+        }
+    }
+
+    /// The `Cache-Control` header value.
+    pub fn to_header(self) -> String {
+        match self {
+            CacheOp::NoCache => "no-cache".to_string(),
+            CacheOp::NoStore => "no-store".to_string(),
+            CacheOp::MaxAge(seconds) => format!("max-age={seconds}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_cache_op() {
+        assert_eq!(CacheOp::parse("no"), Some(CacheOp::NoCache));
+        assert_eq!(CacheOp::parse("No-Store"), Some(CacheOp::NoStore));
+        assert_eq!(CacheOp::parse("3600").map(CacheOp::to_header), Some("max-age=3600".into()));
+        assert_eq!(CacheOp::parse("-1"), None);
+        assert_eq!(CacheOp::parse("soon"), None);
+    }
+}
@@ -0,0 +1,114 @@
+//! A stable JSON form of a [`RuleFile`] for snapshot tests.
+//!
+//! The layout is versioned by [`CANONICAL_VERSION`] and only changes with
+//! it, whatever happens to the types it is built from. Members are written in
+//! a fixed order, and layout-only details such as spacing and spans are left
+//! out, so a snapshot changes only when the rules do.
+use crate::json::JsonValue;
+use crate::rule_file::{LineItem, RuleFile};
+use crate::{OpValue, ProxyRule, TemplatePart};
+
+/// Bumped whenever the shape of [`RuleFile::to_canonical_json`] changes.
+pub const CANONICAL_VERSION: u32 = 1;
+
+impl RuleFile {
+    /// The file as pretty-printed JSON: a `version` and one entry per line
+    /// with its zero-based `line` number and `kind`.
+    pub fn to_canonical_json(&self) -> String {
+        self.to_canonical_value().to_pretty_string()
+    }
+
+    pub fn to_canonical_value(&self) -> JsonValue {
+        let lines = self.lines().iter().map(|line| {
+            let (kind, mut fields) = match &line.item {
+                LineItem::Rule(rule) => ("rule", rule_fields(rule, true)),
+                LineItem::Disabled(rule) => ("rule", rule_fields(rule, false)),
+                LineItem::PatternOnly(pattern) => ("pattern", vec![field("pattern", string(&pattern.raw))]),
+                LineItem::Comment(text) => ("comment", vec![field("text", string(text))]),
+                LineItem::Blank => ("blank", vec![]),
+                LineItem::Include(uri) => ("include", vec![field("url", string(&uri.to_string()))]),
+                LineItem::ValueBlock { key, body } => {
+                    ("value", vec![field("key", string(key)), field("body", string(body))])
+                }
+                LineItem::Invalid { text, error } => (
+                    "invalid",
+                    vec![field("text", string(text)), field("error", string(&error.to_string()))],
+                ),
+            };
+            fields.insert(0, field("line", JsonValue::Number(line.number as f64)));
+            fields.insert(1, field("kind", string(kind)));
+            JsonValue::Object(fields)
+        });
+        JsonValue::Object(vec![
+            field("version", JsonValue::Number(CANONICAL_VERSION.into())),
+            field("lines", JsonValue::Array(lines.collect())),
+        ])
+    }
+}
+
+fn field(key: &str, value: JsonValue) -> (String, JsonValue) {
+    (key.to_string(), value)
+}
+
+fn string(s: &str) -> JsonValue {
+    JsonValue::String(s.to_string())
+}
+
+fn rule_fields(rule: &ProxyRule, enabled: bool) -> Vec<(String, JsonValue)> {
+    let ops = rule.operations().into_iter().map(|op| {
+        JsonValue::Object(vec![field("protocol", string(&op.name)), field("value", value(&op.value))])
+    });
+    vec![
+        field("enabled", JsonValue::Bool(enabled)),
+        field("pattern", string(&rule.source.to_string())),
+        field("ops", JsonValue::Array(ops.collect())),
+    ]
+}
+
+fn value(value: &OpValue) -> JsonValue {
+    let (kind, fields) = match value {
+        OpValue::Raw(text) => ("raw", vec![field("text", string(text))]),
+        OpValue::Inline(text) => ("inline", vec![field("text", string(text))]),
+        OpValue::Value(key) => ("value", vec![field("key", string(key))]),
+        OpValue::TemplateString(template) => {
+            let parts = template.parts.iter().map(|part| match part {
+                TemplatePart::RawString(text) => JsonValue::Object(vec![field("text", string(text))]),
+                TemplatePart::Value(name) => JsonValue::Object(vec![field("value", string(name))]),
+                TemplatePart::Call { name, args } => JsonValue::Object(vec![
+                    field("call", string(name)),
+                    field("args", JsonValue::Array(args.iter().map(|a| string(a)).collect())),
+                ]),
+            });
+            ("template", vec![field("parts", JsonValue::Array(parts.collect()))])
+        }
+    };
+    let mut object = vec![field("kind", string(kind))];
+    object.extend(fields);
+    JsonValue::Object(object)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_canonical_json() {
+        let file = RuleFile::parse("# mocks\na.com   file://{mock} resBody://`${id}!`\n\n#off b.com c.com");
+        assert_eq!(
+            file.to_canonical_value().to_string(),
+            concat!(
+                r#"{"version":1,"lines":["#,
+                r#"{"line":0,"kind":"comment","text":"mocks"},"#,
+                r#"{"line":1,"kind":"rule","enabled":true,"pattern":"a.com","ops":["#,
+                r#"{"protocol":"file","value":{"kind":"value","key":"mock"}},"#,
+                r#"{"protocol":"resBody","value":{"kind":"template","parts":[{"value":"id"},{"text":"!"}]}}]},"#,
+                r#"{"line":2,"kind":"blank"},"#,
+                r#"{"line":3,"kind":"rule","enabled":false,"pattern":"b.com","ops":["#,
+                r#"{"protocol":"host","value":{"kind":"raw","text":"c.com"}}]}]}"#,
+            )
+        );
+        // spacing doesn't show up in the snapshot
+        let spaced = RuleFile::parse("# mocks\na.com file://{mock}  resBody://`${id}!`\n\n#off b.com   c.com");
+        assert_eq!(spaced.to_canonical_json(), file.to_canonical_json());
+    }
+}
@@ -0,0 +1,150 @@
+//! What each protocol does to a request, for grouping a line's operations.
+use std::fmt;
+
+use crate::{ProxyRule, Rule};
+
+/// The kinds of operation, in the order whistle applies them to a request:
+/// filters decide which rules apply, the request is rewritten, delayed and
+/// sent on, and the response is rewritten on its way back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum OpCategory {
+    /// `includeFilter://`, `ignore://`, `enable://` and the others deciding
+    /// which rules and features apply.
+    Filter,
+    /// Operations on the request's url, headers or body, and those such as
+    /// `headerReplace://` that can also change the response.
+    RequestRewrite,
+    /// `reqDelay://`, `resSpeed://` and the other delays and speed limits.
+    Throttle,
+    /// Where the request goes: `host://`, proxies and forwarding targets.
+    Connection,
+    /// An operation handed to a [plugin](crate::plugin), any undocumented
+    /// protocol included.
+    Plugin,
+    /// Mocks, redirects, and operations on the response's status, headers
+    /// or body.
+    ResponseRewrite,
+    /// `log://`, `weinre://` and the other debugging aids.
+    Debug,
+}
+
+/// The category of every documented protocol, and of the filters.
+pub const PROTOCOL_CATEGORIES: &[(OpCategory, &[&str])] = &[
+    (
+        OpCategory::Filter,
+        &["includeFilter", "excludeFilter", "filter", "ignore", "skip", "enable", "disable", "lineProps"],
+    ),
+    (
+        OpCategory::RequestRewrite,
+        &[
+            "method", "referer", "auth", "ua", "forwardedFor", "reqType", "reqCharset", "reqCookies",
+            "reqCors", "reqHeaders", "headerReplace", "reqBody", "reqPrepend", "reqAppend", "reqReplace",
+            "reqMerge", "reqWrite", "reqWriteRaw", "reqScript", "rulesFile", "rulesScript", "reqRules",
+            "urlParams", "params", "urlReplace", "pathReplace", "frameScript", "delete",
+        ],
+    ),
+    (OpCategory::Throttle, &["reqDelay", "resDelay", "reqSpeed", "resSpeed"]),
+    (
+        OpCategory::Connection,
+        &["host", "proxy", "http-proxy", "https-proxy", "socks", "pac", "http", "https", "ws", "wss", "tunnel"],
+    ),
+    (
+        OpCategory::ResponseRewrite,
+        &[
+            "file", "xfile", "tpl", "xtpl", "rawfile", "xrawfile", "redirect", "locationHref", "statusCode",
+            "replaceStatus", "responseFor", "cache", "attachment", "resType", "resCharset", "resCookies",
+            "resCors", "resHeaders", "resBody", "resPrepend", "resAppend", "resReplace", "resMerge",
+            "resWrite", "resWriteRaw", "resScript", "resRules", "htmlAppend", "htmlPrepend", "htmlBody",
+            "jsAppend", "jsPrepend", "jsBody", "cssAppend", "cssPrepend", "cssBody", "trailers",
+        ],
+    ),
+    (OpCategory::Debug, &["weinre", "log", "pipe", "style"]),
+];
+
+impl OpCategory {
+    /// The category of `protocol`, [`OpCategory::Plugin`] for any protocol
+    /// not in [`PROTOCOL_CATEGORIES`].
+    pub fn of(protocol: &str) -> OpCategory {
+        PROTOCOL_CATEGORIES
+            .iter()
+            .find(|(_, protocols)| protocols.contains(&protocol))
+            .map_or(OpCategory::Plugin, |(category, _)| *category)
+    }
+}
+
+impl fmt::Display for OpCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            OpCategory::Filter => "filter",
+            OpCategory::RequestRewrite => "request rewrite",
+            OpCategory::Throttle => "throttle",
+            OpCategory::Connection => "connection",
+            OpCategory::Plugin => "plugin",
+            OpCategory::ResponseRewrite => "response rewrite",
+            OpCategory::Debug => "debug",
+        })
+    }
+}
+
+impl Rule {
+    pub fn category(&self) -> OpCategory {
+        OpCategory::of(&self.name)
+    }
+}
+
+impl ProxyRule {
+    /// The [operations](ProxyRule::operations) of the rule grouped by
+    /// category, categories in application order and operations in line
+    /// order within each.
+    pub fn ops_by_category(&self) -> Vec<(OpCategory, Vec<Rule>)> {
+        let mut groups: Vec<(OpCategory, Vec<Rule>)> = vec![];
+        for op in self.operations() {
+            let category = op.category();
+            match groups.iter_mut().find(|(c, _)| *c == category) {
+                Some((_, ops)) => ops.push(op),
+                None => groups.push((category, vec![op])),
+            }
+        }
+        groups.sort_by_key(|(category, _)| *category);
+        groups
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ops::DOCUMENTED_PROTOCOLS;
+    use crate::parse_proxy_rule;
+
+    #[test]
+    fn test_every_protocol_has_one_category() {
+        for protocol in DOCUMENTED_PROTOCOLS {
+            let count = PROTOCOL_CATEGORIES.iter().filter(|(_, protocols)| protocols.contains(protocol)).count();
+            assert_eq!(count, 1, "{protocol}");
+        }
+        assert_eq!(OpCategory::of("whistle.vase"), OpCategory::Plugin);
+    }
+
+    #[test]
+    fn test_ops_by_category() {
+        let rule = parse_proxy_rule("a.com 127.0.0.1 file://x reqHeaders://{h} log://1 resDelay://100 ua://x")
+            .unwrap()
+            .1;
+        let groups: Vec<_> = rule
+            .ops_by_category()
+            .into_iter()
+            .map(|(category, ops)| (category, ops.into_iter().map(|op| op.name).collect::<Vec<_>>()))
+            .collect();
+        assert_eq!(
+            groups,
+            vec![
+                (OpCategory::RequestRewrite, vec!["reqHeaders".to_string(), "ua".into()]),
+                (OpCategory::Throttle, vec!["resDelay".into()]),
+                (OpCategory::Connection, vec!["host".into()]),
+                (OpCategory::ResponseRewrite, vec!["file".into()]),
+                (OpCategory::Debug, vec!["log".into()]),
+            ]
+        );
+        assert_eq!(rule.operations()[1].category().to_string(), "response rewrite");
+    }
+}
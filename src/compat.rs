@@ -0,0 +1,164 @@
+//! Which whistle release first supports each protocol, for rule files
+//! deployed to an older whistle than the one they were written against.
+use std::fmt;
+
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::rule_file::{RuleFile, RuleId};
+use crate::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct WhistleVersion {
+    pub major: u16,
+    pub minor: u16,
+    pub patch: u16,
+}
+
+impl WhistleVersion {
+    pub const fn new(major: u16, minor: u16, patch: u16) -> Self {
+        WhistleVersion { major, minor, patch }
+    }
+
+    /// Read `2.9.14` or `v2.9`, a missing part counting as 0.
+    pub fn parse(version: &str) -> Option<WhistleVersion> {
+        let version = version.strip_prefix('v').unwrap_or(version);
+        let mut parts = version.split('.').map(|part| part.parse::<u16>());
+        let major = parts.next()?.ok()?;
+        let minor = parts.next().unwrap_or(Ok(0)).ok()?;
+        let patch = parts.next().unwrap_or(Ok(0)).ok()?;
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(WhistleVersion::new(major, minor, patch))
+    }
+}
+
+impl fmt::Display for WhistleVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProtocolInfo {
+    pub name: &'static str,
+    /// The first release supporting the protocol.
+    pub since: WhistleVersion,
+}
+
+impl ProtocolInfo {
+    pub const fn new(name: &'static str, since: WhistleVersion) -> Self {
+        ProtocolInfo { name, since }
+    }
+}
+
+/// Protocols added after the first releases, each with the whistle
+/// changelog entry it was taken from. A protocol that isn't listed is taken
+/// to be supported by every version; none is listed until its release has
+/// been checked, and [`RuleFile::check_compatibility_with`] takes a table
+/// of one's own meanwhile.
+pub const PROTOCOL_VERSIONS: &[ProtocolInfo] = &[];
+
+/// What is known about `protocol`, `None` when any version supports it.
+pub fn protocol_info(protocol: &str) -> Option<&'static ProtocolInfo> {
+    PROTOCOL_VERSIONS.iter().find(|info| info.name == protocol)
+}
+
+/// An operation that the targeted whistle version doesn't support.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompatibilityWarning {
+    pub rule: RuleId,
+    pub span: Span,
+    pub protocol: String,
+    pub since: WhistleVersion,
+}
+
+impl fmt::Display for CompatibilityWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}://` needs whistle {} or later", self.protocol, self.since)
+    }
+}
+
+impl From<&CompatibilityWarning> for Diagnostic {
+    fn from(warning: &CompatibilityWarning) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            code: "unsupported-protocol",
+            span: warning.span,
+            message: warning.to_string(),
+            fix: None,
+        }
+    }
+}
+
+impl RuleFile {
+    /// Every operation of the enabled rules that `version` doesn't support
+    /// yet, in document order.
+    pub fn check_compatibility(&self, version: WhistleVersion) -> Vec<CompatibilityWarning> {
+        self.check_compatibility_with(version, PROTOCOL_VERSIONS)
+    }
+
+    /// [`RuleFile::check_compatibility`] with the first releases in
+    /// `protocols` instead of [`PROTOCOL_VERSIONS`].
+    pub fn check_compatibility_with(
+        &self,
+        version: WhistleVersion,
+        protocols: &[ProtocolInfo],
+    ) -> Vec<CompatibilityWarning> {
+        let mut warnings = vec![];
+        for (id, rule, enabled) in self.rules() {
+            if !enabled {
+                continue;
+            }
+            let spans = self.op_spans(id);
+            for (index, op) in rule.operations().iter().enumerate() {
+                let info = protocols.iter().find(|info| info.name == op.name);
+                let Some(info) = info.filter(|info| info.since > version) else {
+                    continue;
+                };
+                warnings.push(CompatibilityWarning {
+                    rule: id,
+                    span: spans.get(index).copied().unwrap_or_default(),
+                    protocol: op.name.clone(),
+                    since: info.since,
+                });
+            }
+        }
+        warnings
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_whistle_version() {
+        assert_eq!(WhistleVersion::parse("v2.9.14"), Some(WhistleVersion::new(2, 9, 14)));
+        assert_eq!(WhistleVersion::parse("1.15"), Some(WhistleVersion::new(1, 15, 0)));
+        assert_eq!(WhistleVersion::parse("2.x"), None);
+        assert!(WhistleVersion::new(1, 15, 0) > WhistleVersion::new(1, 9, 3));
+    }
+
+    #[test]
+    fn test_check_compatibility() {
+        let since = |name, minor| ProtocolInfo::new(name, WhistleVersion::new(1, minor, 0));
+        let protocols = [since("pipe", 15), since("lineProps", 19), since("headerReplace", 17)];
+        let file = RuleFile::parse("a.com file://x pipe://p\n#off b.com lineProps://x\nc.com headerReplace://x");
+        let found: Vec<_> = file
+            .check_compatibility_with(WhistleVersion::new(1, 14, 2), &protocols)
+            .into_iter()
+            .map(|w| (w.rule, w.protocol, w.span))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (RuleId(0), "pipe".to_string(), Span::new(15, 23)),
+                (RuleId(2), "headerReplace".to_string(), Span::new(55, 72)),
+            ]
+        );
+        assert!(file.check_compatibility_with(WhistleVersion::new(1, 19, 0), &protocols).is_empty());
+        assert!(file.check_compatibility(WhistleVersion::new(1, 0, 0)).is_empty());
+        let diagnostic = Diagnostic::from(&file.check_compatibility_with(WhistleVersion::new(1, 0, 0), &protocols)[0]);
+        assert_eq!(diagnostic.message, "`pipe://` needs whistle 1.15.0 or later");
+    }
+}
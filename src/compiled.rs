@@ -0,0 +1,200 @@
+//! A [`RuleSet`] prepared once for matching many requests.
+//!
+//! [`RuleSet::resolve`] reads every rule's pattern and filters again for each
+//! request. A [`CompiledMatcher`] does that up front and indexes the rules
+//! with an exact host, so a request only looks at the rules for its host and
+//! the wildcard ones. It holds no locks and is `Send + Sync`, so one matcher
+//! can be shared by all the threads of a proxy.
+use std::collections::HashMap;
+use std::time::Instant;
+
+use crate::filter::Filter;
+use crate::matcher::{MatchObserver, MatchOptions, Request, RequestUrl};
+use crate::normalize::ascii_lowercase;
+use crate::pattern::Pattern;
+use crate::rule_set::RuleSet;
+use crate::ProxyRule;
+
+#[derive(Debug, Clone)]
+struct CompiledRule {
+    rule: ProxyRule,
+    pattern: Pattern,
+    include: Vec<Filter>,
+    exclude: Vec<Filter>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CompiledMatcher {
+    rules: Vec<CompiledRule>,
+    /// Rules with an exact host, by lowercased host, in rule order.
+    by_host: HashMap<String, Vec<usize>>,
+    /// Every other rule, in rule order.
+    others: Vec<usize>,
+    options: MatchOptions,
+}
+
+impl CompiledMatcher {
+    pub fn new(rules: &RuleSet) -> Self {
+        Self::with_options(rules, MatchOptions::default())
+    }
+
+    pub fn with_options(rules: &RuleSet, options: MatchOptions) -> Self {
+        let mut matcher = CompiledMatcher { options, ..Default::default() };
+        for (index, rule) in rules.iter().enumerate() {
+            let pattern = rule.pattern();
+            let host = pattern.host();
+            let exact = !pattern.is_wildcard()
+                && !host.is_empty()
+                && host.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '.');
+            if exact {
+                matcher.by_host.entry(host.to_ascii_lowercase()).or_default().push(index);
+            } else {
+                matcher.others.push(index);
+            }
+            let (include, exclude) = rule.filters();
+            matcher.rules.push(CompiledRule { rule: rule.clone(), pattern, include, exclude });
+        }
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            rules = matcher.rules.len(),
+            hosts = matcher.by_host.len(),
+            wildcards = matcher.others.len(),
+            "compiled matcher"
+        );
+        matcher
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// The rules applying to `url`, in order, as [`RuleSet::resolve`]
+    /// gives them.
+    pub fn match_url(&self, url: &str) -> Vec<&ProxyRule> {
+        self.match_request(&Request::new(url))
+    }
+
+    pub fn match_request(&self, request: &Request) -> Vec<&ProxyRule> {
+        self.match_inner(request, None)
+    }
+
+    /// Like [`CompiledMatcher::match_request`], telling `observer` about
+    /// every rule checked. Rules the host index skips aren't reported.
+    pub fn match_observed(&self, request: &Request, observer: &impl MatchObserver) -> Vec<&ProxyRule> {
+        self.match_inner(request, Some(observer))
+    }
+
+    fn match_inner(&self, request: &Request, observer: Option<&dyn MatchObserver>) -> Vec<&ProxyRule> {
+        let url = RequestUrl::parse(request.url);
+        let host = ascii_lowercase(url.host);
+        let exact = self.by_host.get(&*host).map_or(&[][..], Vec::as_slice);
+        // both lists are in rule order, so merging them keeps it
+        let (mut i, mut j) = (0, 0);
+        let mut matches = vec![];
+        while i < exact.len() || j < self.others.len() {
+            let index = match (exact.get(i), self.others.get(j)) {
+                (Some(&a), Some(&b)) if a < b => {
+                    i += 1;
+                    a
+                }
+                (_, Some(&b)) => {
+                    j += 1;
+                    b
+                }
+                (Some(&a), None) => {
+                    i += 1;
+                    a
+                }
+                (None, None) => break,
+            };
+            let rule = &self.rules[index];
+            let matched = match observer {
+                Some(observer) => {
+                    let start = Instant::now();
+                    let matched = self.applies(rule, request, &url);
+                    observer.observe(index, matched, start.elapsed());
+                    matched
+                }
+                None => self.applies(rule, request, &url),
+            };
+            if matched {
+                #[cfg(feature = "tracing")]
+                trace_match(request.url, &rule.rule);
+                matches.push(&rule.rule);
+            }
+        }
+        #[cfg(feature = "tracing")]
+        if matches.is_empty() {
+            tracing::trace!(url = request.url, "no rule matched");
+        }
+        matches
+    }
+
+    fn applies(&self, rule: &CompiledRule, request: &Request, url: &RequestUrl) -> bool {
+        let options = &self.options;
+        if !rule.pattern.matches_parsed(url, options) {
+            return false;
+        }
+        if !rule.include.is_empty() && !rule.include.iter().any(|f| f.matches(request, options)) {
+            return false;
+        }
+        !rule.exclude.iter().any(|f| f.matches(request, options))
+    }
+}
+
+/// Which pattern matched `url` and the operations it brings.
+#[cfg(feature = "tracing")]
+pub(crate) fn trace_match(url: &str, rule: &ProxyRule) {
+    let ops: Vec<String> = rule.operations().into_iter().map(|op| op.name).collect();
+    tracing::trace!(url, pattern = %rule.source, ops = ?ops, "rule matched");
+}
+
+impl RuleSet {
+    pub fn compile(&self) -> CompiledMatcher {
+        CompiledMatcher::new(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rule_file::RuleFile;
+
+    #[test]
+    fn test_compiled_matcher() {
+        let set = RuleFile::parse(
+            "*.a.com file://1\na.com file://2\nb.com file://3\n.a.com file://4\nA.com/api file://5 excludeFilter://m:post",
+        )
+        .rule_set();
+        let matcher = set.compile();
+        for url in ["http://a.com/api", "http://x.a.com/", "http://b.com", "http://c.com/"] {
+            assert_eq!(matcher.match_url(url), set.resolve(&Request::new(url)), "{url}");
+        }
+        let targets: Vec<_> = matcher.match_url("http://a.com/api/x").iter().map(|r| r.target.host.clone()).collect();
+        assert_eq!(targets, vec!["2", "4", "5"]);
+        let post = Request { method: Some("POST"), ..Request::new("http://a.com/api") };
+        assert_eq!(matcher.match_request(&post).len(), 2);
+
+        let checked = std::sync::Mutex::new(vec![]);
+        let observer = |rule, matched, _elapsed| checked.lock().unwrap().push((rule, matched));
+        matcher.match_observed(&Request::new("http://b.com/"), &observer);
+        assert_eq!(*checked.lock().unwrap(), vec![(0, false), (2, true), (3, false)]);
+    }
+
+    #[test]
+    fn test_shared_across_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<CompiledMatcher>();
+
+        let matcher = RuleFile::parse("a.com file://1").rule_set().compile();
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| assert_eq!(matcher.match_url("http://a.com/").len(), 1));
+            }
+        });
+    }
+}
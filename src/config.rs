@@ -0,0 +1,192 @@
+//! Rule sets as structured config, behind the `serde` feature.
+//!
+//! [`RuleConfig`] is serde's view of a rule set, for teams that keep proxy
+//! config in TOML, YAML or JSON next to the rest of their settings:
+//!
+//! ```toml
+//! [[rules]]
+//! pattern = "api.example.com"
+//! ops = ["127.0.0.1:8080", "resHeaders://{cors}"]
+//! exclude_filters = ["m:options"]
+//!
+//! [values]
+//! cors = "access-control-allow-origin: *"
+//! ```
+//!
+//! Each op is written as in a rule line, the first one being the target.
+//! Filters are the values of `includeFilter://` and `excludeFilter://`.
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::markdown_values::ValueMap;
+use crate::rule_file::{value_fence, LineItem, RuleFile};
+use crate::rule_set::RuleSet;
+use crate::{parse_proxy_rule_complete, quote_if_needed, OpValue, ParserOptions, ProxyRule, WhistleParseError};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<RuleEntry>,
+    /// The `{key}` values the rules use.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub values: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleEntry {
+    pub pattern: String,
+    pub ops: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include_filters: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude_filters: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    /// The entry at `index` of [`RuleConfig::rules`] isn't a valid rule.
+    Rule { index: usize, error: WhistleParseError },
+    /// Line `number` of the whistle text isn't a valid rule.
+    Line { number: usize, error: WhistleParseError },
+}
+
+impl RuleEntry {
+    /// The entry as a whistle rule line.
+    pub fn to_line(&self) -> String {
+        let filters = [("includeFilter", &self.include_filters), ("excludeFilter", &self.exclude_filters)];
+        let filters = filters
+            .into_iter()
+            .flat_map(|(name, values)| values.iter().map(move |value| format!("{name}://{}", quote_if_needed(value))));
+        std::iter::once(self.pattern.clone())
+            .chain(self.ops.iter().cloned())
+            .chain(filters)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn from_rule(rule: &ProxyRule) -> RuleEntry {
+        let mut entry = RuleEntry {
+            pattern: rule.source.to_string(),
+            ops: vec![rule.target.to_string()],
+            ..Default::default()
+        };
+        for op in &rule.rules {
+            let value = match &op.value {
+                OpValue::Raw(s) => s.clone(),
+                value => value.to_string(),
+            };
+            match op.name.as_str() {
+                "includeFilter" => entry.include_filters.push(value),
+                "excludeFilter" => entry.exclude_filters.push(value),
+                _ => entry.ops.push(op.to_string()),
+            }
+        }
+        entry
+    }
+}
+
+impl RuleConfig {
+    /// Read whistle text, taking values from its ```` ```key ```` blocks.
+    /// Disabled rules and comments are left out.
+    pub fn from_text(text: &str) -> Result<RuleConfig, ConfigError> {
+        let mut config = RuleConfig::default();
+        for line in RuleFile::parse(text).lines() {
+            match &line.item {
+                LineItem::Rule(rule) => config.rules.push(RuleEntry::from_rule(rule)),
+                LineItem::ValueBlock { key, body } => {
+                    config.values.insert(key.clone(), body.clone());
+                }
+                LineItem::Invalid { error, .. } => {
+                    return Err(ConfigError::Line { number: line.number, error: error.clone() })
+                }
+                _ => {}
+            }
+        }
+        Ok(config)
+    }
+
+    /// Whistle text with the values as blocks ahead of the rules, which
+    /// [`RuleConfig::from_text`] reads back to the same config.
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        for (key, body) in &self.values {
+            let fence = value_fence(body);
+            out.push_str(&format!("{fence}{key}\n{body}\n{fence}\n"));
+        }
+        for rule in &self.rules {
+            out.push_str(&rule.to_line());
+            out.push('\n');
+        }
+        out
+    }
+
+    pub fn value_map(&self) -> ValueMap {
+        let mut values = ValueMap::new();
+        for (key, body) in &self.values {
+            values.insert(key.clone(), body.clone());
+        }
+        values
+    }
+}
+
+impl RuleSet {
+    pub fn from_config(config: &RuleConfig) -> Result<RuleSet, ConfigError> {
+        let options = ParserOptions::default();
+        config
+            .rules
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                parse_proxy_rule_complete(&entry.to_line(), &options).map_err(|error| ConfigError::Rule { index, error })
+            })
+            .collect()
+    }
+
+    /// The rules as config, without values, which a rule set doesn't have.
+    pub fn to_config(&self) -> RuleConfig {
+        RuleConfig { rules: self.iter().map(RuleEntry::from_rule).collect(), values: BTreeMap::new() }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TOML: &str = r#"
+[[rules]]
+pattern = "api.example.com"
+ops = ["127.0.0.1:8080", "resHeaders://{cors}"]
+exclude_filters = ["m:options"]
+
+[[rules]]
+pattern = "*.example.com"
+ops = ["file:///mock"]
+
+[values]
+cors = "access-control-allow-origin: *"
+"#;
+
+    #[test]
+    fn test_config_round_trip() {
+        let config: RuleConfig = toml::from_str(TOML).unwrap();
+        let set = RuleSet::from_config(&config).unwrap();
+        assert_eq!(set.rules[0].to_string(), "api.example.com 127.0.0.1:8080 resHeaders://{cors} excludeFilter://m:options");
+        assert_eq!(set.to_config().rules, config.rules);
+        assert_eq!(config.value_map().get("cors"), Some("access-control-allow-origin: *"));
+
+        let text = config.to_text();
+        assert_eq!(RuleConfig::from_text(&text), Ok(config.clone()));
+        assert_eq!(toml::from_str::<RuleConfig>(&toml::to_string(&config).unwrap()).unwrap(), config);
+    }
+
+    #[test]
+    fn test_config_errors() {
+        let config = RuleConfig {
+            rules: vec![RuleEntry { pattern: "a.com".into(), ops: vec!["b.com".into(), "c d".into()], ..Default::default() }],
+            ..Default::default()
+        };
+        assert!(matches!(RuleSet::from_config(&config), Err(ConfigError::Rule { index: 0, .. })));
+        assert!(matches!(RuleConfig::from_text("# x\na.com b.com c"), Err(ConfigError::Line { number: 1, .. })));
+    }
+}
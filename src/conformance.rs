@@ -0,0 +1,170 @@
+//! A corpus of canonical whistle rules with the operations they parse to and
+//! the urls they apply to, for checking this parser and other
+//! implementations of it against the same examples.
+//!
+//! The corpus is plain text, [`CORPUS`], whose format is described at its
+//! top; bindings and ports can read that file directly or run
+//! [`Case::check`] through this crate.
+use std::fmt;
+
+use crate::matcher::{MatchOptions, Request};
+use crate::{parse_proxy_rule_complete, OpValue, ParserOptions};
+
+pub const CORPUS: &str = include_str!("../conformance/cases.txt");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    Raw,
+    Value,
+    Inline,
+    Template,
+}
+
+impl ValueKind {
+    pub fn of(value: &OpValue) -> ValueKind {
+        match value {
+            OpValue::Raw(_) => ValueKind::Raw,
+            OpValue::Value(_) => ValueKind::Value,
+            OpValue::Inline(_) => ValueKind::Inline,
+            OpValue::TemplateString(_) => ValueKind::Template,
+        }
+    }
+
+    fn parse(kind: &str) -> Option<ValueKind> {
+        match kind {
+            "raw" => Some(ValueKind::Raw),
+            "value" => Some(ValueKind::Value),
+            "inline" => Some(ValueKind::Inline),
+            "template" => Some(ValueKind::Template),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpectedOp {
+    pub name: String,
+    pub kind: ValueKind,
+    /// The value without the delimiters of its kind.
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Case {
+    pub name: String,
+    pub input: String,
+    pub pattern: String,
+    pub ops: Vec<ExpectedOp>,
+    pub matches: Vec<String>,
+    pub rejects: Vec<String>,
+}
+
+/// A line of the corpus that isn't a case header, a known key or a comment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorpusError {
+    /// Zero-based line number.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for CorpusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line + 1, self.message)
+    }
+}
+
+/// The cases of [`CORPUS`].
+pub fn cases() -> Vec<Case> {
+    parse_corpus(CORPUS).expect("the corpus is well-formed")
+}
+
+pub fn parse_corpus(text: &str) -> Result<Vec<Case>, CorpusError> {
+    let mut cases: Vec<Case> = vec![];
+    for (line, raw) in text.lines().enumerate() {
+        let error = |message: &str| CorpusError { line, message: message.to_string() };
+        let trimmed = raw.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = trimmed.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            cases.push(Case { name: name.to_string(), ..Default::default() });
+            continue;
+        }
+        let case = cases.last_mut().ok_or_else(|| error("expected a `[name]` first"))?;
+        let (key, value) = trimmed.split_once(" = ").ok_or_else(|| error("expected `key = value`"))?;
+        let value = value.to_string();
+        match key {
+            "input" => case.input = value,
+            "pattern" => case.pattern = value,
+            "matches" => case.matches.push(value),
+            "rejects" => case.rejects.push(value),
+            "op" => {
+                let mut parts = value.splitn(3, ' ');
+                let (Some(name), Some(kind), value) = (parts.next(), parts.next(), parts.next()) else {
+                    return Err(error("expected `op = name kind value`"));
+                };
+                let kind = ValueKind::parse(kind).ok_or_else(|| error("unknown value kind"))?;
+                let value = value.unwrap_or_default().to_string();
+                case.ops.push(ExpectedOp { name: name.to_string(), kind, value });
+            }
+            _ => return Err(error("unknown key")),
+        }
+    }
+    Ok(cases)
+}
+
+impl Case {
+    /// Run the case through this crate's parser and matcher, describing the
+    /// first difference. The rule is compared once
+    /// [normalized](crate::ProxyRule::normalize), as whistle reads it.
+    pub fn check(&self) -> Result<(), String> {
+        let rule = parse_proxy_rule_complete(&self.input, &ParserOptions::default())
+            .map_err(|e| format!("{}: parse failed: {e}", self.name))?
+            .normalize();
+        if rule.source.to_string() != self.pattern {
+            return Err(format!("{}: pattern is `{}`, expected `{}`", self.name, rule.source, self.pattern));
+        }
+        let ops: Vec<ExpectedOp> = rule
+            .operations()
+            .into_iter()
+            .map(|op| {
+                let value = match &op.value {
+                    OpValue::Raw(s) | OpValue::Value(s) | OpValue::Inline(s) => s.clone(),
+                    OpValue::TemplateString(ts) => ts.to_string(),
+                };
+                ExpectedOp { kind: ValueKind::of(&op.value), name: op.name, value }
+            })
+            .collect();
+        if ops != self.ops {
+            return Err(format!("{}: operations are {ops:?}, expected {:?}", self.name, self.ops));
+        }
+        let options = MatchOptions::default();
+        for (urls, expected) in [(&self.matches, true), (&self.rejects, false)] {
+            for url in urls {
+                if rule.applies_to(&Request::new(url), &options) != expected {
+                    let verb = if expected { "should" } else { "shouldn't" };
+                    return Err(format!("{}: {verb} apply to {url}", self.name));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_corpus() {
+        let cases = cases();
+        assert!(cases.len() >= 15);
+        for case in &cases {
+            assert_eq!(case.check(), Ok(()));
+        }
+        assert_eq!(
+            parse_corpus("input = x"),
+            Err(CorpusError { line: 0, message: "expected a `[name]` first".into() })
+        );
+    }
+}
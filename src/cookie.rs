@@ -0,0 +1,153 @@
+//! `reqCookies://` and `resCookies://` values: `name=value` pairs, or a
+//! JSON object whose values are either the cookie value or an object with
+//! it and its attributes.
+use crate::form::parse_form;
+use crate::json::{parse_json, JsonValue};
+use crate::markdown_values::ValueMap;
+use crate::OpValue;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CookieSpec {
+    pub name: String,
+    pub value: String,
+    pub path: Option<String>,
+    pub domain: Option<String>,
+    /// Seconds, negative to delete the cookie.
+    pub max_age: Option<i64>,
+    pub expires: Option<String>,
+    pub secure: bool,
+    pub http_only: bool,
+    pub same_site: Option<String>,
+}
+
+impl CookieSpec {
+    pub fn new(name: impl Into<String>, value: impl Into<String>) -> Self {
+        CookieSpec { name: name.into(), value: value.into(), ..Default::default() }
+    }
+
+    /// Read the cookies of a value, `None` if it is JSON without the right
+    /// shape.
+    pub fn parse_list(value: &str) -> Option<Vec<CookieSpec>> {
+        let value = value.trim();
+        if !value.starts_with('{') {
+            return Some(parse_form(value).into_iter().map(|(name, value)| CookieSpec::new(name, value)).collect());
+        }
+        let JsonValue::Object(fields) = parse_json(value).ok()? else {
+            return None;
+        };
+        fields.iter().map(|(name, value)| CookieSpec::from_json(name, value)).collect()
+    }
+
+    fn from_json(name: &str, json: &JsonValue) -> Option<CookieSpec> {
+        let mut cookie = CookieSpec::new(name, "");
+        let attributes = match json {
+            JsonValue::Object(attributes) => attributes,
+            value => {
+                cookie.value = scalar(value)?;
+                return Some(cookie);
+            }
+        };
+        for (key, value) in attributes {
+            match key.to_ascii_lowercase().as_str() {
+                "value" => cookie.value = scalar(value)?,
+                "path" => cookie.path = Some(scalar(value)?),
+                "domain" => cookie.domain = Some(scalar(value)?),
+                "maxage" | "max-age" => cookie.max_age = Some(scalar(value)?.parse().ok()?),
+                "expires" => cookie.expires = Some(scalar(value)?),
+                "secure" => cookie.secure = value == &JsonValue::Bool(true),
+                "httponly" => cookie.http_only = value == &JsonValue::Bool(true),
+                "samesite" => cookie.same_site = Some(scalar(value)?),
+                _ => {}
+            }
+        }
+        Some(cookie)
+    }
+
+    /// The cookie as a `Set-Cookie` header value.
+    pub fn to_set_cookie(&self) -> String {
+        let mut header = format!("{}={}", self.name, self.value);
+        let mut push = |attribute: &str, value: Option<&str>| {
+            header.push_str("; ");
+            header.push_str(attribute);
+            if let Some(value) = value {
+                header.push('=');
+                header.push_str(value);
+            }
+        };
+        if let Some(path) = &self.path {
+            push("Path", Some(path));
+        }
+        if let Some(domain) = &self.domain {
+            push("Domain", Some(domain));
+        }
+        if let Some(max_age) = self.max_age {
+            push("Max-Age", Some(&max_age.to_string()));
+        }
+        if let Some(expires) = &self.expires {
+            push("Expires", Some(expires));
+        }
+        if self.secure {
+            push("Secure", None);
+        }
+        if self.http_only {
+            push("HttpOnly", None);
+        }
+        if let Some(same_site) = &self.same_site {
+            push("SameSite", Some(same_site));
+        }
+        header
+    }
+}
+
+fn scalar(value: &JsonValue) -> Option<String> {
+    match value {
+        JsonValue::String(s) => Some(s.clone()),
+        JsonValue::Number(n) => Some(n.to_string()),
+        JsonValue::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+impl OpValue {
+    /// The cookies of an inline or raw cookie operation value.
+    pub fn as_cookies(&self) -> Option<Vec<CookieSpec>> {
+        match self {
+            OpValue::Inline(s) | OpValue::Raw(s) => CookieSpec::parse_list(s),
+            _ => None,
+        }
+    }
+
+    /// Like [`OpValue::as_cookies`], also reading `{key}` values from `values`.
+    pub fn as_cookies_in(&self, values: &ValueMap) -> Option<Vec<CookieSpec>> {
+        match self {
+            OpValue::Value(key) => CookieSpec::parse_list(values.get(key)?),
+            _ => self.as_cookies(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_cookies() {
+        assert_eq!(
+            CookieSpec::parse_list("a=1&b=x%20y"),
+            Some(vec![CookieSpec::new("a", "1"), CookieSpec::new("b", "x y")])
+        );
+        let cookies = CookieSpec::parse_list(
+            r#"{"a": 1, "sid": {"value": "abc", "path": "/", "maxAge": -1, "httpOnly": true, "sameSite": "Lax"}}"#,
+        )
+        .unwrap();
+        assert_eq!(cookies[0], CookieSpec::new("a", "1"));
+        assert_eq!(cookies[1].to_set_cookie(), "sid=abc; Path=/; Max-Age=-1; HttpOnly; SameSite=Lax");
+        assert_eq!(CookieSpec::parse_list(r#"{"a": [1]}"#), None);
+
+        let mut values = ValueMap::new();
+        values.insert("c", r#"{"x": "1"}"#);
+        let value = OpValue::Value("c".into());
+        assert_eq!(value.as_cookies(), None);
+        assert_eq!(value.as_cookies_in(&values), Some(vec![CookieSpec::new("x", "1")]));
+    }
+}
@@ -0,0 +1,142 @@
+//! `reqCors://` and `resCors://` values: `*`, `enable`, or the fields as
+//! `origin=…&methods=…` pairs or a JSON object.
+use crate::form::parse_form;
+use crate::json::{parse_json, JsonValue};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum CorsOrigin {
+    /// `*`.
+    #[default]
+    Any,
+    /// Echo the `Origin` of the request, what `enable` does.
+    Request,
+    Exact(String),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CorsOp {
+    pub origin: CorsOrigin,
+    pub methods: Vec<String>,
+    pub headers: Vec<String>,
+    pub credentials: bool,
+    /// How long a preflight may be cached, in seconds.
+    pub max_age: Option<u64>,
+}
+
+impl CorsOp {
+    /// Read `*`, `enable`, `origin=…&credentials=true` or
+    /// `{"origin": "…", "methods": […]}`. Lists are comma separated in the
+    /// pair form and either a string or an array in JSON.
+    pub fn parse(value: &str) -> Option<CorsOp> {
+        let value = value.trim();
+        match value {
+            "" | "*" => return Some(CorsOp::default()),
+            "enable" => return Some(CorsOp { origin: CorsOrigin::Request, credentials: true, ..Default::default() }),
+            _ => {}
+        }
+        if value.starts_with('{') {
+            return CorsOp::from_json(&parse_json(value).ok()?);
+        }
+        let mut op = CorsOp::default();
+        for (key, value) in parse_form(value) {
+            op.set(&key, Field::Text(&value))?;
+        }
+        Some(op)
+    }
+
+    /// The fields of a JSON object, as in a `resCors://{cors}` value.
+    pub fn from_json(json: &JsonValue) -> Option<CorsOp> {
+        let JsonValue::Object(fields) = json else {
+            return None;
+        };
+        let mut op = CorsOp::default();
+        for (key, value) in fields {
+            op.set(key, Field::Json(value))?;
+        }
+        Some(op)
+    }
+
+    fn set(&mut self, key: &str, value: Field) -> Option<()> {
+        match key.to_ascii_lowercase().as_str() {
+            "origin" => {
+                self.origin = match value.text()?.as_str() {
+                    "*" => CorsOrigin::Any,
+                    "enable" => CorsOrigin::Request,
+                    origin => CorsOrigin::Exact(origin.to_string()),
+                }
+            }
+            "methods" => self.methods = value.list()?,
+            "headers" => self.headers = value.list()?,
+            "credentials" => self.credentials = value.flag()?,
+            "maxage" => self.max_age = Some(value.text()?.parse().ok()?),
+            // whistle ignores fields it doesn't know
+            _ => {}
+        }
+        Some(())
+    }
+}
+
+enum Field<'a> {
+    Text(&'a str),
+    Json(&'a JsonValue),
+}
+
+impl Field<'_> {
+    fn text(&self) -> Option<String> {
+        match self {
+            Field::Text(s) => Some(s.to_string()),
+            Field::Json(JsonValue::String(s)) => Some(s.clone()),
+            Field::Json(JsonValue::Number(n)) => Some(n.to_string()),
+            Field::Json(_) => None,
+        }
+    }
+
+    fn list(&self) -> Option<Vec<String>> {
+        let split = |s: &str| s.split(',').map(str::trim).filter(|s| !s.is_empty()).map(str::to_string).collect();
+        match self {
+            Field::Json(JsonValue::Array(items)) => {
+                items.iter().map(|item| item.as_str().map(str::to_string)).collect()
+            }
+            field => field.text().map(|s| split(&s)),
+        }
+    }
+
+    fn flag(&self) -> Option<bool> {
+        match self {
+            Field::Json(JsonValue::Bool(b)) => Some(*b),
+            field => match field.text()?.as_str() {
+                "true" | "1" => Some(true),
+                "false" | "0" | "" => Some(false),
+                _ => None,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_cors_op() {
+        assert_eq!(CorsOp::parse("*"), Some(CorsOp::default()));
+        let enable = CorsOp::parse("enable").unwrap();
+        assert_eq!((enable.origin, enable.credentials), (CorsOrigin::Request, true));
+
+        let op = CorsOp::parse("origin=https://a.com&methods=GET,%20POST&credentials=true&maxAge=600").unwrap();
+        assert_eq!(
+            op,
+            CorsOp {
+                origin: CorsOrigin::Exact("https://a.com".into()),
+                methods: vec!["GET".into(), "POST".into()],
+                headers: vec![],
+                credentials: true,
+                max_age: Some(600),
+            }
+        );
+        let json = CorsOp::parse(r#"{"origin": "https://a.com", "methods": ["GET", "POST"], "credentials": true, "maxAge": 600}"#);
+        assert_eq!(json, Some(op));
+        assert_eq!(CorsOp::parse("credentials=maybe"), None);
+        assert_eq!(CorsOp::parse(r#"{"methods": 1}"#).map(|op| op.methods), Some(vec!["1".into()]));
+    }
+}
@@ -0,0 +1,97 @@
+//! `data:` urls as operation values, e.g.
+//! `file://data:text/html;base64,PGgxPm1vY2s8L2gxPg==`, so small mocks can be
+//! written inline instead of living on disk.
+use crate::form::percent_decode_bytes;
+use crate::{base64, OpValue};
+
+/// Media type assumed by RFC 2397 when a data url doesn't name one.
+pub const DEFAULT_MIME: &str = "text/plain;charset=US-ASCII";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataUri {
+    /// The media type with its parameters, without the `;base64` marker.
+    pub mime: String,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DataUriError {
+    NotDataUri,
+    /// There is no `,` between the media type and the data.
+    MissingComma,
+    /// Invalid base64 at this byte offset into the url.
+    InvalidBase64(usize),
+}
+
+pub fn parse_data_uri(input: &str) -> Result<DataUri, DataUriError> {
+    let rest = input
+        .get(..5)
+        .filter(|scheme| scheme.eq_ignore_ascii_case("data:"))
+        .map(|_| &input[5..])
+        .ok_or(DataUriError::NotDataUri)?;
+    let (meta, data) = rest.split_once(',').ok_or(DataUriError::MissingComma)?;
+    let data_offset = input.len() - data.len();
+
+    let (mime, is_base64) = match meta.strip_suffix(";base64") {
+        Some(mime) => (mime, true),
+        None => (meta, false),
+    };
+    let mime = if mime.is_empty() {
+        DEFAULT_MIME.to_string()
+    } else if mime.starts_with(';') {
+        format!("text/plain{mime}")
+    } else {
+        mime.to_string()
+    };
+    let bytes = if is_base64 {
+        let data = String::from_utf8_lossy(&percent_decode_bytes(data, false)).into_owned();
+        base64::decode(&data).map_err(|at| DataUriError::InvalidBase64(data_offset + at))?
+    } else {
+        percent_decode_bytes(data, false)
+    };
+    Ok(DataUri { mime, bytes })
+}
+
+impl OpValue {
+    /// The decoded payload when the value is a raw `data:` url.
+    pub fn as_data_uri(&self) -> Option<Result<DataUri, DataUriError>> {
+        match self {
+            OpValue::Raw(s) if s.len() >= 5 && s[..5].eq_ignore_ascii_case("data:") => {
+                Some(parse_data_uri(s))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_data_uri() {
+        assert_eq!(
+            parse_data_uri("data:text/html;base64,PGgxPg=="),
+            Ok(DataUri { mime: "text/html".into(), bytes: b"<h1>".to_vec() })
+        );
+        assert_eq!(
+            parse_data_uri("data:,a%20b+c"),
+            Ok(DataUri { mime: DEFAULT_MIME.into(), bytes: b"a b+c".to_vec() })
+        );
+        assert_eq!(
+            parse_data_uri("data:;charset=utf-8,x").unwrap().mime,
+            "text/plain;charset=utf-8"
+        );
+        assert_eq!(parse_data_uri("data:text/html"), Err(DataUriError::MissingComma));
+        assert_eq!(parse_data_uri("data:;base64,ab!c"), Err(DataUriError::InvalidBase64(15)));
+    }
+
+    #[test]
+    fn test_op_value_as_data_uri() {
+        let rules = crate::get_rules(" file://data:application/json,{} file:///tmp/a").unwrap().1;
+        let data = rules[0].value.as_data_uri().unwrap().unwrap();
+        assert_eq!(data.mime, "application/json");
+        assert_eq!(data.bytes, b"{}");
+        assert_eq!(rules[1].value.as_data_uri(), None);
+    }
+}
@@ -0,0 +1,158 @@
+//! `delete://` values, the parts of a request or response to remove, such as
+//! `delete://req.headers.x-test|res.headers.set-cookie`.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeleteScope {
+    Req,
+    Res,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DeleteField {
+    /// A header, lowercased.
+    Header(String),
+    Cookie(String),
+    /// The whole body, or one dotted path into a JSON or form body.
+    Body(Option<String>),
+    /// All of the query, or one key. Always in the request scope.
+    UrlParam(Option<String>),
+    Type,
+    Charset,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DeleteOp {
+    pub fields: Vec<(DeleteScope, DeleteField)>,
+}
+
+impl DeleteOp {
+    /// Read the `|` separated list. Both the dotted `req.headers.x` and the
+    /// protocol-like `reqHeaders.x` spellings are accepted. `None` when any
+    /// entry names something `delete://` can't remove.
+    pub fn parse(value: &str) -> Option<DeleteOp> {
+        let fields = value
+            .split('|')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(parse_entry)
+            .collect::<Option<Vec<_>>>()?;
+        if fields.is_empty() {
+            return None;
+        }
+        Some(DeleteOp { fields })
+    }
+
+    /// The headers removed in `scope`.
+    pub fn headers(&self, scope: DeleteScope) -> impl Iterator<Item = &str> {
+        self.fields.iter().filter_map(move |field| match field {
+            (s, DeleteField::Header(name)) if *s == scope => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Remove the headers of `scope` from `headers`, every value of each.
+    #[cfg(feature = "http")]
+    pub fn apply_to_header_map(&self, scope: DeleteScope, headers: &mut http::HeaderMap) {
+        for name in self.headers(scope) {
+            headers.remove(name);
+        }
+    }
+
+    /// Remove the headers of `scope` from `headers`, ignoring case.
+    pub fn apply_to_headers(&self, scope: DeleteScope, headers: &mut Vec<(String, String)>) {
+        headers.retain(|(name, _)| !self.headers(scope).any(|n| n.eq_ignore_ascii_case(name)));
+    }
+}
+
+fn parse_entry(entry: &str) -> Option<(DeleteScope, DeleteField)> {
+    let (head, rest) = match entry.split_once('.') {
+        Some((head, rest)) => (head, Some(rest)),
+        None => (entry, None),
+    };
+    if head == "urlParams" {
+        return Some((DeleteScope::Req, DeleteField::UrlParam(rest.map(str::to_string))));
+    }
+    // `req.headers.x` and `reqHeaders.x` name the same field
+    let (scope, kind, field) = match head {
+        "req" | "res" => {
+            let rest = rest?;
+            let (kind, field) = match rest.split_once('.') {
+                Some((kind, field)) => (kind, Some(field)),
+                None => (rest, None),
+            };
+            (head, kind, field)
+        }
+        _ => (head.get(..3)?, head.get(3..)?, rest),
+    };
+    let scope = match scope {
+        "req" => DeleteScope::Req,
+        "res" => DeleteScope::Res,
+        _ => return None,
+    };
+    let field = field.filter(|f| !f.is_empty());
+    let field = match (kind.to_ascii_lowercase().as_str(), field) {
+        ("headers" | "h", Some(name)) => DeleteField::Header(name.to_ascii_lowercase()),
+        ("cookies", Some(name)) => DeleteField::Cookie(name.to_string()),
+        ("body", path) => DeleteField::Body(path.map(str::to_string)),
+        ("urlparams", key) if scope == DeleteScope::Req => DeleteField::UrlParam(key.map(str::to_string)),
+        ("type", None) => DeleteField::Type,
+        ("charset", None) => DeleteField::Charset,
+        _ => return None,
+    };
+    Some((scope, field))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_delete_op() {
+        let op = DeleteOp::parse("req.headers.X-Test|resHeaders.set-cookie|reqBody.user.name|urlParams|resType").unwrap();
+        assert_eq!(
+            op.fields,
+            vec![
+                (DeleteScope::Req, DeleteField::Header("x-test".into())),
+                (DeleteScope::Res, DeleteField::Header("set-cookie".into())),
+                (DeleteScope::Req, DeleteField::Body(Some("user.name".into()))),
+                (DeleteScope::Req, DeleteField::UrlParam(None)),
+                (DeleteScope::Res, DeleteField::Type),
+            ]
+        );
+        assert_eq!(
+            DeleteOp::parse("reqH.a|res.cookies.sid").unwrap().fields,
+            vec![
+                (DeleteScope::Req, DeleteField::Header("a".into())),
+                (DeleteScope::Res, DeleteField::Cookie("sid".into())),
+            ]
+        );
+        for invalid in ["", "req", "req.headers", "resUrlParams.a", "body.x", "reqType.x"] {
+            assert_eq!(DeleteOp::parse(invalid), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_apply_to_headers() {
+        let op = DeleteOp::parse("res.headers.set-cookie|req.headers.x-a").unwrap();
+        let mut headers = vec![
+            ("Set-Cookie".to_string(), "a=1".to_string()),
+            ("X-A".to_string(), "1".to_string()),
+            ("set-cookie".to_string(), "b=2".to_string()),
+        ];
+        op.apply_to_headers(DeleteScope::Res, &mut headers);
+        assert_eq!(headers, vec![("X-A".to_string(), "1".to_string())]);
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_apply_to_header_map() {
+        let op = DeleteOp::parse("res.headers.set-cookie|req.headers.x-a").unwrap();
+        let mut headers = http::HeaderMap::new();
+        headers.append("Set-Cookie", "a=1".parse().unwrap());
+        headers.append("set-cookie", "b=2".parse().unwrap());
+        headers.insert("X-A", "1".parse().unwrap());
+        op.apply_to_header_map(DeleteScope::Res, &mut headers);
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers["x-a"], "1");
+    }
+}
@@ -0,0 +1,290 @@
+//! One shape for parse errors, lint warnings and audit findings, so every
+//! front end renders them alike and can apply their fixes.
+use std::fmt;
+
+use crate::audit::{audit, FindingKind, SecurityFinding};
+use crate::edit::{apply_edits, TextEdit};
+use crate::lint::{lint, LintKind, LintWarning};
+use crate::markdown_values::{ChecksumMismatch, DuplicateKey};
+use crate::markdown_values::ValueError;
+use crate::meta::Date;
+use crate::rule_file::{LineItem, NodeKind, RuleFile, RuleId, DISABLED_MARKER};
+use crate::workspace::ValueProblem;
+use crate::{Span, WhistleParseError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum Severity {
+    Error,
+    Warning,
+    /// Worth a look, such as audit findings.
+    Info,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "info",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// A stable kebab-case name, `invalid-host` or `conflict`.
+    pub code: &'static str,
+    pub span: Span,
+    pub message: String,
+    pub fix: Option<TextEdit>,
+}
+
+impl From<&WhistleParseError> for Diagnostic {
+    fn from(error: &WhistleParseError) -> Self {
+        let message = match error {
+            WhistleParseError::Syntax { kind, .. } => format!("syntax error ({})", kind.description()),
+            WhistleParseError::InvalidScheme { scheme, .. } => format!("invalid scheme `{scheme}`"),
+            WhistleParseError::InvalidHost { host, .. } => format!("invalid host `{host}`"),
+            WhistleParseError::InvalidPort { port, .. } => format!("invalid port `{port}`"),
+            WhistleParseError::MissingHost { .. } => "missing host".to_string(),
+            WhistleParseError::TrailingInput { text, .. } => format!("`{text}` is not an operation"),
+            WhistleParseError::ExtraPattern { text, .. } => {
+                format!("`{text}` is a second pattern, not an operation")
+            }
+            WhistleParseError::LimitExceeded { limit, max, .. } => format!("{limit:?} limit of {max} exceeded"),
+            WhistleParseError::InvalidUtf8 { .. } => "invalid UTF-8".to_string(),
+        };
+        Diagnostic { severity: Severity::Error, code: error.code(), span: error.span(), message, fix: None }
+    }
+}
+
+/// A warning on the repeated key; the message names where the key was
+/// first defined.
+impl From<&DuplicateKey> for Diagnostic {
+    fn from(duplicate: &DuplicateKey) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            code: "duplicate-value",
+            span: duplicate.duplicate,
+            message: duplicate.to_string(),
+            fix: None,
+        }
+    }
+}
+
+impl From<&ChecksumMismatch> for Diagnostic {
+    fn from(mismatch: &ChecksumMismatch) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            code: "checksum-mismatch",
+            span: mismatch.span,
+            message: mismatch.to_string(),
+            fix: None,
+        }
+    }
+}
+
+impl From<&ValueProblem> for Diagnostic {
+    fn from(problem: &ValueProblem) -> Self {
+        let (severity, code, message) = match problem {
+            ValueProblem::Undefined(reference) => {
+                (Severity::Warning, "undefined-value", format!("no value is defined as `{}`", reference.key))
+            }
+            ValueProblem::Unused { key, .. } => (Severity::Info, "unused-value", format!("value `{key}` is never used")),
+            ValueProblem::Unresolvable { key, error, .. } => {
+                let message = match error {
+                    ValueError::CircularReference { chain } => format!("value `{key}` refers to itself: {}", chain.join(" -> ")),
+                    _ => format!("value `{key}` nests references too deeply"),
+                };
+                (Severity::Error, "unresolvable-value", message)
+            }
+        };
+        Diagnostic { severity, code, span: problem.span(), message, fix: None }
+    }
+}
+
+impl From<&SecurityFinding> for Diagnostic {
+    fn from(finding: &SecurityFinding) -> Self {
+        let code = match finding.kind {
+            FindingKind::DisablesInterception => "disables-interception",
+            FindingKind::PermissiveCors => "permissive-cors",
+            FindingKind::PublicAddress(_) => "public-address",
+            FindingKind::Script(_) => "script",
+        };
+        Diagnostic {
+            severity: Severity::Info,
+            code,
+            span: finding.span,
+            message: finding.kind.to_string(),
+            fix: None,
+        }
+    }
+}
+
+impl Diagnostic {
+    /// The diagnostic for a lint warning in `file`. Typos are fixed by
+    /// renaming the protocol, expired rules by disabling them, the others by
+    /// removing the operation, or disabling the rule when the operation is
+    /// its target, without which the line would be a pattern alone.
+    pub fn from_lint(warning: &LintWarning, file: &RuleFile) -> Self {
+        let text = file.text();
+        let code = match warning.kind {
+            LintKind::Conflict { .. } => "conflict",
+            LintKind::DebugOperation(_) => "debug-operation",
+            LintKind::Duplicate(_) => "duplicate",
+            LintKind::Typo { .. } => "typo",
+            LintKind::Expired(_) => "expired-rule",
+        };
+        let fix = match &warning.kind {
+            LintKind::Typo { protocol, suggestion } => {
+                let start = warning.span.start;
+                TextEdit::new(Span::new(start, start + protocol.len()), *suggestion)
+            }
+            LintKind::Expired(_) => disable(text, warning.span.start),
+            _ if file.op_spans(warning.rule).first() == Some(&warning.span) => disable(text, warning.span.start),
+            _ => {
+                let before = text[..warning.span.start].trim_end_matches([' ', '\t']);
+                TextEdit::new(Span::new(before.len(), warning.span.end), "")
+            }
+        };
+        Diagnostic {
+            severity: Severity::Warning,
+            code,
+            span: warning.span,
+            message: warning.kind.to_string(),
+            fix: Some(fix),
+        }
+    }
+}
+
+/// The edit disabling the rule on the line of `text` around `at`.
+fn disable(text: &str, at: usize) -> TextEdit {
+    let line = text[..at].rfind(['\n', '\r']).map_or(0, |at| at + 1);
+    let start = line + (text[line..].len() - text[line..].trim_start_matches([' ', '\t']).len());
+    TextEdit::new(Span::new(start, start), format!("{DISABLED_MARKER} "))
+}
+
+/// `text` with the fixes of `diagnostics` applied. A fix overlapping an
+/// earlier one is left for the next run.
+pub fn apply_fixes(text: &str, diagnostics: &[Diagnostic]) -> String {
+    let edits: Vec<TextEdit> = diagnostics.iter().filter_map(|d| d.fix.clone()).collect();
+    apply_edits(text, &edits)
+}
+
+/// An `info` for each protocol alias of the file's
+/// [options](crate::ParserOptions::aliases), so editors can show what the
+/// shorthand was read as.
+fn alias_diagnostics(file: &RuleFile) -> Vec<Diagnostic> {
+    let aliases = &file.options().aliases;
+    if aliases.is_empty() {
+        return vec![];
+    }
+    let mut diagnostics = vec![];
+    for line in file.lines() {
+        for node in file.nodes(RuleId(line.number)) {
+            let text = &file.text()[node.span.start..node.span.end];
+            let name = match node.kind {
+                NodeKind::Protocol => text,
+                NodeKind::Pattern => text.split_once("://").map_or("", |(scheme, _)| scheme),
+                NodeKind::Value => continue,
+            };
+            if let Some(protocol) = aliases.get(name) {
+                diagnostics.push(Diagnostic {
+                    severity: Severity::Info,
+                    code: "alias",
+                    span: Span::new(node.span.start, node.span.start + name.len()),
+                    message: format!("`{name}://` is an alias of `{protocol}://`"),
+                    fix: None,
+                });
+            }
+        }
+    }
+    diagnostics
+}
+
+/// Parse errors, lint warnings, audit findings and expanded aliases of
+/// `file`, in document order, rules [expired](crate::lint::expired) by
+/// `today` included.
+pub fn diagnostics(file: &RuleFile, today: Date) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = file
+        .lines()
+        .iter()
+        .filter_map(|line| match &line.item {
+            LineItem::Invalid { error, .. } => Some(Diagnostic::from(error)),
+            _ => None,
+        })
+        .collect();
+    diagnostics.extend(lint(file, today).iter().map(|w| Diagnostic::from_lint(w, file)));
+    diagnostics.extend(audit(file).iter().map(Diagnostic::from));
+    diagnostics.extend(alias_diagnostics(file));
+    diagnostics.sort_by_key(|d| (d.span.start, d.severity));
+    diagnostics
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TODAY: Date = Date { year: 2025, month: 3, day: 1 };
+
+    #[test]
+    fn test_duplicate_value_diagnostic() {
+        let values = crate::markdown_values::ValueMap::parse("```a\n1\n```\n```a\n2\n```");
+        let diagnostic = Diagnostic::from(&values.duplicates()[0]);
+        assert_eq!((diagnostic.code, diagnostic.span), ("duplicate-value", Span::new(14, 15)));
+        assert_eq!(diagnostic.message, "value `a` at 14 is already defined at 3");
+    }
+
+    #[test]
+    fn test_alias_diagnostics() {
+        let options = crate::ParserOptions::default().with_alias("mock", "file");
+        let file = RuleFile::parse_with("a.com mock://x\nmock://y b.com", options).unwrap();
+        let found: Vec<_> = diagnostics(&file, TODAY).into_iter().map(|d| (d.code, d.span, d.message)).collect();
+        assert_eq!(
+            found,
+            vec![
+                ("alias", Span::new(6, 10), "`mock://` is an alias of `file://`".to_string()),
+                ("alias", Span::new(15, 19), "`mock://` is an alias of `file://`".to_string()),
+            ]
+        );
+        assert_eq!(file.rule_set().rules[1].source.scheme, "file");
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let file = RuleFile::parse("a.com b.com oops\nc.com file://x  file://y\nd.com 8.8.8.8");
+        let found: Vec<_> = diagnostics(&file, TODAY).into_iter().map(|d| (d.severity, d.code, d.span)).collect();
+        assert_eq!(
+            found,
+            vec![
+                (Severity::Error, "trailing-input", Span::new(12, 16)),
+                (Severity::Warning, "conflict", Span::new(33, 41)),
+                (Severity::Info, "public-address", Span::new(42, 55)),
+            ]
+        );
+        let conflict = &diagnostics(&file, TODAY)[1];
+        assert_eq!(conflict.fix, Some(TextEdit::new(Span::new(31, 41), "")));
+        assert_eq!(conflict.message, "`file://` is ignored, whistle uses `file://` from earlier on the line");
+        assert_eq!(diagnostics(&file, TODAY)[0].message, "`oops` is not an operation");
+    }
+
+    #[test]
+    fn test_apply_fixes() {
+        let text = "a.com resheaders://{a} log://1\nb.com file://x file://y log://2\nc.com ua://x ua://x\n";
+        let fixed = apply_fixes(text, &diagnostics(&RuleFile::parse(text), TODAY));
+        assert_eq!(fixed, "a.com resHeaders://{a}\nb.com file://x\nc.com ua://x\n");
+        assert!(diagnostics(&RuleFile::parse(&fixed), TODAY).is_empty());
+
+        let text = "c.com log://1\nd.com log://2 ua://x\n";
+        let found = diagnostics(&RuleFile::parse(text), TODAY);
+        assert_eq!(found.iter().map(|d| d.code).collect::<Vec<_>>(), ["debug-operation", "debug-operation"]);
+        assert_eq!(apply_fixes(text, &found), "#off c.com log://1\n#off d.com log://2 ua://x\n");
+
+        let text = "x.com 127.0.0.1\n  a.com 127.0.0.1 # expires:2001-01-01\n";
+        let expired = diagnostics(&RuleFile::parse(text), TODAY);
+        assert_eq!(expired[0].code, "expired-rule");
+        assert_eq!(apply_fixes(text, &expired), "x.com 127.0.0.1\n  #off a.com 127.0.0.1 # expires:2001-01-01\n");
+    }
+}
@@ -0,0 +1,255 @@
+//! Compare this parser with a reference implementation, usually whistle's
+//! own, to find inputs the two read differently.
+//!
+//! The reference is anything implementing [`ReferenceParser`]. For whistle
+//! that is [`NodeReference`], which runs a node script with the rule text
+//! on stdin. The script prints one JSON array with an object per enabled
+//! rule, in order:
+//!
+//! ```text
+//! [{"pattern": "www.a.com", "ops": [["file", "/mock"], ["reqHeaders", "{h}"]]}]
+//! ```
+//!
+//! Operations are `[protocol, value]` pairs with the value as written after
+//! `://`, a target without a scheme being `["host", "127.0.0.1"]`.
+//!
+//! `scripts/whistle-reference.js` is that script for whistle itself, loaded
+//! from `WHISTLE_PATH` or the node modules:
+//!
+//! ```no_run
+//! use whistle_proxy_rule_parser::differential::{Differential, NodeReference};
+//!
+//! let differential = Differential::new(NodeReference::new("scripts/whistle-reference.js"));
+//! for (input, divergence) in differential.run(1, 1_000) {
+//!     println!("{input:?}: {divergence}");
+//! }
+//! ```
+use std::fmt;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use crate::json::{parse_json, JsonValue};
+use crate::rule_file::RuleFile;
+use crate::ProxyRule;
+
+/// A rule reduced to what both parsers can report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReferenceRule {
+    pub pattern: String,
+    /// `(protocol, value)` for each operation, the target first.
+    pub ops: Vec<(String, String)>,
+}
+
+impl ReferenceRule {
+    /// The rule as whistle reads it, pattern first.
+    pub fn from_proxy_rule(rule: &ProxyRule) -> Self {
+        let rule = rule.normalize();
+        ReferenceRule {
+            pattern: rule.source.to_string(),
+            ops: rule.operations().into_iter().map(|op| (op.name, op.value.to_string())).collect(),
+        }
+    }
+
+    fn from_json(value: &JsonValue) -> Option<Self> {
+        let pattern = value.get("pattern")?.as_str()?.to_string();
+        let JsonValue::Array(ops) = value.get("ops")? else {
+            return None;
+        };
+        let ops = ops
+            .iter()
+            .map(|op| match op {
+                JsonValue::Array(pair) => match pair.as_slice() {
+                    [name, value] => Some((name.as_str()?.to_string(), value.as_str()?.to_string())),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect::<Option<_>>()?;
+        Some(ReferenceRule { pattern, ops })
+    }
+}
+
+/// A parser to compare with. Errors are reported as text since they come
+/// from the reference, not from this crate.
+pub trait ReferenceParser {
+    fn parse(&self, input: &str) -> Result<Vec<ReferenceRule>, String>;
+}
+
+impl<F: Fn(&str) -> Result<Vec<ReferenceRule>, String>> ReferenceParser for F {
+    fn parse(&self, input: &str) -> Result<Vec<ReferenceRule>, String> {
+        self(input)
+    }
+}
+
+/// Runs `node script` per input, see the [module docs](self) for what the
+/// script prints.
+#[derive(Debug, Clone)]
+pub struct NodeReference {
+    pub node: PathBuf,
+    pub script: PathBuf,
+}
+
+impl NodeReference {
+    pub fn new(script: impl Into<PathBuf>) -> Self {
+        NodeReference { node: PathBuf::from("node"), script: script.into() }
+    }
+}
+
+impl ReferenceParser for NodeReference {
+    fn parse(&self, input: &str) -> Result<Vec<ReferenceRule>, String> {
+        let mut child = Command::new(&self.node)
+            .arg(&self.script)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("can't run {}: {e}", self.node.display()))?;
+        child
+            .stdin
+            .take()
+            .expect("stdin is piped")
+            .write_all(input.as_bytes())
+            .map_err(|e| e.to_string())?;
+        let output = child.wait_with_output().map_err(|e| e.to_string())?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).into_owned());
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let JsonValue::Array(rules) = parse_json(stdout.trim()).map_err(|e| format!("{e:?}"))? else {
+            return Err("the script didn't print an array".to_string());
+        };
+        rules
+            .iter()
+            .map(|rule| ReferenceRule::from_json(rule).ok_or_else(|| format!("malformed rule {rule}")))
+            .collect()
+    }
+}
+
+/// How the two parsers read an input differently.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Divergence {
+    /// The reference couldn't parse the input at all.
+    ReferenceFailed(String),
+    RuleCount { ours: usize, theirs: usize },
+    /// The first rule read differently, by its position among the rules.
+    Rule { index: usize, ours: ReferenceRule, theirs: ReferenceRule },
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Divergence::ReferenceFailed(message) => write!(f, "reference failed: {message}"),
+            Divergence::RuleCount { ours, theirs } => write!(f, "{ours} rules, the reference has {theirs}"),
+            Divergence::Rule { index, ours, theirs } => {
+                write!(f, "rule {index} is {ours:?}, the reference has {theirs:?}")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Differential<R> {
+    pub reference: R,
+}
+
+impl<R: ReferenceParser> Differential<R> {
+    pub fn new(reference: R) -> Self {
+        Differential { reference }
+    }
+
+    /// The first difference in how the enabled rules of `input` are read,
+    /// `None` when both agree.
+    pub fn compare(&self, input: &str) -> Option<Divergence> {
+        let theirs = match self.reference.parse(input) {
+            Ok(rules) => rules,
+            Err(message) => return Some(Divergence::ReferenceFailed(message)),
+        };
+        let file = RuleFile::parse(input);
+        let ours: Vec<ReferenceRule> = file
+            .rules()
+            .filter(|(_, _, enabled)| *enabled)
+            .map(|(_, rule, _)| ReferenceRule::from_proxy_rule(rule))
+            .collect();
+        if ours.len() != theirs.len() {
+            return Some(Divergence::RuleCount { ours: ours.len(), theirs: theirs.len() });
+        }
+        let (index, (ours, theirs)) = ours.into_iter().zip(theirs).enumerate().find(|(_, (a, b))| a != b)?;
+        Some(Divergence::Rule { index, ours, theirs })
+    }
+
+    /// Compare `count` random inputs generated from `seed`, returning the
+    /// ones that diverge.
+    pub fn run(&self, seed: u64, count: usize) -> Vec<(String, Divergence)> {
+        random_inputs(seed)
+            .take(count)
+            .filter_map(|input| self.compare(&input).map(|d| (input, d)))
+            .collect()
+    }
+}
+
+/// Rule-like text built from fragments whistle gives meaning to, the same
+/// sequence for the same `seed`.
+pub fn random_inputs(seed: u64) -> impl Iterator<Item = String> {
+    const FRAGMENTS: &[&str] = &[
+        "a.com", "*.b.com", "/api", "http://", "file://", "reqHeaders://", "(x=1)", "{v}", "`${id}`",
+        ":8080", "127.0.0.1", " ", " ", "\n", "#", "!", "?q=1", "*", "^", "excludeFilter://m:post",
+    ];
+    // xorshift, which never reaches zero from a non-zero seed
+    let mut state = seed.max(1);
+    std::iter::repeat_with(move || {
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as usize
+        };
+        let len = 1 + next() % 12;
+        (0..len).map(|_| FRAGMENTS[next() % FRAGMENTS.len()]).collect()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// This crate as its own reference, which must always agree.
+    fn ourselves(input: &str) -> Result<Vec<ReferenceRule>, String> {
+        let file = RuleFile::parse(input);
+        Ok(file.rules().filter(|r| r.2).map(|(_, rule, _)| ReferenceRule::from_proxy_rule(rule)).collect())
+    }
+
+    #[test]
+    fn test_compare() {
+        assert_eq!(Differential::new(ourselves).run(7, 500), vec![]);
+
+        let without_ops = |input: &str| {
+            ourselves(input).map(|rules| {
+                rules.into_iter().map(|r| ReferenceRule { ops: r.ops[..1].to_vec(), ..r }).collect()
+            })
+        };
+        let differential = Differential::new(without_ops);
+        assert_eq!(differential.compare("file://x a.com"), None);
+        let Some(Divergence::Rule { index: 1, ours, theirs }) =
+            differential.compare("a.com 1.1.1.1\nb.com file://x reqHeaders://{h}")
+        else {
+            panic!("rule 1 should diverge");
+        };
+        assert_eq!(ours.ops[1], ("reqHeaders".to_string(), "{h}".to_string()));
+        assert_eq!(theirs.ops.len(), 1);
+    }
+
+    #[test]
+    fn test_reference_rule_from_json() {
+        let json = parse_json(r#"{"pattern": "a.com", "ops": [["host", "127.0.0.1"]]}"#).unwrap();
+        assert_eq!(
+            ReferenceRule::from_json(&json),
+            Some(ReferenceRule { pattern: "a.com".into(), ops: vec![("host".into(), "127.0.0.1".into())] })
+        );
+        let reference = NodeReference { node: "/nonexistent/node".into(), script: "x.js".into() };
+        assert!(matches!(
+            Differential::new(reference).compare("a.com b.com"),
+            Some(Divergence::ReferenceFailed(_))
+        ));
+    }
+}
@@ -0,0 +1,51 @@
+//! Text edits, the way refactorings and fixes hand their changes to an
+//! editor instead of rewriting whole documents.
+use crate::Span;
+
+/// Replace the text at `span` with `new_text`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub span: Span,
+    pub new_text: String,
+}
+
+impl TextEdit {
+    pub fn new(span: Span, new_text: impl Into<String>) -> Self {
+        TextEdit { span, new_text: new_text.into() }
+    }
+}
+
+/// `text` with `edits` applied, in any order. Spans refer to the original
+/// text, so edits don't shift each other; an edit overlapping an earlier one
+/// is skipped.
+pub fn apply_edits(text: &str, edits: &[TextEdit]) -> String {
+    let mut edits: Vec<&TextEdit> = edits.iter().collect();
+    edits.sort_by_key(|edit| (edit.span.start, edit.span.end));
+    let mut out = String::with_capacity(text.len());
+    let mut pos = 0;
+    for edit in edits {
+        if edit.span.start < pos || edit.span.end > text.len() {
+            continue;
+        }
+        out.push_str(&text[pos..edit.span.start]);
+        out.push_str(&edit.new_text);
+        pos = edit.span.end;
+    }
+    out.push_str(&text[pos..]);
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_apply_edits() {
+        let edits = [
+            TextEdit::new(Span::new(6, 11), "c.com"),
+            TextEdit::new(Span::new(0, 0), "# x\n"),
+            TextEdit::new(Span::new(8, 9), "overlap"),
+        ];
+        assert_eq!(apply_edits("a.com b.com", &edits), "# x\na.com c.com");
+    }
+}
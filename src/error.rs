@@ -0,0 +1,161 @@
+//! Errors reported by the `Result`-returning parse entry points.
+use std::fmt;
+
+use crate::Span;
+
+/// New kinds of error may be added in any release, so matches outside this
+/// crate need a `_` arm; [`WhistleParseError::code`] names the kind in a way
+/// that doesn't change between versions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum WhistleParseError {
+    /// The text doesn't have the shape of a rule at all.
+    Syntax { span: Span, kind: nom::error::ErrorKind },
+    /// A scheme that doesn't start with a letter or contains other
+    /// characters than letters, digits, `+`, `-` and `.`.
+    InvalidScheme { scheme: String, span: Span },
+    /// A host with characters that can't appear in a hostname or pattern.
+    InvalidHost { host: String, span: Span },
+    /// A port that's neither a number, a range, nor `*`.
+    InvalidPort { port: String, span: Span },
+    /// An `http://`-like uri without a host.
+    MissingHost { span: Span },
+    /// Text after the last operation that isn't an operation itself.
+    TrailingInput { text: String, span: Span },
+    /// A second pattern after a pattern-first rule, `a.com b.com c.com`.
+    /// Whistle only shares operations across patterns written after the
+    /// operation; see [`parse_multi_pattern`](crate::multi_pattern::parse_multi_pattern).
+    ExtraPattern { text: String, span: Span },
+    /// The input is larger than a configured limit allows; `span` covers
+    /// the part that's over it.
+    LimitExceeded { limit: Limit, max: usize, span: Span },
+    /// Bytes that aren't UTF-8.
+    InvalidUtf8 { span: Span },
+}
+
+/// Which of the [`Limits`](crate::Limits) was exceeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Limit {
+    InputSize,
+    LineLength,
+    TemplateParts,
+    IncludeDepth,
+}
+
+impl WhistleParseError {
+    /// A kebab-case name for the kind of error, `invalid-host` or
+    /// `trailing-input`. Codes are never renamed or reused, so tools can
+    /// key on them across versions.
+    pub fn code(&self) -> &'static str {
+        match self {
+            WhistleParseError::Syntax { .. } => "syntax",
+            WhistleParseError::InvalidScheme { .. } => "invalid-scheme",
+            WhistleParseError::InvalidHost { .. } => "invalid-host",
+            WhistleParseError::InvalidPort { .. } => "invalid-port",
+            WhistleParseError::MissingHost { .. } => "missing-host",
+            WhistleParseError::TrailingInput { .. } => "trailing-input",
+            WhistleParseError::ExtraPattern { .. } => "extra-pattern",
+            WhistleParseError::LimitExceeded { .. } => "limit-exceeded",
+            WhistleParseError::InvalidUtf8 { .. } => "invalid-utf8",
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            WhistleParseError::Syntax { span, .. }
+            | WhistleParseError::InvalidScheme { span, .. }
+            | WhistleParseError::InvalidHost { span, .. }
+            | WhistleParseError::InvalidPort { span, .. }
+            | WhistleParseError::MissingHost { span }
+            | WhistleParseError::TrailingInput { span, .. }
+            | WhistleParseError::ExtraPattern { span, .. }
+            | WhistleParseError::LimitExceeded { span, .. }
+            | WhistleParseError::InvalidUtf8 { span } => *span,
+        }
+    }
+
+    fn span_mut(&mut self) -> &mut Span {
+        match self {
+            WhistleParseError::Syntax { span, .. }
+            | WhistleParseError::InvalidScheme { span, .. }
+            | WhistleParseError::InvalidHost { span, .. }
+            | WhistleParseError::InvalidPort { span, .. }
+            | WhistleParseError::MissingHost { span }
+            | WhistleParseError::TrailingInput { span, .. }
+            | WhistleParseError::ExtraPattern { span, .. }
+            | WhistleParseError::LimitExceeded { span, .. }
+            | WhistleParseError::InvalidUtf8 { span } => span,
+        }
+    }
+
+    /// The same error with its span relative to a text in which the parsed
+    /// input starts at `base`.
+    pub fn shifted(mut self, base: usize) -> Self {
+        let span = self.span_mut();
+        *span = span.shifted(base);
+        self
+    }
+
+    /// Build a [`WhistleParseError::Syntax`] from a nom error on a slice
+    /// of `input`, spanning up to the end of the offending token.
+    pub fn from_nom(input: &str, err: nom::Err<nom::error::Error<&str>>) -> Self {
+        match err {
+            nom::Err::Error(e) | nom::Err::Failure(e) => {
+                let start = offset_in(input, e.input);
+                let len = e.input.find(char::is_whitespace).unwrap_or(e.input.len());
+                WhistleParseError::Syntax { span: Span::new(start, start + len), kind: e.code }
+            }
+            nom::Err::Incomplete(_) => WhistleParseError::Syntax {
+                span: Span::new(input.len(), input.len()),
+                kind: nom::error::ErrorKind::Eof,
+            },
+        }
+    }
+}
+
+/// Byte offset of `part` in `input`; sub-parsers often report errors on a
+/// slice of a token rather than on a suffix of the whole input.
+pub(crate) fn offset_in(input: &str, part: &str) -> usize {
+    let offset = (part.as_ptr() as usize).wrapping_sub(input.as_ptr() as usize);
+    if offset <= input.len() {
+        offset
+    } else {
+        input.len() - part.len().min(input.len())
+    }
+}
+
+impl fmt::Display for WhistleParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let span = self.span();
+        match self {
+            WhistleParseError::Syntax { kind, .. } => {
+                write!(f, "syntax error ({}) at {}", kind.description(), span.start)
+            }
+            WhistleParseError::InvalidScheme { scheme, .. } => {
+                write!(f, "invalid scheme `{scheme}` at {}", span.start)
+            }
+            WhistleParseError::InvalidHost { host, .. } => {
+                write!(f, "invalid host `{host}` at {}", span.start)
+            }
+            WhistleParseError::InvalidPort { port, .. } => {
+                write!(f, "invalid port `{port}` at {}", span.start)
+            }
+            WhistleParseError::MissingHost { .. } => write!(f, "missing host at {}", span.start),
+            WhistleParseError::TrailingInput { text, .. } => {
+                write!(f, "unexpected `{text}` at {}", span.start)
+            }
+            WhistleParseError::ExtraPattern { text, .. } => write!(
+                f,
+                "`{text}` at {} is a second pattern; write the operation first to share it",
+                span.start
+            ),
+            WhistleParseError::LimitExceeded { limit, max, .. } => {
+                write!(f, "{limit:?} limit of {max} exceeded at {}", span.start)
+            }
+            WhistleParseError::InvalidUtf8 { .. } => write!(f, "invalid UTF-8 at {}", span.start),
+        }
+    }
+}
+
+impl std::error::Error for WhistleParseError {}
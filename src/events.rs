@@ -0,0 +1,114 @@
+//! Read a rule file as a stream of callbacks instead of a [`RuleFile`].
+//!
+//! Lines are split off the input as they are read and dropped once their
+//! callback returns, so a consumer that only looks at each rule once, like
+//! a log enricher, never holds the parsed document; only a value block's
+//! body is gathered before its callback.
+//!
+//! [`RuleFile`]: crate::rule_file::RuleFile
+use crate::rule_file::{each_line, LineItem};
+use crate::{ParserOptions, ProxyRule, Span, Uri, WhistleParseError};
+
+/// Callbacks for [`parse_events`], each with the zero-based line number and
+/// document span of its line. Every callback does nothing by default; blank
+/// lines and lines with only a pattern have none.
+pub trait RuleEventHandler {
+    /// A rule, with `enabled` false for a `#off` one.
+    fn on_rule(&mut self, _number: usize, _span: Span, _rule: ProxyRule, _enabled: bool) {}
+
+    /// `# text`, with the text after `#`.
+    fn on_comment(&mut self, _number: usize, _span: Span, _text: &str) {}
+
+    fn on_value_block(&mut self, _number: usize, _span: Span, _key: &str, _body: &str) {}
+
+    /// `@url`.
+    fn on_include(&mut self, _number: usize, _span: Span, _uri: Uri) {}
+
+    fn on_error(&mut self, _number: usize, _span: Span, _error: &WhistleParseError) {}
+}
+
+/// Feed the lines of `input` to `handler` in order.
+pub fn parse_events(input: &str, handler: &mut impl RuleEventHandler) {
+    emit(input, &ParserOptions::default(), handler);
+}
+
+/// Like [`parse_events`], with the limits of `options` checked first.
+pub fn parse_events_with(
+    input: &str,
+    options: &ParserOptions,
+    handler: &mut impl RuleEventHandler,
+) -> Result<(), WhistleParseError> {
+    options.limits.check_input_size(input)?;
+    emit(input, options, handler);
+    Ok(())
+}
+
+fn emit(input: &str, options: &ParserOptions, handler: &mut impl RuleEventHandler) {
+    each_line(input, options, |line| {
+        let (number, span) = (line.number, line.span);
+        match line.item {
+            LineItem::Rule(rule) => handler.on_rule(number, span, rule, true),
+            LineItem::Disabled(rule) => handler.on_rule(number, span, rule, false),
+            LineItem::Comment(text) => handler.on_comment(number, span, &text),
+            LineItem::ValueBlock { key, body } => handler.on_value_block(number, span, &key, &body),
+            LineItem::Include(uri) => handler.on_include(number, span, uri),
+            LineItem::Invalid { error, .. } => handler.on_error(number, span, &error),
+            LineItem::PatternOnly(_) | LineItem::Blank => {}
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Default)]
+    struct Recorder(Vec<String>);
+
+    impl RuleEventHandler for Recorder {
+        fn on_rule(&mut self, number: usize, _span: Span, rule: ProxyRule, enabled: bool) {
+            self.0.push(format!("{number} rule {} {enabled}", rule.source));
+        }
+
+        fn on_comment(&mut self, number: usize, _span: Span, text: &str) {
+            self.0.push(format!("{number} comment {text}"));
+        }
+
+        fn on_value_block(&mut self, number: usize, span: Span, key: &str, body: &str) {
+            self.0.push(format!("{number} value {key}={body:?} {}..{}", span.start, span.end));
+        }
+
+        fn on_include(&mut self, number: usize, _span: Span, uri: Uri) {
+            self.0.push(format!("{number} include {uri}"));
+        }
+
+        fn on_error(&mut self, number: usize, _span: Span, _error: &WhistleParseError) {
+            self.0.push(format!("{number} error"));
+        }
+    }
+
+    #[test]
+    fn test_parse_events() {
+        let mut recorder = Recorder::default();
+        parse_events("# mocks\na.com b.com\n\n#off c.com d.com\n```x\n1\n```\na.com b.com c d\n@a.txt", &mut recorder);
+        assert_eq!(
+            recorder.0,
+            vec![
+                "0 comment mocks",
+                "1 rule a.com true",
+                "3 rule c.com false",
+                "4 value x=\"1\" 38..48",
+                "7 error",
+                "8 include a.txt",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_events_with_limits() {
+        let mut options = ParserOptions::default();
+        options.limits.max_input_size = Some(4);
+        let result = parse_events_with("a.com b.com", &options, &mut Recorder::default());
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,61 @@
+//! Pictures of where a [`RuleSet`] sends traffic.
+use std::fmt::Write;
+
+pub use crate::rule_set::ROUTING_PROTOCOLS;
+use crate::rule_set::RuleSet;
+
+/// A Graphviz digraph with a box per pattern and an edge for each routing
+/// operation, labeled with its protocol. Following edges from a target that
+/// is itself a pattern shows multi-hop setups.
+pub fn to_dot(rules: &RuleSet) -> String {
+    let mut out = String::from("digraph rules {\n");
+    let mut patterns: Vec<String> = vec![];
+    for rule in rules {
+        let pattern = rule.source.to_string();
+        if !patterns.contains(&pattern) {
+            let _ = writeln!(out, "    {} [shape=box];", dot_id(&pattern));
+            patterns.push(pattern);
+        }
+    }
+    for rule in rules {
+        let from = dot_id(&rule.source.to_string());
+        let target = (rule.target_protocol(), rule.target_value());
+        let ops = rule.rules.iter().map(|op| (op.name.as_str(), op.value.to_string()));
+        for (protocol, to) in std::iter::once(target).chain(ops) {
+            if ROUTING_PROTOCOLS.contains(&protocol) && !to.is_empty() {
+                let _ = writeln!(out, "    {from} -> {} [label={}];", dot_id(&to), dot_id(protocol));
+            }
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn dot_id(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rule_file::RuleFile;
+
+    #[test]
+    fn test_to_dot() {
+        let file = RuleFile::parse(
+            "a.com b.com\nb.com 127.0.0.1:8080 reqHeaders://{h}\nc.com file://x redirect://\"d.com/?q=\\\"\"",
+        );
+        assert_eq!(
+            to_dot(&file.rule_set()),
+            r#"digraph rules {
+    "a.com" [shape=box];
+    "b.com" [shape=box];
+    "c.com" [shape=box];
+    "a.com" -> "b.com" [label="host"];
+    "b.com" -> "127.0.0.1:8080" [label="host"];
+    "c.com" -> "d.com/?q=\"" [label="redirect"];
+}
+"#
+        );
+    }
+}
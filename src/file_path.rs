@@ -0,0 +1,153 @@
+//! Local paths in `file://` style values.
+//!
+//! Rule files are shared between Windows and unix machines, so a value is
+//! classified the same way on every platform and only turned into a native
+//! [`PathBuf`] at the end.
+use std::path::PathBuf;
+
+use crate::{OpValue, Uri};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilePathKind {
+    /// `D:\mock\a.html` or `D:/mock/a.html`.
+    Drive(char),
+    /// `\\server\share\a.html` or `//server/share/a.html`.
+    Unc { server: String, share: String },
+    /// `/mock/a.html`.
+    Absolute,
+    Relative,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilePath {
+    pub kind: FilePathKind,
+    pub segments: Vec<String>,
+}
+
+impl FilePath {
+    /// Classify `input`, accepting both `/` and `\` as separators.
+    pub fn parse(input: &str) -> FilePath {
+        let input = input.strip_prefix("file://").unwrap_or(input);
+        let is_sep = |c: char| c == '/' || c == '\\';
+        let segments = |s: &str| -> Vec<String> {
+            s.split(is_sep).filter(|s| !s.is_empty()).map(str::to_string).collect()
+        };
+
+        // `file:///D:/a` puts a separator in front of the drive
+        let trimmed = input.trim_start_matches(is_sep);
+        let mut chars = trimmed.chars();
+        if let (Some(letter), Some(':')) = (chars.next(), chars.next()) {
+            let rest = chars.as_str();
+            if letter.is_ascii_alphabetic() && (rest.is_empty() || rest.starts_with(is_sep)) {
+                return FilePath {
+                    kind: FilePathKind::Drive(letter.to_ascii_uppercase()),
+                    segments: segments(rest),
+                };
+            }
+        }
+
+        let leading = input.len() - trimmed.len();
+        if leading == 2 {
+            let mut parts = segments(trimmed).into_iter();
+            if let (Some(server), Some(share)) = (parts.next(), parts.next()) {
+                return FilePath {
+                    kind: FilePathKind::Unc { server, share },
+                    segments: parts.collect(),
+                };
+            }
+        }
+        FilePath {
+            kind: if leading > 0 { FilePathKind::Absolute } else { FilePathKind::Relative },
+            segments: segments(trimmed),
+        }
+    }
+
+    fn join(&self, sep: &str) -> String {
+        let prefix = match &self.kind {
+            FilePathKind::Drive(letter) => format!("{letter}:{sep}"),
+            FilePathKind::Unc { server, share } => format!("{sep}{sep}{server}{sep}{share}{sep}"),
+            FilePathKind::Absolute => sep.to_string(),
+            FilePathKind::Relative => String::new(),
+        };
+        prefix + &self.segments.join(sep)
+    }
+
+    pub fn to_windows_string(&self) -> String {
+        self.join("\\")
+    }
+
+    pub fn to_posix_string(&self) -> String {
+        self.join("/")
+    }
+
+    /// A path using the separators of the platform this runs on.
+    pub fn to_path_buf(&self) -> PathBuf {
+        if cfg!(windows) {
+            PathBuf::from(self.to_windows_string())
+        } else {
+            PathBuf::from(self.to_posix_string())
+        }
+    }
+}
+
+impl OpValue {
+    /// The local path of a raw `file://…`-style value.
+    pub fn as_file_path(&self) -> Option<FilePath> {
+        match self {
+            OpValue::Raw(s) if !s.is_empty() => Some(FilePath::parse(s)),
+            _ => None,
+        }
+    }
+}
+
+impl Uri {
+    /// The local path of a `file://` uri. The uri splitter reads a drive or
+    /// UNC path as the host, so the parts are joined back before parsing.
+    pub fn as_file_path(&self) -> Option<FilePath> {
+        if self.scheme != "file" {
+            return None;
+        }
+        Some(FilePath::parse(&format!("{}{}{}", self.host, self.path, self.query)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_file_path() {
+        let path = FilePath::parse(r"D:\mock\index.html");
+        assert_eq!(path.kind, FilePathKind::Drive('D'));
+        assert_eq!(path.to_windows_string(), r"D:\mock\index.html");
+        assert_eq!(FilePath::parse("file:///d:/mock/index.html"), path);
+
+        let path = FilePath::parse(r"\\server\share\mock\a.html");
+        assert_eq!(
+            path.kind,
+            FilePathKind::Unc { server: "server".into(), share: "share".into() }
+        );
+        assert_eq!(path.to_windows_string(), r"\\server\share\mock\a.html");
+        assert_eq!(path.to_posix_string(), "//server/share/mock/a.html");
+
+        assert_eq!(FilePath::parse("/tmp/a").to_posix_string(), "/tmp/a");
+        assert_eq!(FilePath::parse("mock/a").kind, FilePathKind::Relative);
+        #[cfg(not(windows))]
+        assert_eq!(FilePath::parse("/tmp//a").to_path_buf(), PathBuf::from("/tmp/a"));
+    }
+
+    #[test]
+    fn test_file_path_in_rule() {
+        let rule = crate::parse_proxy_rule(r"a.com file://D:\mock\index.html file://\\srv\s\f")
+            .unwrap()
+            .1;
+        assert_eq!(
+            rule.target.as_file_path().unwrap().to_windows_string(),
+            r"D:\mock\index.html"
+        );
+        assert_eq!(
+            rule.rules[0].value.as_file_path().unwrap().to_windows_string(),
+            r"\\srv\s\f"
+        );
+    }
+}
@@ -0,0 +1,182 @@
+//! `includeFilter://` and `excludeFilter://` values and their evaluation.
+use crate::matcher::{uri_matches, MatchOptions, Request, RequestUrl};
+use crate::pattern::{Pattern, PortMatcher, SchemeMatcher};
+use crate::{OpValue, ProxyRule};
+
+#[derive(Debug, Clone)]
+pub enum Filter {
+    /// `m:post`, compared ignoring case.
+    Method(String),
+    /// `h:x-test=1`, or `h:x-test` for any value. Names ignore case, the
+    /// value has to be contained in the header's.
+    Header { name: String, value: Option<String> },
+    /// `b:keyword`, contained in the body snippet.
+    Body(String),
+    /// `s:500`.
+    Status(u16),
+    /// `/regex/` or `/regex/i` over the url.
+    #[cfg(feature = "regex")]
+    UrlRegex(regex::Regex),
+    /// Any other value is a pattern the url has to match.
+    Url(Pattern),
+    /// A filter this build can't evaluate, such as a regex without the
+    /// `regex` feature. It never matches.
+    Unsupported(String),
+}
+
+impl Filter {
+    pub fn parse(value: &str) -> Filter {
+        let kind = value.split_once(':').filter(|(kind, _)| kind.len() == 1);
+        match kind {
+            Some(("m", method)) => return Filter::Method(method.to_string()),
+            Some(("h", header)) => {
+                let (name, value) = match header.split_once('=') {
+                    Some((name, value)) => (name, Some(value.to_string())),
+                    None => (header, None),
+                };
+                return Filter::Header { name: name.to_string(), value };
+            }
+            Some(("b", body)) => return Filter::Body(body.to_string()),
+            Some(("s", status)) => {
+                return match status.parse() {
+                    Ok(status) => Filter::Status(status),
+                    Err(_) => Filter::Unsupported(value.to_string()),
+                }
+            }
+            _ => {}
+        }
+        if let Some(regex) = value.strip_prefix('/').and_then(|v| v.rsplit_once('/')) {
+            return url_regex(value, regex);
+        }
+        match Pattern::parse(value) {
+            Ok(pattern) => Filter::Url(pattern),
+            Err(_) => Filter::Unsupported(value.to_string()),
+        }
+    }
+
+    /// Whether `request` passes the filter. Filters on what the request
+    /// doesn't carry, like a status before the response, don't match.
+    pub fn matches(&self, request: &Request, options: &MatchOptions) -> bool {
+        match self {
+            Filter::Method(method) => request.method.is_some_and(|m| m.eq_ignore_ascii_case(method)),
+            Filter::Header { name, value } => request.headers.iter().any(|(n, v)| {
+                n.eq_ignore_ascii_case(name) && value.as_deref().is_none_or(|value| v.contains(value))
+            }),
+            Filter::Body(keyword) => request.body.is_some_and(|body| body.contains(keyword.as_str())),
+            Filter::Status(status) => request.status == Some(*status),
+            #[cfg(feature = "regex")]
+            Filter::UrlRegex(regex) => regex.is_match(request.url),
+            Filter::Url(pattern) => pattern.matches_with(request.url, options),
+            Filter::Unsupported(_) => false,
+        }
+    }
+}
+
+#[cfg(feature = "regex")]
+fn url_regex(value: &str, (expr, flags): (&str, &str)) -> Filter {
+    regex::RegexBuilder::new(expr)
+        .case_insensitive(flags.contains('i'))
+        .build()
+        .map_or_else(|_| Filter::Unsupported(value.to_string()), Filter::UrlRegex)
+}
+
+#[cfg(not(feature = "regex"))]
+fn url_regex(value: &str, _: (&str, &str)) -> Filter {
+    Filter::Unsupported(value.to_string())
+}
+
+impl ProxyRule {
+    /// The filters of the rule's `includeFilter://` operations.
+    pub fn include_filters(&self) -> Vec<Filter> {
+        self.filters().0
+    }
+
+    /// The filters of the rule's `excludeFilter://` operations.
+    pub fn exclude_filters(&self) -> Vec<Filter> {
+        self.filters().1
+    }
+
+    /// The include and the exclude filters, read in one pass over the
+    /// operations as they are. Nothing is allocated for a rule without
+    /// filters.
+    pub(crate) fn filters(&self) -> (Vec<Filter>, Vec<Filter>) {
+        let is_filter = |name: &str| name == "includeFilter" || name == "excludeFilter";
+        let target = if is_filter(&self.target.scheme) { self.target_rule() } else { None };
+        let (mut include, mut exclude) = (vec![], vec![]);
+        for op in target.iter().chain(&self.rules).filter(|op| is_filter(&op.name)) {
+            let (OpValue::Raw(s) | OpValue::Inline(s)) = &op.value else {
+                continue;
+            };
+            match op.name.as_str() {
+                "includeFilter" => include.push(Filter::parse(s)),
+                _ => exclude.push(Filter::parse(s)),
+            }
+        }
+        (include, exclude)
+    }
+
+    /// Whether the pattern matches and the filters let `request` through:
+    /// one of the include filters, when there are any, has to match, and
+    /// none of the exclude filters. The filters are read again on every
+    /// call, a [`CompiledMatcher`](crate::compiled::CompiledMatcher) reads
+    /// them once.
+    pub fn applies_to(&self, request: &Request, options: &MatchOptions) -> bool {
+        let (source, url) = (&self.source, RequestUrl::parse(request.url));
+        if !SchemeMatcher::written_matches(&source.scheme, url.scheme)
+            || !uri_matches(source, PortMatcher::of_host(&source.host), &url, options)
+        {
+            return false;
+        }
+        let (include, exclude) = self.filters();
+        if !include.is_empty() && !include.iter().any(|f| f.matches(request, options)) {
+            return false;
+        }
+        !exclude.iter().any(|f| f.matches(request, options))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rule_set::RuleSet;
+
+    #[test]
+    fn test_filters() {
+        let rules: RuleSet = [
+            "a.com file://post includeFilter://m:post",
+            "a.com file://test includeFilter://h:x-test=1 excludeFilter://s:500",
+            "a.com file://body includeFilter://b:keyword",
+            "a.com file://api excludeFilter://a.com/static",
+            "a.com file://any",
+        ]
+        .iter()
+        .map(|line| crate::parse_proxy_rule(line).unwrap().1)
+        .collect();
+        let hosts = |request: &Request| -> Vec<String> {
+            rules.resolve(request).iter().map(|rule| rule.target.host.clone()).collect()
+        };
+
+        let mut request = Request::new("https://a.com/static/x.js");
+        assert_eq!(hosts(&request), ["any"]);
+        request.method = Some("POST");
+        request.headers = vec![("X-Test", "1,2")];
+        request.body = Some("...keyword...");
+        assert_eq!(hosts(&request), ["post", "test", "body", "any"]);
+        request.status = Some(500);
+        assert_eq!(hosts(&request), ["post", "body", "any"]);
+        assert_eq!(hosts(&Request::new("https://a.com/api")), ["api", "any"]);
+        assert!(rules.resolve(&Request::new("https://b.com/")).is_empty());
+
+        let rule = crate::parse_proxy_rule("a.com includeFilter://m:post excludeFilter://s:500").unwrap().1;
+        assert_eq!((rule.include_filters().len(), rule.exclude_filters().len()), (1, 1));
+        assert!(!rule.applies_to(&Request::new("http://a.com/"), &MatchOptions::default()));
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_regex_filter() {
+        let filter = Filter::parse(r"/\.JSON$/i");
+        assert!(filter.matches(&Request::new("http://a.com/x.json"), &MatchOptions::default()));
+        assert!(!filter.matches(&Request::new("http://a.com/x.js"), &MatchOptions::default()));
+    }
+}
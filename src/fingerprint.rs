@@ -0,0 +1,68 @@
+//! Stable identities for rules.
+use std::fmt;
+
+use crate::ProxyRule;
+
+/// A 64-bit FNV-1a hash, stable across runs, platforms and compiler
+/// versions, unlike `std`'s `DefaultHasher`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Fingerprint(pub u64);
+
+impl Fingerprint {
+    pub fn of(text: &str) -> Fingerprint {
+        let hash = text.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, b| {
+            (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+        });
+        Fingerprint(hash)
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl ProxyRule {
+    /// Hash of the [normalized](ProxyRule::normalize) rule's text, so the
+    /// same rule gets the same fingerprint however it was spaced or ordered
+    /// in the file it came from. Schemes and hosts are lowercased first, as
+    /// rules equal but for their case are `==`.
+    pub fn fingerprint(&self) -> Fingerprint {
+        let mut rule = self.normalize();
+        for uri in [&mut rule.source, &mut rule.target] {
+            uri.scheme.make_ascii_lowercase();
+            if uri.has_hostname() {
+                uri.host.make_ascii_lowercase();
+            }
+        }
+        Fingerprint::of(&rule.to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rule_file::{parse_lines, LineItem};
+
+    #[test]
+    fn test_fingerprint() {
+        let lines = parse_lines("a.com  b.com\tfile://x\n# note\n\u{3000}a.com b.com file://x \na.com b.com file://y");
+        let prints: Vec<_> = lines
+            .iter()
+            .filter_map(|line| match &line.item {
+                LineItem::Rule(rule) => Some(rule.fingerprint()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(prints.len(), 3);
+        assert_eq!(prints[0], prints[1]);
+        assert_ne!(prints[0], prints[2]);
+        assert_eq!(Fingerprint::of("a.com b.com file://x").to_string(), "13734053440f6384");
+
+        let fingerprint = |text: &str| crate::parse_proxy_rule(text).unwrap().1.fingerprint();
+        assert_eq!(fingerprint("file://x a.com"), fingerprint("a.com file://x"));
+        assert_eq!(fingerprint("A.com HTTP://B.com"), fingerprint("a.com http://b.com"));
+        assert_ne!(fingerprint("a.com file://X"), fingerprint("a.com file://x"));
+    }
+}
@@ -0,0 +1,207 @@
+//! `a=1&b=2` payloads of `params://`, `urlParams://`, `reqMerge://` and friends.
+use crate::json::{parse_json, JsonValue};
+use crate::markdown_values::ValueMap;
+use crate::{OpValue, Rule};
+
+/// Split a form-urlencoded string into its pairs, in order. Keys without `=`
+/// get an empty value and empty segments (`a=1&&b=2`) are skipped.
+pub fn parse_form(input: &str) -> Vec<(String, String)> {
+    input
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect()
+}
+
+/// The pairs of a `urlParams://` or `params://` payload, the string form of
+/// each member for a json object and [`parse_form`] otherwise.
+pub fn parse_params(input: &str) -> Vec<(String, String)> {
+    match parse_json(input) {
+        Ok(JsonValue::Object(members)) => {
+            let text = |v: &JsonValue| v.as_str().map_or_else(|| v.to_string(), str::to_string);
+            members.iter().map(|(k, v)| (k.clone(), text(v))).collect()
+        }
+        _ => parse_form(input),
+    }
+}
+
+/// Decode `%XX` escapes and `+` as a space. Malformed escapes are kept as is
+/// and invalid UTF-8 is replaced.
+pub fn percent_decode(input: &str) -> String {
+    String::from_utf8_lossy(&percent_decode_bytes(input, true)).into_owned()
+}
+
+/// Escape everything but the unreserved characters, the way `querystring`
+/// does, so a space becomes `%20`.
+pub fn percent_encode(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    for b in input.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'!' | b'~' | b'*'
+            | b'\'' | b'(' | b')' => out.push(b as char),
+            _ => out.push_str(&format!("%{b:02X}")),
+        }
+    }
+    out
+}
+
+/// The url the `urlParams://` or `params://` operation `op` sends
+/// `original_url` to, see [`merge_url_params`]. An op whose value is a
+/// `{key}` leaves the url as it is; resolve it with [`OpValue::as_form_in`]
+/// and merge the pairs instead.
+pub fn apply_url_params(original_url: &str, op: &Rule) -> String {
+    match op.value.as_form() {
+        Some(params) => merge_url_params(original_url, &params),
+        None => original_url.to_string(),
+    }
+}
+
+/// `original_url` with `params` merged into its query. A key already in
+/// the query is overwritten in place, every other pair is appended, and
+/// the rest of the query and the `#hash` are kept as they were written.
+pub fn merge_url_params(original_url: &str, params: &[(String, String)]) -> String {
+    let (url, fragment) = match original_url.split_once('#') {
+        Some((url, fragment)) => (url, Some(fragment)),
+        None => (original_url, None),
+    };
+    let (base, query) = url.split_once('?').unwrap_or((url, ""));
+    // the last value of a key repeated in the operation wins
+    let mut params: Vec<(&str, &str)> = params
+        .iter()
+        .enumerate()
+        .filter(|(i, (key, _))| !params[i + 1..].iter().any(|(k, _)| k == key))
+        .map(|(_, (key, value))| (key.as_str(), value.as_str()))
+        .collect();
+    let encode = |(key, value): (&str, &str)| format!("{}={}", percent_encode(key), percent_encode(value));
+
+    let mut pairs = vec![];
+    let mut replaced = vec![];
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let key = percent_decode(pair.split_once('=').map_or(pair, |(key, _)| key));
+        match params.iter().position(|(k, _)| *k == key) {
+            Some(i) => pairs.push(encode(params.remove(i))),
+            None if replaced.contains(&key) => {}
+            None => {
+                pairs.push(pair.to_string());
+                continue;
+            }
+        }
+        replaced.push(key);
+    }
+    pairs.extend(params.into_iter().map(encode));
+
+    let mut out = base.to_string();
+    if !pairs.is_empty() {
+        out.push('?');
+        out.push_str(&pairs.join("&"));
+    }
+    if let Some(fragment) = fragment {
+        out.push('#');
+        out.push_str(fragment);
+    }
+    out
+}
+
+pub(crate) fn percent_decode_bytes(input: &str, plus_as_space: bool) -> Vec<u8> {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' if plus_as_space => out.push(b' '),
+            b'%' => {
+                // `from_str_radix` also takes a sign, as in `%+1`
+                let hex = input
+                    .get(i + 1..i + 3)
+                    .filter(|h| h.bytes().all(|b| b.is_ascii_hexdigit()))
+                    .and_then(|h| u8::from_str_radix(h, 16).ok());
+                match hex {
+                    Some(b) => {
+                        out.push(b);
+                        i += 2;
+                    }
+                    None => out.push(b'%'),
+                }
+            }
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    out
+}
+
+impl OpValue {
+    /// The pairs of an inline `(a=1&b=2)` or raw payload, see
+    /// [`parse_params`].
+    pub fn as_form(&self) -> Option<Vec<(String, String)>> {
+        match self {
+            OpValue::Inline(s) | OpValue::Raw(s) => Some(parse_params(s)),
+            _ => None,
+        }
+    }
+
+    /// Like [`OpValue::as_form`], also reading `{key}` payloads from `values`.
+    pub fn as_form_in(&self, values: &ValueMap) -> Option<Vec<(String, String)>> {
+        match self {
+            OpValue::Value(key) => values.get(key).map(|body| parse_params(body.trim())),
+            _ => self.as_form(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_form() {
+        assert_eq!(
+            parse_form("b=2&a=1&&flag&name=J%C3%B6rg+K&bad=%zz&sign=%+1"),
+            vec![
+                ("b".into(), "2".into()),
+                ("a".into(), "1".into()),
+                ("flag".into(), "".into()),
+                ("name".into(), "Jörg K".into()),
+                ("bad".into(), "%zz".into()),
+                ("sign".into(), "% 1".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_apply_url_params() {
+        let rule = crate::parse_rule("urlParams://(lang=en&q=a%20b&lang=zh)").unwrap().1;
+        assert_eq!(
+            apply_url_params("http://a.com/s?q=x&page=2&q=y#top", &rule),
+            "http://a.com/s?q=a%20b&page=2&lang=zh#top"
+        );
+        assert_eq!(apply_url_params("http://a.com/", &rule), "http://a.com/?q=a%20b&lang=zh");
+        assert_eq!(merge_url_params("http://a.com/?a=%2F", &[]), "http://a.com/?a=%2F");
+        let reference = crate::parse_rule("params://{p}").unwrap().1;
+        assert_eq!(apply_url_params("http://a.com/?a=1", &reference), "http://a.com/?a=1");
+    }
+
+    #[test]
+    fn test_op_value_as_form() {
+        let rule = crate::parse_rule("urlParams://(x=1&y=%2F)").unwrap().1;
+        assert_eq!(
+            rule.value.as_form(),
+            Some(vec![("x".into(), "1".into()), ("y".into(), "/".into())])
+        );
+
+        let json = OpValue::Raw(r#"{"a":"x y","b":2,"c":null}"#.into());
+        assert_eq!(
+            json.as_form(),
+            Some(vec![("a".into(), "x y".into()), ("b".into(), "2".into()), ("c".into(), "null".into())])
+        );
+
+        let mut values = ValueMap::new();
+        values.insert("params", "a=1\n");
+        let value = OpValue::Value("params".into());
+        assert_eq!(value.as_form(), None);
+        assert_eq!(value.as_form_in(&values), Some(vec![("a".into(), "1".into())]));
+    }
+}
@@ -0,0 +1,119 @@
+//! `Arbitrary` for the AST, generating rules that serialize to text which
+//! parses back to the same value. Useful as seeds for fuzzers and for
+//! round-trip property tests.
+use ::arbitrary::{Arbitrary, Result, Unstructured};
+
+use crate::{OpValue, ProxyRule, Rule, TemplatePart, TemplateString, Uri};
+
+const WORD: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+const KEY: &[u8] = b"abcxyz0123456789._-";
+const HOST: &[u8] = b"abcxyz0123456789.-_*";
+const PATH: &[u8] = b"abcxyz019/._-~%";
+const QUERY: &[u8] = b"abcxyz019=&%";
+const RAW: &[u8] = b"abcXYZ019/._-:;=&%@!*~ ";
+const INLINE: &[u8] = b"abcXYZ019=&:,\"'[]";
+const TEMPLATE_TEXT: &[u8] = b"abcXYZ019/._-=&:";
+
+fn string_of(u: &mut Unstructured, chars: &[u8], min: usize, max: usize) -> Result<String> {
+    let len = u.int_in_range(min..=max)?;
+    (0..len).map(|_| u.choose(chars).map(|&c| c as char)).collect()
+}
+
+impl<'a> Arbitrary<'a> for Uri {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let scheme = match bool::arbitrary(u)? {
+            true => string_of(u, WORD, 1, 8)?,
+            false => String::new(),
+        };
+        let host = string_of(u, HOST, 1, 16)?;
+        let path = match bool::arbitrary(u)? {
+            true => format!("/{}", string_of(u, PATH, 0, 16)?),
+            false => String::new(),
+        };
+        // without a path the host would swallow the query
+        let query = match !path.is_empty() && bool::arbitrary(u)? {
+            true => format!("?{}", string_of(u, QUERY, 0, 12)?),
+            false => String::new(),
+        };
+        Ok(Uri { scheme, host, path, query })
+    }
+}
+
+impl<'a> Arbitrary<'a> for TemplateString {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let len = u.int_in_range(1..=4)?;
+        let mut parts: Vec<TemplatePart> = vec![];
+        for _ in 0..len {
+            let raw_allowed = !matches!(parts.last(), Some(TemplatePart::RawString(_)));
+            let part = match u.int_in_range(0..=2)? {
+                0 if raw_allowed => TemplatePart::RawString(string_of(u, TEMPLATE_TEXT, 1, 10)?),
+                0 | 1 => TemplatePart::Value(string_of(u, KEY, 1, 10)?),
+                _ => TemplatePart::Call {
+                    name: string_of(u, WORD, 1, 8)?,
+                    args: (0..u.int_in_range(0..=3)?)
+                        .map(|_| string_of(u, WORD, 1, 6))
+                        .collect::<Result<_>>()?,
+                },
+            };
+            parts.push(part);
+        }
+        Ok(TemplateString { parts })
+    }
+}
+
+impl<'a> Arbitrary<'a> for OpValue {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(match u.int_in_range(0..=3)? {
+            0 => OpValue::Inline(string_of(u, INLINE, 0, 12)?),
+            1 => OpValue::Value(string_of(u, KEY, 0, 12)?),
+            2 => OpValue::Raw(string_of(u, RAW, 0, 16)?),
+            _ => OpValue::TemplateString(TemplateString::arbitrary(u)?),
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Rule {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Rule {
+            name: string_of(u, WORD, 1, 10)?,
+            value: OpValue::arbitrary(u)?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for ProxyRule {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(ProxyRule {
+            source: Uri::arbitrary(u)?,
+            target: Uri::arbitrary(u)?,
+            rules: (0..u.int_in_range(0..=4)?)
+                .map(|_| Rule::arbitrary(u))
+                .collect::<Result<_>>()?,
+            origin: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parse_proxy_rule_complete, ParserOptions};
+
+    #[test]
+    fn test_round_trip() {
+        let mut seed = 0x9e37_79b9_7f4a_7c15_u64;
+        for _ in 0..5000 {
+            let bytes: Vec<u8> = (0..256)
+                .map(|_| {
+                    seed ^= seed << 13;
+                    seed ^= seed >> 7;
+                    seed ^= seed << 17;
+                    seed as u8
+                })
+                .collect();
+            let rule = ProxyRule::arbitrary(&mut Unstructured::new(&bytes)).unwrap();
+            let text = rule.to_string();
+            assert_eq!(parse_proxy_rule_complete(&text, &ParserOptions::default()), Ok(rule), "{text}");
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//! A form of [`RuleSet`] sharing repeated strings.
+//!
+//! Generated rule files repeat the same hosts and protocol names thousands
+//! of times; here each distinct one is stored once behind an `Arc<str>`.
+//! Operation values are mostly distinct and are kept as they are.
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::rule_set::RuleSet;
+use crate::{OpValue, ProxyRule, Rule, Uri};
+
+/// Hands out one `Arc<str>` per distinct string.
+#[derive(Debug, Clone, Default)]
+pub struct Interner {
+    strings: HashSet<Arc<str>>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn intern(&mut self, s: &str) -> Arc<str> {
+        if let Some(interned) = self.strings.get(s) {
+            return Arc::clone(interned);
+        }
+        let interned: Arc<str> = Arc::from(s);
+        self.strings.insert(Arc::clone(&interned));
+        interned
+    }
+
+    /// How many distinct strings are stored.
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct InternedUri {
+    pub scheme: Arc<str>,
+    pub host: Arc<str>,
+    pub path: Arc<str>,
+    pub query: Arc<str>,
+}
+
+impl InternedUri {
+    fn new(uri: &Uri, interner: &mut Interner) -> Self {
+        InternedUri {
+            scheme: interner.intern(&uri.scheme),
+            host: interner.intern(&uri.host),
+            path: interner.intern(&uri.path),
+            query: interner.intern(&uri.query),
+        }
+    }
+
+    pub fn to_uri(&self) -> Uri {
+        Uri {
+            scheme: self.scheme.to_string(),
+            host: self.host.to_string(),
+            path: self.path.to_string(),
+            query: self.query.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InternedOp {
+    pub name: Arc<str>,
+    pub value: OpValue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InternedRule {
+    pub source: InternedUri,
+    pub target: InternedUri,
+    pub rules: Vec<InternedOp>,
+}
+
+impl InternedRule {
+    pub fn new(rule: &ProxyRule, interner: &mut Interner) -> Self {
+        InternedRule {
+            source: InternedUri::new(&rule.source, interner),
+            target: InternedUri::new(&rule.target, interner),
+            rules: rule
+                .rules
+                .iter()
+                .map(|op| InternedOp { name: interner.intern(&op.name), value: op.value.clone() })
+                .collect(),
+        }
+    }
+
+    pub fn to_rule(&self) -> ProxyRule {
+        ProxyRule {
+            source: self.source.to_uri(),
+            target: self.target.to_uri(),
+            rules: self
+                .rules
+                .iter()
+                .map(|op| Rule { name: op.name.to_string(), value: op.value.clone() })
+                .collect(),
+            origin: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct InternedRuleSet {
+    pub rules: Vec<InternedRule>,
+    interner: Interner,
+}
+
+impl InternedRuleSet {
+    pub fn new(rules: &RuleSet) -> Self {
+        let mut interner = Interner::new();
+        let rules = rules.iter().map(|rule| InternedRule::new(rule, &mut interner)).collect();
+        InternedRuleSet { rules, interner }
+    }
+
+    /// Add a rule, sharing strings with the rules already in the set.
+    pub fn push(&mut self, rule: &ProxyRule) {
+        let rule = InternedRule::new(rule, &mut self.interner);
+        self.rules.push(rule);
+    }
+
+    pub fn interner(&self) -> &Interner {
+        &self.interner
+    }
+
+    pub fn to_rule_set(&self) -> RuleSet {
+        self.rules.iter().map(InternedRule::to_rule).collect()
+    }
+}
+
+impl From<&RuleSet> for InternedRuleSet {
+    fn from(rules: &RuleSet) -> Self {
+        InternedRuleSet::new(rules)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rule_file::RuleFile;
+
+    #[test]
+    fn test_interned_rule_set() {
+        let rules = RuleFile::parse("a.com file://x\na.com/b file://y reqHeaders://{h}\nc.com a.com").rule_set();
+        let interned = InternedRuleSet::new(&rules);
+        assert_eq!(interned.to_rule_set(), rules);
+        assert!(Arc::ptr_eq(&interned.rules[0].source.host, &interned.rules[1].source.host));
+        assert!(Arc::ptr_eq(&interned.rules[0].source.host, &interned.rules[2].target.host));
+        assert!(Arc::ptr_eq(&interned.rules[0].target.scheme, &interned.rules[1].target.scheme));
+        // "", a.com, file, x, /b, y, reqHeaders, c.com
+        assert_eq!(interned.interner().len(), 8);
+    }
+}
@@ -0,0 +1,443 @@
+//! A small JSON reader for operation payloads such as `resHeaders://{headers}`
+//! or `reqCors://({"origin":"*"})`.
+//!
+//! It keeps object keys in document order and reports errors with a [`Span`]
+//! into the payload, which callers can [`Span::shifted`] back into the rule
+//! line or values document the payload came from.
+use std::fmt;
+
+use crate::markdown_values::ValueMap;
+use crate::{OpValue, Span};
+
+/// Nesting deeper than this is rejected instead of recursing further.
+pub const MAX_JSON_DEPTH: usize = 128;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// Compact JSON, `{"a":[1,true]}`, keys in their order. Numbers without a
+/// fraction are written as integers, and ones JSON can't hold as `null`.
+impl fmt::Display for JsonValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut out = String::new();
+        self.write(&mut out, None, 0);
+        f.write_str(&out)
+    }
+}
+
+impl JsonValue {
+    /// JSON with two-space indentation and one member per line, for output
+    /// people diff.
+    pub fn to_pretty_string(&self) -> String {
+        let mut out = String::new();
+        self.write(&mut out, Some(2), 0);
+        out
+    }
+
+    fn write(&self, out: &mut String, indent: Option<usize>, depth: usize) {
+        let newline = |out: &mut String, depth: usize| {
+            if let Some(width) = indent {
+                out.push('\n');
+                out.push_str(&" ".repeat(width * depth));
+            }
+        };
+        match self {
+            JsonValue::Null => out.push_str("null"),
+            JsonValue::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+            JsonValue::Number(n) if !n.is_finite() => out.push_str("null"),
+            JsonValue::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => out.push_str(&(*n as i64).to_string()),
+            JsonValue::Number(n) => out.push_str(&n.to_string()),
+            JsonValue::String(s) => write_string(out, s),
+            JsonValue::Array(items) if items.is_empty() => out.push_str("[]"),
+            JsonValue::Object(fields) if fields.is_empty() => out.push_str("{}"),
+            JsonValue::Array(items) => {
+                out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    newline(out, depth + 1);
+                    item.write(out, indent, depth + 1);
+                }
+                newline(out, depth);
+                out.push(']');
+            }
+            JsonValue::Object(fields) => {
+                out.push('{');
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    newline(out, depth + 1);
+                    write_string(out, key);
+                    out.push_str(if indent.is_some() { ": " } else { ":" });
+                    value.write(out, indent, depth + 1);
+                }
+                newline(out, depth);
+                out.push('}');
+            }
+        }
+    }
+}
+
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JsonError {
+    Syntax { span: Span, message: String },
+    /// The value is a `{key}` that isn't in the values document.
+    MissingValue(String),
+    /// Only inline, `{key}` and raw values carry a JSON payload.
+    NotJson,
+}
+
+impl JsonError {
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            JsonError::Syntax { span, .. } => Some(*span),
+            _ => None,
+        }
+    }
+}
+
+pub fn parse_json(input: &str) -> Result<JsonValue, JsonError> {
+    let mut parser = JsonParser { input, pos: 0 };
+    parser.skip_ws();
+    let value = parser.value(0)?;
+    parser.skip_ws();
+    if parser.pos < input.len() {
+        return Err(parser.error_at(parser.pos, input.len(), "unexpected trailing characters"));
+    }
+    Ok(value)
+}
+
+impl OpValue {
+    /// Parse an inline `(…)` or raw payload as JSON.
+    pub fn as_json(&self) -> Result<JsonValue, JsonError> {
+        match self {
+            OpValue::Inline(s) | OpValue::Raw(s) => parse_json(s),
+            _ => Err(JsonError::NotJson),
+        }
+    }
+
+    /// Like [`OpValue::as_json`], also reading `{key}` payloads from `values`.
+    pub fn as_json_in(&self, values: &ValueMap) -> Result<JsonValue, JsonError> {
+        match self {
+            OpValue::Value(key) => {
+                let body = values
+                    .get(key)
+                    .ok_or_else(|| JsonError::MissingValue(key.clone()))?;
+                parse_json(body)
+            }
+            _ => self.as_json(),
+        }
+    }
+}
+
+struct JsonParser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> JsonParser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_ws(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start_matches([' ', '\t', '\n', '\r']).len();
+    }
+
+    fn error_at(&self, start: usize, end: usize, message: &str) -> JsonError {
+        JsonError::Syntax {
+            span: Span::new(start, end),
+            message: message.to_string(),
+        }
+    }
+
+    fn error_here(&self, message: &str) -> JsonError {
+        let end = self.pos + self.peek().map_or(0, char::len_utf8);
+        self.error_at(self.pos, end, message)
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), JsonError> {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error_here(&format!("expected `{c}`")))
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<JsonValue, JsonError> {
+        if depth > MAX_JSON_DEPTH {
+            return Err(self.error_here("nested too deeply"));
+        }
+        match self.peek() {
+            Some('{') => self.object(depth),
+            Some('[') => self.array(depth),
+            Some('"') => self.string().map(JsonValue::String),
+            Some('-' | '0'..='9') => self.number(),
+            Some(_) => self.keyword(),
+            None => Err(self.error_here("unexpected end of input")),
+        }
+    }
+
+    fn keyword(&mut self) -> Result<JsonValue, JsonError> {
+        for (word, value) in [
+            ("true", JsonValue::Bool(true)),
+            ("false", JsonValue::Bool(false)),
+            ("null", JsonValue::Null),
+        ] {
+            if self.rest().starts_with(word) {
+                self.pos += word.len();
+                return Ok(value);
+            }
+        }
+        Err(self.error_here("expected a value"))
+    }
+
+    fn object(&mut self, depth: usize) -> Result<JsonValue, JsonError> {
+        self.expect('{')?;
+        let mut fields = vec![];
+        self.skip_ws();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(JsonValue::Object(fields));
+        }
+        loop {
+            self.skip_ws();
+            if self.peek() != Some('"') {
+                return Err(self.error_here("expected a string key"));
+            }
+            let key = self.string()?;
+            self.skip_ws();
+            self.expect(':')?;
+            self.skip_ws();
+            let value = self.value(depth + 1)?;
+            fields.push((key, value));
+            self.skip_ws();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some('}') => {
+                    self.pos += 1;
+                    return Ok(JsonValue::Object(fields));
+                }
+                _ => return Err(self.error_here("expected `,` or `}`")),
+            }
+        }
+    }
+
+    fn array(&mut self, depth: usize) -> Result<JsonValue, JsonError> {
+        self.expect('[')?;
+        let mut items = vec![];
+        self.skip_ws();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(JsonValue::Array(items));
+        }
+        loop {
+            self.skip_ws();
+            items.push(self.value(depth + 1)?);
+            self.skip_ws();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(']') => {
+                    self.pos += 1;
+                    return Ok(JsonValue::Array(items));
+                }
+                _ => return Err(self.error_here("expected `,` or `]`")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, JsonError> {
+        let start = self.pos;
+        self.expect('"')?;
+        let mut out = String::new();
+        loop {
+            let at = self.pos;
+            match self.bump() {
+                None => return Err(self.error_at(start, self.pos, "unterminated string")),
+                Some('"') => return Ok(out),
+                Some('\\') => match self.bump() {
+                    Some('"') => out.push('"'),
+                    Some('\\') => out.push('\\'),
+                    Some('/') => out.push('/'),
+                    Some('b') => out.push('\u{8}'),
+                    Some('f') => out.push('\u{c}'),
+                    Some('n') => out.push('\n'),
+                    Some('r') => out.push('\r'),
+                    Some('t') => out.push('\t'),
+                    Some('u') => out.push(self.unicode_escape(at)?),
+                    _ => return Err(self.error_at(at, self.pos, "invalid escape")),
+                },
+                Some(c) if (c as u32) < 0x20 => {
+                    return Err(self.error_at(at, self.pos, "control character in string"))
+                }
+                Some(c) => out.push(c),
+            }
+        }
+    }
+
+    fn hex4(&mut self, at: usize) -> Result<u32, JsonError> {
+        let digits = self.rest().get(..4).filter(|d| d.chars().all(|c| c.is_ascii_hexdigit()));
+        match digits {
+            Some(d) => {
+                self.pos += 4;
+                Ok(u32::from_str_radix(d, 16).unwrap_or_default())
+            }
+            None => Err(self.error_at(at, self.pos, "invalid unicode escape")),
+        }
+    }
+
+    fn unicode_escape(&mut self, at: usize) -> Result<char, JsonError> {
+        let high = self.hex4(at)?;
+        let code = if (0xD800..0xDC00).contains(&high) {
+            if !self.rest().starts_with("\\u") {
+                return Err(self.error_at(at, self.pos, "unpaired surrogate"));
+            }
+            self.pos += 2;
+            let low = self.hex4(at)?;
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err(self.error_at(at, self.pos, "unpaired surrogate"));
+            }
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            high
+        };
+        char::from_u32(code).ok_or_else(|| self.error_at(at, self.pos, "invalid unicode escape"))
+    }
+
+    fn number(&mut self) -> Result<JsonValue, JsonError> {
+        let start = self.pos;
+        let len = self
+            .rest()
+            .find(|c: char| !(c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')))
+            .unwrap_or(self.rest().len());
+        let text = &self.rest()[..len];
+        let digits = text.strip_prefix('-').unwrap_or(text);
+        let leading_zero = digits.starts_with('0') && digits[1..].starts_with(|c: char| c.is_ascii_digit());
+        let valid = digits.starts_with(|c: char| c.is_ascii_digit()) && !leading_zero && !text.contains(".e") && !text.contains(".E");
+        match text.parse::<f64>() {
+            Ok(n) if valid && !text.ends_with('.') => {
+                self.pos += len;
+                Ok(JsonValue::Number(n))
+            }
+            _ => Err(self.error_at(start, start + len, "invalid number")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_json() {
+        let value = parse_json(r#" {"a": [1, -2.5e1, true, null], "b": "x\"é"} "#).unwrap();
+        assert_eq!(
+            value,
+            JsonValue::Object(vec![
+                (
+                    "a".into(),
+                    JsonValue::Array(vec![
+                        JsonValue::Number(1.0),
+                        JsonValue::Number(-25.0),
+                        JsonValue::Bool(true),
+                        JsonValue::Null,
+                    ])
+                ),
+                ("b".into(), JsonValue::String("x\"é".into())),
+            ])
+        );
+        assert_eq!(value.get("b").and_then(JsonValue::as_str), Some("x\"é"));
+
+        assert_eq!(
+            parse_json(r#"{"a":1 "b":2}"#),
+            Err(JsonError::Syntax { span: Span::new(7, 8), message: "expected `,` or `}`".into() })
+        );
+        assert_eq!(parse_json("[1]x").unwrap_err().span(), Some(Span::new(3, 4)));
+        assert!(parse_json("01").is_err());
+    }
+
+    #[test]
+    fn test_write_json() {
+        let text = r#"{"a":[1,-2.5,true,null],"b":"x\"\\\n\u0001é","c":{},"d":[]}"#;
+        let value = parse_json(text).unwrap();
+        assert_eq!(value.to_string(), text);
+        assert_eq!(parse_json(&value.to_pretty_string()), Ok(value));
+        assert_eq!(
+            parse_json(r#"{"a":[1],"b":{}}"#).unwrap().to_pretty_string(),
+            "{\n  \"a\": [\n    1\n  ],\n  \"b\": {}\n}"
+        );
+        assert_eq!(JsonValue::Number(f64::NAN).to_string(), "null");
+    }
+
+    #[test]
+    fn test_op_value_as_json() {
+        let rule = crate::parse_rule(r#"resHeaders://({"x-a":"1"})"#).unwrap().1;
+        assert_eq!(
+            rule.value.as_json().unwrap().get("x-a"),
+            Some(&JsonValue::String("1".into()))
+        );
+
+        let mut values = ValueMap::new();
+        values.insert("cors", r#"{"origin": "*"}"#);
+        let value = OpValue::Value("cors".into());
+        assert_eq!(value.as_json(), Err(JsonError::NotJson));
+        assert!(value.as_json_in(&values).unwrap().get("origin").is_some());
+        assert_eq!(
+            OpValue::Value("nope".into()).as_json_in(&values),
+            Err(JsonError::MissingValue("nope".into()))
+        );
+    }
+}
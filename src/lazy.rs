@@ -0,0 +1,195 @@
+//! Rules whose operation values are parsed on first use.
+//!
+//! Bulk matching only looks at patterns, so [`LazyProxyRule::parse`] splits
+//! the operations at their `://` and leaves values, templates included, as
+//! raw text until something asks for them. A value that doesn't parse is
+//! reported then, by [`LazyOpValue::get`].
+use std::sync::OnceLock;
+
+use nom::combinator::all_consuming;
+
+use crate::{
+    parse_rule_value, parse_uri, validate, Limits, OpValue, ParserOptions, ProxyRule, Rule, Span, Uri,
+    WhistleParseError,
+};
+
+/// An operation value kept as its text until [`LazyOpValue::get`].
+#[derive(Debug, Clone)]
+pub struct LazyOpValue {
+    raw: String,
+    span: Span,
+    limits: Limits,
+    value: OnceLock<Result<OpValue, WhistleParseError>>,
+}
+
+impl LazyOpValue {
+    pub fn new(raw: impl Into<String>, span: Span) -> Self {
+        LazyOpValue::with_limits(raw, span, Limits::default())
+    }
+
+    /// A value whose template is checked against `limits` when parsed.
+    pub fn with_limits(raw: impl Into<String>, span: Span, limits: Limits) -> Self {
+        LazyOpValue { raw: raw.into(), span, limits, value: OnceLock::new() }
+    }
+
+    pub fn raw(&self) -> &str {
+        &self.raw
+    }
+
+    /// Where the value is in the parsed line.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// The parsed value, parsing it on the first call. Errors have spans
+    /// in the parsed line, like the eager parser's.
+    pub fn get(&self) -> Result<&OpValue, &WhistleParseError> {
+        self.value
+            .get_or_init(|| {
+                let (_, value) = all_consuming(parse_rule_value)(&self.raw)
+                    .map_err(|e| WhistleParseError::from_nom(&self.raw, e).shifted(self.span.start))?;
+                if let OpValue::TemplateString(ts) = &value {
+                    self.limits.check_template_parts(ts.parts.len(), self.span)?;
+                }
+                Ok(value)
+            })
+            .as_ref()
+    }
+
+    pub fn is_parsed(&self) -> bool {
+        self.value.get().is_some()
+    }
+}
+
+impl PartialEq for LazyOpValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.raw == other.raw
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LazyRule {
+    pub name: String,
+    pub value: LazyOpValue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LazyProxyRule {
+    pub source: Uri,
+    pub target: Uri,
+    pub rules: Vec<LazyRule>,
+}
+
+impl LazyProxyRule {
+    /// Parse the pattern and target of a whole line, and split its
+    /// operations without parsing their values.
+    pub fn parse(input: &str) -> Result<LazyProxyRule, WhistleParseError> {
+        LazyProxyRule::parse_with(input, &ParserOptions::default())
+    }
+
+    /// Like [`LazyProxyRule::parse`], with the checks and aliases of
+    /// [`parse_proxy_rule_complete`](crate::parse_proxy_rule_complete).
+    /// Template limits are checked once a value is parsed.
+    pub fn parse_with(input: &str, options: &ParserOptions) -> Result<LazyProxyRule, WhistleParseError> {
+        options.limits.check_line_length(input)?;
+        let normalized = options.normalize_separators(input);
+        // separators are replaced byte for byte, so offsets carry over
+        let input = normalized.as_ref();
+        let tokens = crate::tokens(input);
+        let uri = |index: usize| -> Result<Uri, WhistleParseError> {
+            let (at, token) = tokens.get(index).copied().unwrap_or((input.len(), ""));
+            all_consuming(parse_uri)(token)
+                .map(|(_, uri)| uri)
+                .map_err(|e| WhistleParseError::from_nom(token, e).shifted(at))
+        };
+        let mut source = uri(0)?;
+        let mut target = uri(1)?;
+        if options.strict_uri {
+            validate::validate_uri(&source, tokens[0].0, true)?;
+            validate::validate_uri(&target, tokens[1].0, false)?;
+        }
+        for uri in [&mut source, &mut target] {
+            if let Some(protocol) = options.aliases.get(&uri.scheme) {
+                uri.scheme = protocol.to_string();
+            }
+        }
+        let mut rules = vec![];
+        for &(at, token) in tokens.iter().skip(2) {
+            let op = token
+                .split_once("://")
+                .filter(|(name, _)| !name.is_empty() && name.chars().all(crate::is_protocol_char));
+            let Some((name, value)) = op else {
+                if crate::multi_pattern::looks_like_pattern(token) {
+                    return Err(WhistleParseError::ExtraPattern {
+                        text: token.to_string(),
+                        span: Span::new(at, at + token.len()),
+                    });
+                }
+                return Err(WhistleParseError::TrailingInput {
+                    text: input[at..].trim_end().to_string(),
+                    span: Span::new(at, at + input[at..].trim_end().len()),
+                });
+            };
+            let start = at + name.len() + 3;
+            rules.push(LazyRule {
+                name: options.aliases.get(name).unwrap_or(name).to_string(),
+                value: LazyOpValue::with_limits(value, Span::new(start, start + value.len()), options.limits),
+            });
+        }
+        Ok(LazyProxyRule { source, target, rules })
+    }
+
+    /// The rule with every value parsed, or the first value's error.
+    pub fn to_rule(&self) -> Result<ProxyRule, WhistleParseError> {
+        let rules = self
+            .rules
+            .iter()
+            .map(|r| Ok(Rule { name: r.name.clone(), value: r.value.get().cloned().map_err(Clone::clone)? }))
+            .collect::<Result<_, WhistleParseError>>()?;
+        Ok(ProxyRule { source: self.source.clone(), target: self.target.clone(), rules, origin: None })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_lazy_rule() {
+        let line = "a.com/x file://{m} resBody://`${name}-x` reqHeaders://(a=1)";
+        let lazy = LazyProxyRule::parse(line).unwrap();
+        assert_eq!(lazy.source.path, "/x");
+        assert!(lazy.rules.iter().all(|r| !r.value.is_parsed()));
+        assert_eq!(lazy.rules[0].value.raw(), "`${name}-x`");
+        assert_eq!(lazy.rules[1].value.span(), Span::new(54, 59));
+        assert!(matches!(lazy.rules[0].value.get(), Ok(OpValue::TemplateString(_))));
+        assert!(lazy.rules[0].value.is_parsed());
+        assert_eq!(lazy.to_rule(), Ok(crate::parse_proxy_rule(line).unwrap().1));
+
+        assert!(matches!(
+            LazyProxyRule::parse("a.com b.com c.com"),
+            Err(WhistleParseError::ExtraPattern { span, .. }) if span == Span::new(12, 17)
+        ));
+    }
+
+    #[test]
+    fn test_lazy_errors_and_options() {
+        let line = "a.com b.com file://`${a}${b}` reqHeaders://`x";
+        let lazy = LazyProxyRule::parse(line).unwrap();
+        let eager = crate::parse_proxy_rule_complete(line, &ParserOptions::default());
+        assert_eq!(lazy.rules[1].value.get(), eager.as_ref().map(|r| &r.rules[1].value));
+
+        let mut options = ParserOptions::default().with_alias("mock", "file");
+        options.limits.max_template_parts = Some(1);
+        let lazy = LazyProxyRule::parse_with("a.com b.com mock://`${a}${b}`", &options).unwrap();
+        assert_eq!(lazy.rules[0].name, "file");
+        assert!(matches!(lazy.to_rule(), Err(WhistleParseError::LimitExceeded { .. })));
+        options.limits.max_line_length = Some(8);
+        assert!(LazyProxyRule::parse_with("a.com b.com", &options).is_err());
+        let strict = ParserOptions::strict();
+        assert_eq!(
+            LazyProxyRule::parse_with("a.com http:///x", &strict),
+            Err(WhistleParseError::MissingHost { span: Span::new(13, 13) })
+        );
+    }
+}
@@ -1,20 +1,155 @@
-use nom::character::is_space;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
 use nom::combinator::all_consuming;
 use nom::error::{ErrorKind, ParseError};
 use nom::Err::Error;
-use nom::character::complete::none_of;
+use nom::character::complete::one_of;
 use nom::multi::separated_list0;
 use nom::Parser;
-use nom::{branch::alt, multi::many0, sequence::delimited};
+use nom::{branch::alt, sequence::delimited};
 use nom::{
-    bytes::complete::{tag, take_until, take_till1, take_while, take_while1},
-    character::complete::{multispace0, char as char1},
-    combinator::{opt, map},
+    bytes::complete::{tag, take_until, take_while, take_while1},
+    character::complete::char as char1,
+    combinator::{opt, map, map_parser, verify},
     sequence::{preceded, terminated, tuple},
     IResult,
 };
 
+pub mod alias;
+#[cfg(feature = "typed-ops")]
+pub mod attachment;
+#[cfg(feature = "typed-ops")]
+pub mod audit;
+#[cfg(feature = "markdown")]
+mod base64;
+#[cfg(feature = "data-uri")]
+pub mod data_uri;
+pub mod builder;
+#[cfg(feature = "typed-ops")]
+pub mod cache;
+#[cfg(feature = "convert")]
+pub mod canonical;
+#[cfg(feature = "typed-ops")]
+pub mod category;
+#[cfg(feature = "typed-ops")]
+pub mod compat;
+pub mod compiled;
+#[cfg(all(feature = "serde", feature = "convert"))]
+pub mod config;
+pub mod conformance;
+#[cfg(feature = "typed-ops")]
+pub mod cookie;
+#[cfg(feature = "typed-ops")]
+pub mod cors;
+#[cfg(feature = "typed-ops")]
+pub mod delete;
+#[cfg(feature = "typed-ops")]
+pub mod diagnostic;
+#[cfg(feature = "differential")]
+pub mod differential;
+mod error;
+pub mod edit;
+pub mod events;
+#[cfg(feature = "convert")]
+pub mod export;
+pub mod file_path;
+pub mod filter;
+pub mod fingerprint;
+#[cfg(feature = "typed-ops")]
+pub mod form;
+#[cfg(feature = "arbitrary")]
+mod fuzz;
+pub mod intern;
+#[cfg(feature = "convert")]
+pub mod json;
+pub mod lazy;
+pub mod line_index;
+#[cfg(feature = "typed-ops")]
+pub mod lint;
+pub mod loader;
+#[cfg(feature = "markdown")]
 pub mod markdown_values;
+pub mod matcher;
+pub mod meta;
+#[cfg(feature = "typed-ops")]
+pub mod method;
+#[cfg(feature = "typed-ops")]
+pub mod mime;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+pub mod multi_pattern;
+mod normalize;
+mod options;
+#[cfg(feature = "typed-ops")]
+pub mod ops;
+pub mod origin;
+pub mod pattern;
+#[cfg(feature = "typed-ops")]
+pub mod plugin;
+pub mod preprocess;
+#[cfg(feature = "pretty-errors")]
+pub mod pretty;
+#[cfg(feature = "typed-ops")]
+pub mod proxy;
+#[cfg(feature = "typed-ops")]
+pub mod redirect;
+#[cfg(feature = "typed-ops")]
+pub mod registry;
+#[cfg(feature = "render")]
+pub mod render;
+#[cfg(feature = "typed-ops")]
+pub mod replace;
+#[cfg(feature = "tokio")]
+pub mod resolver;
+pub mod rule_file;
+pub mod rule_set;
+#[cfg(feature = "markdown")]
+pub mod script;
+pub mod search;
+#[cfg(feature = "markdown")]
+mod sha256;
+#[cfg(feature = "arc-swap")]
+pub mod shared;
+pub mod validate;
+#[cfg(feature = "markdown")]
+pub mod value_store;
+#[cfg(feature = "markdown")]
+pub mod workspace;
+
+pub use error::{Limit, WhistleParseError};
+pub use options::{Limits, ParserOptions};
+#[cfg(feature = "macros")]
+pub use whistle_proxy_rule_parser_macros::{proxy_rule, rules};
+use origin::Origin;
+
+/// Byte range into the text a node was parsed from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+
+    /// The same range, relative to a text in which this one starts at `base`.
+    pub fn shifted(self, base: usize) -> Self {
+        Span::new(self.start + base, self.end + base)
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Uri {
@@ -24,18 +159,77 @@ pub struct Uri {
     pub query: String,
 }
 
-impl ToString for Uri {
-    fn to_string(&self) -> String {
-        format!("{}://{}{}{}", self.scheme, self.host, self.path, self.query)
+impl fmt::Display for Uri {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rest = format!("{}{}{}", self.host, self.path, self.query);
+        if !self.scheme.is_empty() {
+            write!(f, "{}://", self.scheme)?;
+        }
+        write!(f, "{}", quote_if_needed(&rest))
     }
 }
+/// Uris compare the way whistle reads them: the scheme ignoring ASCII case,
+/// the host too when it's a hostname (no scheme, `*://` or an http-like
+/// one) rather than the value of an operation like `file://`, and the path
+/// and query exactly. `Hash` and `Ord` agree with this, and order by
+/// scheme, host, path, then query. Other equivalent spellings, like a
+/// trailing `/`, only compare equal once [normalized](ProxyRule::normalize).
 impl PartialEq for Uri {
     fn eq(&self, other: &Self) -> bool {
-        self.scheme == other.scheme && self.host == other.host && self.path == other.path && self.query == other.query
+        self.scheme.eq_ignore_ascii_case(&other.scheme)
+            && if self.has_hostname() { self.host.eq_ignore_ascii_case(&other.host) } else { self.host == other.host }
+            && self.path == other.path
+            && self.query == other.query
     }
 }
 
-#[derive(Debug, Clone)]
+impl Eq for Uri {}
+
+impl Uri {
+    fn has_hostname(&self) -> bool {
+        let scheme = self.scheme.as_str();
+        scheme.is_empty() || scheme == "*" || validate::HOST_SCHEMES.iter().any(|s| s.eq_ignore_ascii_case(scheme))
+    }
+}
+
+impl Hash for Uri {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.scheme.bytes().for_each(|b| state.write_u8(b.to_ascii_lowercase()));
+        // keeps `ab` + `c` apart from `a` + `bc`
+        state.write_u8(0xff);
+        if self.has_hostname() {
+            self.host.bytes().for_each(|b| state.write_u8(b.to_ascii_lowercase()));
+            state.write_u8(0xff);
+        } else {
+            self.host.hash(state);
+        }
+        self.path.hash(state);
+        self.query.hash(state);
+    }
+}
+
+impl Ord for Uri {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let cmp_lower = |a: &str, b: &str| {
+            a.bytes().map(|b| b.to_ascii_lowercase()).cmp(b.bytes().map(|b| b.to_ascii_lowercase()))
+        };
+        cmp_lower(&self.scheme, &other.scheme)
+            .then_with(|| match self.has_hostname() {
+                true => cmp_lower(&self.host, &other.host),
+                false => self.host.cmp(&other.host),
+            })
+            .then_with(|| self.path.cmp(&other.path))
+            .then_with(|| self.query.cmp(&other.query))
+    }
+}
+
+impl PartialOrd for Uri {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum OpValue {
     Inline(String),
     Value(String),
@@ -43,19 +237,21 @@ pub enum OpValue {
     TemplateString(TemplateString),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Rule {
     pub name: String,
     pub value: OpValue,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum TemplatePart {
     RawString(String),
     Value(String),
+    /// `${name(arg1, arg2)}`, evaluated by a function registered on the render context.
+    Call { name: String, args: Vec<String> },
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct TemplateString {
     pub parts: Vec<TemplatePart>,
 }
@@ -65,6 +261,156 @@ pub struct ProxyRule {
   pub source: Uri,
   pub target: Uri,
   pub rules: Vec<Rule>,
+  /// Where the rule was written, when it was read from a named file or group.
+  pub origin: Option<Origin>,
+}
+
+/// Rules compare by what they do, not by where they were written: `Eq`,
+/// `Hash` and `Ord` all ignore the origin, and order by pattern, target,
+/// then operations.
+impl PartialEq for ProxyRule {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source && self.target == other.target && self.rules == other.rules
+    }
+}
+
+impl Eq for ProxyRule {}
+
+impl Hash for ProxyRule {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.source.hash(state);
+        self.target.hash(state);
+        self.rules.hash(state);
+    }
+}
+
+impl Ord for ProxyRule {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.source
+            .cmp(&other.source)
+            .then_with(|| self.target.cmp(&other.target))
+            .then_with(|| self.rules.cmp(&other.rules))
+    }
+}
+
+impl PartialOrd for ProxyRule {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for TemplatePart {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplatePart::RawString(s) => f.write_str(s),
+            TemplatePart::Value(name) => write!(f, "${{{name}}}"),
+            TemplatePart::Call { name, args } => write!(f, "${{{}({})}}", name, args.join(",")),
+        }
+    }
+}
+
+/// The template as written between backticks, with `\` escapes wherever
+/// raw text would otherwise read as a backtick, an expression or the
+/// `(…)` wrapping.
+impl fmt::Display for TemplateString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut out = String::new();
+        for (index, part) in self.parts.iter().enumerate() {
+            let TemplatePart::RawString(raw) = part else {
+                out.push_str(&part.to_string());
+                continue;
+            };
+            // the first character after this part, escapes included
+            let after = match self.parts.get(index + 1) {
+                Some(TemplatePart::RawString(next)) => next.chars().next(),
+                Some(_) => Some('$'),
+                None => None,
+            };
+            let mut chars = raw.chars().peekable();
+            while let Some(c) = chars.next() {
+                let next = chars.peek().copied().or(after);
+                let escape = match c {
+                    '`' => true,
+                    '\\' => next.is_none_or(|n| TEMPLATE_ESCAPES.contains(n)),
+                    '$' => next == Some('{'),
+                    '(' => out.is_empty(),
+                    _ => false,
+                };
+                if escape {
+                    out.push('\\');
+                }
+                out.push(c);
+            }
+        }
+        f.write_str(&out)
+    }
+}
+
+impl fmt::Display for OpValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OpValue::Inline(s) => write!(f, "({s})"),
+            OpValue::Value(s) => write!(f, "{{{s}}}"),
+            OpValue::Raw(s) => write!(f, "{}", quote_if_needed(s)),
+            OpValue::TemplateString(ts) => write!(f, "`{ts}`"),
+        }
+    }
+}
+
+impl OpValue {
+    /// `(s)`, a value written on the line.
+    pub fn inline(s: impl Into<String>) -> Self {
+        OpValue::Inline(s.into())
+    }
+
+    /// `{key}`, a reference to a value block.
+    pub fn value_ref(key: impl Into<String>) -> Self {
+        OpValue::Value(key.into())
+    }
+
+    pub fn raw(s: impl Into<String>) -> Self {
+        OpValue::Raw(s.into())
+    }
+
+    pub fn template(parts: impl IntoIterator<Item = TemplatePart>) -> Self {
+        OpValue::TemplateString(TemplateString { parts: parts.into_iter().collect() })
+    }
+
+    /// The bytes of a value written on the line, `None` for a `{key}` or a
+    /// template that has to be resolved first. A value block's bytes, which
+    /// can be base64, come from `ValueMap::bytes`.
+    pub fn as_bytes(&self) -> Option<Cow<'_, [u8]>> {
+        match self {
+            OpValue::Raw(s) | OpValue::Inline(s) => Some(Cow::Borrowed(s.as_bytes())),
+            OpValue::Value(_) | OpValue::TemplateString(_) => None,
+        }
+    }
+}
+
+/// Read a value the way it's written after `name://`, so `(…)`, `{…}`,
+/// `` `…` `` and quotes pick the variant. The value must be a single token.
+impl TryFrom<&str> for OpValue {
+    type Error = WhistleParseError;
+
+    fn try_from(input: &str) -> Result<Self, Self::Error> {
+        all_consuming(parse_rule_value)(input)
+            .map(|(_, value)| value)
+            .map_err(|e| WhistleParseError::from_nom(input, e))
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}://{}", self.name, self.value)
+    }
+}
+
+/// The rule as one whistle line, operations separated by a space.
+impl fmt::Display for ProxyRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.source, self.target)?;
+        self.rules.iter().try_for_each(|rule| write!(f, " {rule}"))
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -92,47 +438,184 @@ fn whitespace<'a, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, &'a st
   take_while1(|c: char| c.is_whitespace())(i)
 }
 
-fn not_space(s: &str) -> IResult<&str, &str> {
-  take_while1(|c:char| !c.is_whitespace())(s)
+/// A `"…"` string, where `\"` stands for a quote and every other backslash is
+/// literal so Windows paths can be quoted as they are.
+pub fn parse_quoted(input: &str) -> IResult<&str, String> {
+    let (body, _) = char1('"')(input)?;
+    let mut out = String::new();
+    let mut chars = body.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((&body[i + 1..], out)),
+            '\\' if body[i + 1..].starts_with('"') => {
+                chars.next();
+                out.push('"');
+            }
+            c => out.push(c),
+        }
+    }
+    Err(Error(nom::error::Error::new(input, ErrorKind::Char)))
 }
 
+/// Wrap `s` in quotes when it couldn't be read back as a single token.
+pub fn quote_if_needed(s: &str) -> std::borrow::Cow<'_, str> {
+    if s.contains(|c: char| c.is_whitespace()) || s.starts_with('"') {
+        format!("\"{}\"", s.replace('"', "\\\"")).into()
+    } else {
+        s.into()
+    }
+}
+
+/// Whether `c` can be part of a protocol name, `https-proxy` and plugin
+/// names like `whistle.mock` included.
+pub(crate) fn is_protocol_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '-' || c == '.'
+}
+
+/// The text up to the next whitespace, except inside a `"…"` string that
+/// starts the token or follows its `name://`.
+fn token0(input: &str) -> IResult<&str, &str> {
+    let scheme_len = input.find(|c: char| !is_protocol_char(c)).unwrap_or(input.len());
+    let quote_at = if input.starts_with('"') {
+        Some(0)
+    } else if scheme_len > 0 && input[scheme_len..].starts_with("://\"") {
+        Some(scheme_len + 3)
+    } else {
+        None
+    };
+    // an unterminated quote is just a character
+    let start = match quote_at.map(|at| parse_quoted(&input[at..])) {
+        Some(Ok((rest, _))) => input.len() - rest.len(),
+        _ => 0,
+    };
+    let end = input[start..]
+        .find(char::is_whitespace)
+        .map_or(input.len(), |i| start + i);
+    Ok((&input[end..], &input[..end]))
+}
+
+/// Succeeds at the end of the input or before whitespace, so that e.g.
+/// `(a)b` isn't read as an inline `(a)` followed by nothing.
+fn token_end(input: &str) -> IResult<&str, ()> {
+    match input.chars().next() {
+        None => Ok((input, ())),
+        Some(c) if c.is_whitespace() => Ok((input, ())),
+        Some(_) => Err(Error(nom::error::Error::new(input, ErrorKind::Eof))),
+    }
+}
+
+pub(crate) fn token(input: &str) -> IResult<&str, &str> {
+    verify(token0, |s: &str| !s.is_empty())(input)
+}
+
+/// Characters a `\` escapes inside a template string. A backslash before
+/// anything else is kept, so `D:\mock\${name}` stays a windows path.
+pub const TEMPLATE_ESCAPES: &str = "\\`$(";
+
 pub fn parse_escaped(input: &str) -> IResult<&str, TemplatePart> {
     let (input, _) = tag("\\")(input)?;
-    let (input, escaped) = none_of("\\")(input)?;
+    let (input, escaped) = one_of(TEMPLATE_ESCAPES)(input)?;
     Ok((input, TemplatePart::RawString(escaped.to_string())))
 }
 
-pub fn parse_template_string(input: &str) -> IResult<&str, TemplateString> {
-    let original_input = input;
-    let (mut input, bracket) = opt(char1('('))(input)?;
-    if bracket.is_some() {
-        input = input.strip_suffix(")").expect(&format!("{original_input} format is wrong"));
+pub fn parse_template_call(input: &str) -> IResult<&str, TemplatePart> {
+    let (input, (name, args)) = tuple((
+        take_while1(|c: char| c.is_alphanumeric() || c == '_'),
+        delimited(char1('('), take_until(")"), char1(')')),
+    ))(input)?;
+    let args = if args.trim().is_empty() {
+        vec![]
+    } else {
+        args.split(',').map(|a| a.trim().to_string()).collect()
+    };
+    Ok((input, TemplatePart::Call { name: name.to_string(), args }))
+}
+
+pub(crate) fn template_expr(s: &str) -> TemplatePart {
+    match all_consuming(parse_template_call)(s) {
+        Ok((_, call)) => call,
+        Err(_) => TemplatePart::Value(s.to_string()),
     }
-    let (mut input, mut parts) = many0(
-        nom::branch::alt((
-            parse_escaped,
-            map(preceded(tag("${"), terminated(take_until("}"), tag("}"))), |s: &str| TemplatePart::Value(s.to_string())),
-            map(take_until("${"), |s: &str| TemplatePart::RawString(s.to_string())),
-        )),
-    )(input)?;
+}
+
+pub fn parse_template_string(input: &str) -> IResult<&str, TemplateString> {
+    let inner = input.strip_prefix('(').and_then(|s| s.strip_suffix(')'));
+    parse_template_parts(inner.unwrap_or(input))
+}
 
-    if parts.is_empty() {
-      // let chars: Vec<char> = input.chars().collect();
-      // let s: String = chars[1..chars.len()-1].into_iter().collect();
-      parts.push(TemplatePart::RawString(input.to_string()));
-      input = "";
+/// Split `input` into raw text and `${…}` parts, without the `(…)` wrapping
+/// handled by [`parse_template_string`].
+pub fn parse_template_parts(input: &str) -> IResult<&str, TemplateString> {
+    let mut parts = vec![];
+    let mut raw = String::new();
+    let mut rest = input;
+    while let Some(c) = rest.chars().next() {
+        if let Ok((next, TemplatePart::RawString(escaped))) = parse_escaped(rest) {
+            raw.push_str(&escaped);
+            rest = next;
+            continue;
+        }
+        // an unterminated `${` is plain text
+        let expr: IResult<&str, &str> = preceded(tag("${"), terminated(take_until("}"), tag("}")))(rest);
+        if let Ok((next, expr)) = expr {
+            if !raw.is_empty() {
+                parts.push(TemplatePart::RawString(std::mem::take(&mut raw)));
+            }
+            parts.push(template_expr(expr));
+            rest = next;
+            continue;
+        }
+        raw.push(c);
+        rest = &rest[c.len_utf8()..];
     }
+    if !raw.is_empty() || parts.is_empty() {
+        parts.push(TemplatePart::RawString(raw));
+    }
+    Ok((rest, TemplateString { parts }))
+}
 
-    Ok((input, TemplateString { parts }))
+/// The text of a `` `…` `` template up to its closing backtick, which a `\`
+/// escapes.
+fn template_body(input: &str) -> IResult<&str, &str> {
+    let mut chars = input.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '`' => return Ok((&input[i..], &input[..i])),
+            c if c.is_whitespace() => return Ok((&input[i..], &input[..i])),
+            _ => {}
+        }
+    }
+    Ok(("", input))
 }
 
 pub fn parse_uri(input: &str) -> IResult<&str, Uri> {
-    let (input, (scheme, host, path, query)) = tuple((
-        opt(terminated(
-            take_while1(|c: char| c.is_alphanumeric()),
-            tag("://"),
-        )),
-        opt(take_while1(|c: char| c != '/')),
+    // `*://` is the explicit spelling of a pattern matching any scheme
+    let (input, scheme) = opt(terminated(
+        alt((take_while1(is_protocol_char), tag("*"))),
+        tag("://"),
+    ))(input)?;
+    let scheme = scheme.unwrap_or_default().to_string();
+
+    // `file://"C:\My Mocks\a.html"` keeps the quoted text as the path
+    if let Ok((input, path)) = parse_quoted(input) {
+        return Ok((
+            input,
+            Uri {
+                scheme,
+                host: String::new(),
+                path,
+                query: String::new(),
+            },
+        ));
+    }
+
+    // an empty authority, `file:///mock`, leaves the host empty and the
+    // path starting at the third slash
+    let (input, (host, path, query)) = tuple((
+        opt(take_while1(|c: char| c != '/' && c != '?')),
         take_while(|c: char| c != '?'),
         take_while(|c: char| !c.is_whitespace()),
     ))(input)?;
@@ -140,7 +623,7 @@ pub fn parse_uri(input: &str) -> IResult<&str, Uri> {
     Ok((
         input,
         Uri {
-            scheme: scheme.unwrap_or_default().to_string(),
+            scheme,
             host: host.unwrap_or_default().to_string(),
             path: path.to_string(),
             query: query.to_string(),
@@ -150,10 +633,11 @@ pub fn parse_uri(input: &str) -> IResult<&str, Uri> {
 
 pub fn parse_rule_value(input: &str) -> IResult<&str, OpValue> {
     let (input, opval) = alt((
-        map(delimited(char1('`'), take_while(|c: char|c != ' ' && c != '\t' && c != '`'), char1('`')), |s:&str| OpValue::TemplateString(parse_template_string(s).unwrap().1)),
-        map(delimited(char1('('), take_while(|c: char|c != ' ' && c != '\t' && c != ')'), char1(')')), |s:&str| OpValue::Inline(s.to_string())),
-        map(delimited(char1('{'), take_while(|c: char|c != ' ' && c != '\t' && c != '}'), char1('}')), |s:&str| OpValue::Value(s.to_string())),
-        map(take_while(|c:char| !is_space(c as u8) ), |s: &str| OpValue::Raw(s.to_string())),
+        map(parse_quoted, OpValue::Raw),
+        map(terminated(map_parser(delimited(char1('`'), template_body, char1('`')), parse_template_string), token_end), OpValue::TemplateString),
+        map(terminated(delimited(char1('('), take_while(|c: char| !c.is_whitespace() && c != ')'), char1(')')), token_end), |s:&str| OpValue::Inline(s.to_string())),
+        map(terminated(delimited(char1('{'), take_while(|c: char| !c.is_whitespace() && c != '}'), char1('}')), token_end), |s:&str| OpValue::Value(s.to_string())),
+        map(take_while(|c: char| !c.is_whitespace()), |s: &str| OpValue::Raw(s.to_string())),
     ))(input)?;
 
     Ok((
@@ -164,8 +648,8 @@ pub fn parse_rule_value(input: &str) -> IResult<&str, OpValue> {
 
 pub fn parse_rule(input: &str) -> IResult<&str, Rule> {
     let (input, (name, value)) = tuple((
-        terminated(take_while1(|c: char| c.is_alphanumeric()), tag("://")),
-        map(take_while(|c: char| !c.is_whitespace()), |s:&str| parse_rule_value(s)),
+        terminated(take_while1(is_protocol_char), tag("://")),
+        map(token0, parse_rule_value),
     ))(input)?;
 
     let (_, value) = value?;
@@ -180,13 +664,24 @@ pub fn parse_rule(input: &str) -> IResult<&str, Rule> {
 }
 
 pub fn get_part(input: &str) -> IResult<&str, &str> {
-    preceded(multispace0, take_till1(|c: char| c.is_whitespace()))(input)
+    preceded(take_while(char::is_whitespace), token)(input)
 }
 
+/// Each token of a line with its offset in `input`.
+pub(crate) fn tokens(input: &str) -> Vec<(usize, &str)> {
+    let mut tokens = vec![];
+    let mut rest = input;
+    while let Ok((next, part)) = get_part(rest) {
+        tokens.push((error::offset_in(input, part), part));
+        rest = next;
+    }
+    tokens
+}
+
+/// The whitespace separated `name://value` operations at the start of
+/// `input`. Stops before the first token that isn't an operation.
 pub fn get_rules(input: &str) -> IResult<&str, Vec<Rule>> {
-  let (rest, rules) = preceded(whitespace, separated_list0(whitespace, map(not_space, |s:&str|  {
-    parse_rule(s).unwrap().1
-}))).parse(input)?;
+  let (rest, rules) = preceded(whitespace, separated_list0(whitespace, map_parser(token, all_consuming(parse_rule)))).parse(input)?;
 
   Ok((
     rest,
@@ -209,7 +704,7 @@ pub fn parse_proxy_rule(input: &str) -> IResult<&str, ProxyRule> {
     let (rest, rules) = if rest.trim().is_empty() {
       (rest, vec![])
     } else {
-      get_rules(rest).unwrap()
+      get_rules(rest)?
     };
 
     Ok((
@@ -218,10 +713,59 @@ pub fn parse_proxy_rule(input: &str) -> IResult<&str, ProxyRule> {
         source,
         target,
         rules,
+        origin: None,
       }
     ))
 }
 
+/// [`parse_proxy_rule`] with a `Result` error and the checks enabled in `options`.
+pub fn parse_proxy_rule_with<'a>(input: &'a str, options: &ParserOptions) -> Result<(&'a str, ProxyRule), WhistleParseError> {
+    options.limits.check_line_length(input)?;
+    let normalized = options.normalize_separators(input);
+    let (rest, mut rule) = parse_proxy_rule(&normalized).map_err(|e| WhistleParseError::from_nom(&normalized, e))?;
+    // separators are replaced byte for byte, so offsets carry over
+    let rest = input.get(input.len() - rest.len()..).unwrap_or_default();
+    let input = normalized.as_ref();
+
+    if options.strict_uri {
+        let source_at = input.len() - input.trim_start().len();
+        validate::validate_uri(&rule.source, source_at, true)?;
+        let (after_source, _) = get_part(input).map_err(|e| WhistleParseError::from_nom(input, e))?;
+        let target_at = input.len() - after_source.trim_start().len();
+        validate::validate_uri(&rule.target, target_at, false)?;
+    }
+    for r in &rule.rules {
+        if let OpValue::TemplateString(ts) = &r.value {
+            options.limits.check_template_parts(ts.parts.len(), Span::new(0, input.len()))?;
+        }
+    }
+    options.aliases.expand(&mut rule);
+    Ok((rest, rule))
+}
+
+/// [`parse_proxy_rule_with`] for when the whole line must be one rule: any
+/// text left over is reported as [`WhistleParseError::TrailingInput`], or as
+/// [`WhistleParseError::ExtraPattern`] when it starts with another pattern.
+pub fn parse_proxy_rule_complete(input: &str, options: &ParserOptions) -> Result<ProxyRule, WhistleParseError> {
+    let (rest, rule) = parse_proxy_rule_with(input, options)?;
+    let trailing = rest.trim();
+    if !trailing.is_empty() {
+        let start = input.len() - rest.trim_start().len();
+        let first = trailing.split_whitespace().next().unwrap_or_default();
+        if multi_pattern::looks_like_pattern(first) {
+            return Err(WhistleParseError::ExtraPattern {
+                text: first.to_string(),
+                span: Span::new(start, start + first.len()),
+            });
+        }
+        return Err(WhistleParseError::TrailingInput {
+            text: trailing.to_string(),
+            span: Span::new(start, start + trailing.len()),
+        });
+    }
+    Ok(rule)
+}
+
 #[cfg(test)]
 mod test {
   use super::*;
@@ -239,6 +783,98 @@ mod test {
     assert_eq!(uri.to_string(), str);
   }
   #[test]
+  fn test_empty_authority(){
+    for (input, host, path, query) in [
+      ("file:///mock/a", "", "/mock/a", ""),
+      ("file:///", "", "/", ""),
+      ("file://", "", "", ""),
+      ("file://?x=1", "", "", "?x=1"),
+      ("tpl:///a?b", "", "/a", "?b"),
+      ("a.com?x=1", "a.com", "", "?x=1"),
+    ] {
+      let (rest, uri) = parse_uri(input).unwrap();
+      assert_eq!(rest, "");
+      assert_eq!((uri.host.as_str(), uri.path.as_str(), uri.query.as_str()), (host, path, query), "{input}");
+      assert_eq!(uri.to_string(), input);
+    }
+    let (_, rule) = parse_proxy_rule("a.com file:///D:/mock/a.html").unwrap();
+    assert_eq!(rule.target.as_file_path().unwrap().to_windows_string(), r"D:\mock\a.html");
+  }
+  #[test]
+  fn test_quoted_values(){
+    let (rest, rule) = parse_proxy_rule(r#"a.com file://"C:\My Mocks\a.html" resBody://"say \"hi\" now" x://"#).unwrap();
+    assert_eq!(rest, "");
+    assert_eq!(rule.target.path, r"C:\My Mocks\a.html");
+    assert_eq!(rule.target.to_string(), r#"file://"C:\My Mocks\a.html""#);
+    assert!(matches!(&rule.rules[0].value, OpValue::Raw(s) if s == r#"say "hi" now"#));
+    assert!(matches!(&rule.rules[1].value, OpValue::Raw(s) if s.is_empty()));
+
+    // an unterminated quote is kept as text
+    let (_, rule) = parse_proxy_rule(r#"a.com b.com resBody://"x"#).unwrap();
+    assert!(matches!(&rule.rules[0].value, OpValue::Raw(s) if s == "\"x"));
+  }
+  #[test]
+  fn test_rule_keys(){
+    use std::collections::{BTreeSet, HashMap};
+    let rule = |s| parse_proxy_rule(s).unwrap().1;
+    let mut hits = HashMap::new();
+    for line in ["A.com file://x", "a.COM file://x", "a.com file://X", "a.com HTTP://B.com", "a.com http://b.com"] {
+      *hits.entry(rule(line)).or_insert(0) += 1;
+    }
+    assert_eq!(hits[&rule("a.com file://x")], 2);
+    assert_eq!(hits[&rule("a.com http://B.COM")], 2);
+    assert_eq!(hits.len(), 3);
+
+    let sorted: Vec<String> = ["c.com b.com", "a.com/y d.com", "a.com/x d.com resCors://*", "a.com/x d.com"]
+      .into_iter()
+      .map(rule)
+      .collect::<BTreeSet<_>>()
+      .iter()
+      .map(ProxyRule::to_string)
+      .collect();
+    assert_eq!(sorted, ["a.com/x d.com", "a.com/x d.com resCors://*", "a.com/y d.com", "c.com b.com"]);
+  }
+  #[test]
+  fn test_op_value_constructors(){
+    assert_eq!(OpValue::try_from("(a=1)"), Ok(OpValue::inline("a=1")));
+    assert_eq!(OpValue::try_from("{body}"), Ok(OpValue::value_ref("body")));
+    assert_eq!(OpValue::try_from("\"a b\""), Ok(OpValue::raw("a b")));
+    assert_eq!(
+      OpValue::try_from("`/${name}`"),
+      Ok(OpValue::template([TemplatePart::RawString("/".into()), TemplatePart::Value("name".into())]))
+    );
+    for value in [OpValue::inline("x"), OpValue::value_ref("k"), OpValue::raw("a b"), OpValue::raw("")] {
+      assert_eq!(OpValue::try_from(value.to_string().as_str()), Ok(value));
+    }
+    assert!(OpValue::try_from("a b").is_err());
+  }
+  #[test]
+  fn test_trailing_input(){
+    let line = "a.com b.com file://x typo:/y z://1";
+    let (rest, rule) = parse_proxy_rule(line).unwrap();
+    assert_eq!(rest, " typo:/y z://1");
+    assert_eq!(rule.rules.len(), 1);
+    assert_eq!(
+      parse_proxy_rule_complete(line, &ParserOptions::default()).unwrap_err(),
+      WhistleParseError::TrailingInput { text: "typo:/y z://1".into(), span: Span::new(21, 34) }
+    );
+    assert!(parse_proxy_rule_complete("a.com b.com file://x  ", &ParserOptions::default()).is_ok());
+  }
+  #[test]
+  fn test_unicode_separators(){
+    let (rest, rule) = parse_proxy_rule("a.com\u{3000}b.com\u{a0}resBody://aĠb").unwrap();
+    assert_eq!(rest, "");
+    assert_eq!(rule.target.host, "b.com");
+    assert_eq!(rule.rules[0].value, OpValue::Raw("aĠb".into()));
+
+    let line = "a.com\u{200b}b.com\u{feff}x://1 y";
+    assert!(parse_proxy_rule(line).unwrap().1.source.host.contains('\u{200b}'));
+    let (rest, rule) = parse_proxy_rule_with(line, &ParserOptions::default()).unwrap();
+    assert_eq!(rest, " y");
+    assert_eq!(rule.target.host, "b.com");
+    assert_eq!(rule.rules[0].value, OpValue::Raw("1".into()));
+  }
+  #[test]
   fn test_template_string(){
     let str = "`x=1&b=2`";
     let (input, ts) = parse_template_string(str).unwrap();
@@ -250,5 +886,39 @@ mod test {
     let (input, ts) = parse_template_string(str).unwrap();
     assert_eq!(input, "");
     assert_eq!(ts.parts, vec![TemplatePart::RawString("`(x=1&b=2)`".into())]);
+
+    let (input, ts) = parse_template_string("a${b}c").unwrap();
+    assert_eq!(input, "");
+    assert_eq!(ts.parts, vec![
+      TemplatePart::RawString("a".into()),
+      TemplatePart::Value("b".into()),
+      TemplatePart::RawString("c".into()),
+    ]);
+
+    let (_, ts) = parse_template_string("${uuid()}-${random(1, 100)}").unwrap();
+    assert_eq!(ts.parts, vec![
+      TemplatePart::Call { name: "uuid".into(), args: vec![] },
+      TemplatePart::RawString("-".into()),
+      TemplatePart::Call { name: "random".into(), args: vec!["1".into(), "100".into()] },
+    ]);
+  }
+  #[test]
+  fn test_template_string_escapes(){
+    let (_, ts) = parse_template_string(r"a\`b\${c}-${d}\\D:\mock\x").unwrap();
+    assert_eq!(ts.parts, vec![
+      TemplatePart::RawString("a`b${c}-".into()),
+      TemplatePart::Value("d".into()),
+      TemplatePart::RawString(r"\D:\mock\x".into()),
+    ]);
+
+    let ts = TemplateString { parts: vec![
+      TemplatePart::RawString("(a`b${c}\\".into()),
+      TemplatePart::Value("d".into()),
+      TemplatePart::RawString(r"D:\x)\".into()),
+    ] };
+    let value = OpValue::TemplateString(ts);
+    assert_eq!(value.to_string(), r"`\(a\`b\${c}\\${d}D:\x)\\`");
+    let text = value.to_string();
+    assert_eq!(parse_rule_value(&text).unwrap(), ("", value));
   }
 }
\ No newline at end of file
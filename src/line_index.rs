@@ -0,0 +1,110 @@
+//! Convert between byte offsets, as in every [`Span`](crate::Span), and the
+//! line and column positions editors use.
+
+/// A zero-based line and column. Columns count UTF-16 code units, as the
+/// language server protocol does.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Position {
+    pub line: usize,
+    pub character: usize,
+}
+
+impl Position {
+    pub fn new(line: usize, character: usize) -> Self {
+        Position { line, character }
+    }
+}
+
+/// The line starts of a document. Lines end at `\n`, `\r\n` and `\r`, the
+/// same as [`split_lines`](crate::rule_file::split_lines).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineIndex {
+    text: String,
+    /// Byte offset of each line, the first is always 0.
+    starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(text: &str) -> LineIndex {
+        let mut starts = vec![0];
+        let bytes = text.as_bytes();
+        let mut i = 0;
+        while i < bytes.len() {
+            match bytes[i] {
+                b'\r' if bytes.get(i + 1) == Some(&b'\n') => {
+                    starts.push(i + 2);
+                    i += 1;
+                }
+                b'\n' | b'\r' => starts.push(i + 1),
+                _ => {}
+            }
+            i += 1;
+        }
+        LineIndex { text: text.to_string(), starts }
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.starts.len()
+    }
+
+    /// The byte range of `line` without its line break.
+    fn line_range(&self, line: usize) -> Option<(usize, usize)> {
+        let start = *self.starts.get(line)?;
+        let end = self.starts.get(line + 1).map_or(self.text.len(), |&next| next);
+        let content = self.text[start..end].trim_end_matches(['\r', '\n']);
+        Some((start, start + content.len()))
+    }
+
+    /// The position of `offset`, clamped to the end of the document. An
+    /// offset inside a character or a `\r\n` counts as the one before it.
+    pub fn offset_to_position(&self, offset: usize) -> Position {
+        let offset = offset.min(self.text.len());
+        let line = self.starts.partition_point(|&start| start <= offset) - 1;
+        let (start, end) = self.line_range(line).unwrap_or_default();
+        let column_end = offset.min(end);
+        let character = self.text[start..]
+            .char_indices()
+            .take_while(|(at, _)| start + at < column_end)
+            .map(|(_, c)| c.len_utf16())
+            .sum();
+        Position { line, character }
+    }
+
+    /// The offset of `position`, `None` past the last line. Columns past the
+    /// end of a line give the end of the line.
+    pub fn position_to_offset(&self, position: Position) -> Option<usize> {
+        let (start, end) = self.line_range(position.line)?;
+        let mut units = 0;
+        for (at, c) in self.text[start..end].char_indices() {
+            if units >= position.character {
+                return Some(start + at);
+            }
+            units += c.len_utf16();
+        }
+        Some(end)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_line_index() {
+        let text = "a.com b.com\r\n# é😀x\rc\n";
+        let index = LineIndex::new(text);
+        assert_eq!(index.line_count(), 4);
+        assert_eq!(index.offset_to_position(6), Position::new(0, 6));
+        assert_eq!(index.offset_to_position(12), Position::new(0, 11));
+        assert_eq!(index.offset_to_position(13), Position::new(1, 0));
+        // `é` is one UTF-16 unit, `😀` two
+        let x = text.find('x').unwrap();
+        assert_eq!(index.offset_to_position(x), Position::new(1, 5));
+        assert_eq!(index.position_to_offset(Position::new(1, 5)), Some(x));
+        assert_eq!(index.position_to_offset(Position::new(1, 99)), Some(x + 1));
+        assert_eq!(index.position_to_offset(Position::new(2, 0)), Some(x + 2));
+        assert_eq!(index.position_to_offset(Position::new(3, 0)), Some(text.len()));
+        assert_eq!(index.position_to_offset(Position::new(4, 0)), None);
+        assert_eq!(index.offset_to_position(1000), Position::new(3, 0));
+    }
+}
@@ -0,0 +1,297 @@
+//! Checks for rules that parse but don't do what they look like they do.
+use std::fmt;
+
+use crate::meta::Date;
+use crate::ops::DOCUMENTED_PROTOCOLS;
+use crate::rule_file::{LineItem, RuleFile, RuleId};
+use crate::{ProxyRule, Span};
+
+/// Protocols of which whistle applies only one per request: the first on
+/// the line wins and the rest are ignored.
+pub const EXCLUSIVE_GROUPS: &[(&str, &[&str])] = &[
+    (
+        "response",
+        &[
+            "file", "xfile", "tpl", "xtpl", "rawfile", "xrawfile", "redirect", "locationHref",
+            "statusCode", "http", "https", "ws", "wss", "tunnel",
+        ],
+    ),
+    ("proxy", &["proxy", "http-proxy", "https-proxy", "socks", "pac"]),
+    ("host", &["host"]),
+];
+
+/// Protocols that take one value, so a second one on the line is ignored.
+/// Others such as `reqHeaders://` merge their values.
+pub const SINGLE_VALUE_PROTOCOLS: &[&str] = &[
+    "replaceStatus", "method", "ua", "referer", "auth", "cache", "reqType", "resType",
+    "reqCharset", "resCharset", "reqDelay", "resDelay", "reqSpeed", "resSpeed", "attachment",
+    "forwardedFor", "responseFor",
+];
+
+/// Two operations of one line of which whistle only honors the first.
+/// Indexes are into [`ProxyRule::operations`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Conflict {
+    /// The exclusive group, or the protocol for single value protocols.
+    pub group: &'static str,
+    pub honored: usize,
+    pub ignored: usize,
+}
+
+/// Every operation of `rule` that an earlier one on the line overrides.
+pub fn conflicts(rule: &ProxyRule) -> Vec<Conflict> {
+    let ops = rule.operations();
+    let group_of = |name: &str| {
+        EXCLUSIVE_GROUPS
+            .iter()
+            .find(|(_, protocols)| protocols.contains(&name))
+            .map(|(group, _)| *group)
+            .or_else(|| SINGLE_VALUE_PROTOCOLS.iter().find(|p| **p == name).copied())
+    };
+    let mut honored: Vec<(&str, usize)> = vec![];
+    let mut conflicts = vec![];
+    for (index, op) in ops.iter().enumerate() {
+        let Some(group) = group_of(&op.name) else {
+            continue;
+        };
+        match honored.iter().find(|(g, _)| *g == group) {
+            Some(&(_, first)) => conflicts.push(Conflict { group, honored: first, ignored: index }),
+            None => honored.push((group, index)),
+        }
+    }
+    conflicts
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LintKind {
+    /// `ignored` is overridden by `honored` earlier on the line.
+    Conflict { honored: String, ignored: String },
+    /// A [debugging operation](crate::ops::DebugOp) left in the rules.
+    DebugOperation(String),
+    /// The same operation and value as earlier on the line.
+    Duplicate(String),
+    /// An undocumented protocol one edit away from a documented one.
+    Typo { protocol: String, suggestion: &'static str },
+    /// An enabled rule past its [`expires`](crate::meta::RuleMeta::expires)
+    /// annotation.
+    Expired(Date),
+}
+
+impl fmt::Display for LintKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LintKind::Conflict { honored, ignored } => {
+                write!(f, "`{ignored}://` is ignored, whistle uses `{honored}://` from earlier on the line")
+            }
+            LintKind::DebugOperation(protocol) => write!(f, "`{protocol}://` is meant for debugging"),
+            LintKind::Duplicate(protocol) => write!(f, "`{protocol}://` repeats an earlier operation"),
+            LintKind::Typo { protocol, suggestion } => {
+                write!(f, "unknown protocol `{protocol}://`, did you mean `{suggestion}://`?")
+            }
+            LintKind::Expired(date) => write!(f, "the rule expired on {date}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintWarning {
+    pub rule: RuleId,
+    /// What the warning is about in the document.
+    pub span: Span,
+    pub kind: LintKind,
+}
+
+/// The documented protocol `name` is probably a misspelling of: one that
+/// differs only in case, or by one edit or swap for names long enough that one edit
+/// isn't another protocol. Plugin protocols are never close enough to guess.
+pub fn suggest_protocol(name: &str) -> Option<&'static str> {
+    if DOCUMENTED_PROTOCOLS.contains(&name) {
+        return None;
+    }
+    DOCUMENTED_PROTOCOLS
+        .iter()
+        .find(|p| p.eq_ignore_ascii_case(name))
+        .or_else(|| {
+            DOCUMENTED_PROTOCOLS
+                .iter()
+                .filter(|p| p.len() > 4 && name.len() > 4)
+                .find(|p| edit_distance(p, name) == 1)
+        })
+        .copied()
+}
+
+/// Edits between `a` and `b`, counting a swap of neighbours as one edit.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    d[0] = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+/// The enabled rules of `file` that expired by `today`, each warning
+/// spanning the `expires:` annotation.
+pub fn expired(file: &RuleFile, today: Date) -> Vec<LintWarning> {
+    let mut warnings = vec![];
+    for (id, _, enabled) in file.rules() {
+        let Some(expires) = file.meta(id).and_then(|meta| meta.expires).filter(|_| enabled) else {
+            continue;
+        };
+        if expires > today {
+            continue;
+        }
+        let comment = file.comment(id).unwrap_or_default();
+        let word = comment
+            .split_whitespace()
+            .find(|word| word.strip_prefix("expires:").and_then(Date::parse).is_some());
+        let start = word.map_or(0, |word| crate::error::offset_in(file.text(), word));
+        warnings.push(LintWarning {
+            rule: id,
+            span: Span::new(start, start + word.map_or(0, str::len)),
+            kind: LintKind::Expired(expires),
+        });
+    }
+    warnings
+}
+
+/// Every warning for the enabled rules of `file`, in document order,
+/// [expired](expired) ones by `today`.
+pub fn lint(file: &RuleFile, today: Date) -> Vec<LintWarning> {
+    let mut warnings = expired(file, today);
+    for line in file.lines() {
+        let LineItem::Rule(rule) = &line.item else {
+            continue;
+        };
+        let spans = file.op_spans(RuleId(line.number));
+        let op_span = |index: usize| spans.get(index).copied().unwrap_or(line.span);
+        let ops = rule.operations();
+        let conflicts = conflicts(rule);
+        for conflict in &conflicts {
+            warnings.push(LintWarning {
+                rule: RuleId(line.number),
+                span: op_span(conflict.ignored),
+                kind: LintKind::Conflict {
+                    honored: ops[conflict.honored].name.clone(),
+                    ignored: ops[conflict.ignored].name.clone(),
+                },
+            });
+        }
+        for (index, op) in rule.ops().iter().enumerate() {
+            if matches!(op, Ok(op) if op.is_debug()) {
+                warnings.push(LintWarning {
+                    rule: RuleId(line.number),
+                    span: op_span(index),
+                    kind: LintKind::DebugOperation(ops[index].name.clone()),
+                });
+            }
+        }
+        for (index, op) in ops.iter().enumerate() {
+            let repeated = ops[..index].contains(op) && !conflicts.iter().any(|c| c.ignored == index);
+            if repeated {
+                warnings.push(LintWarning {
+                    rule: RuleId(line.number),
+                    span: op_span(index),
+                    kind: LintKind::Duplicate(op.name.clone()),
+                });
+            }
+            if let Some(suggestion) = suggest_protocol(&op.name) {
+                warnings.push(LintWarning {
+                    rule: RuleId(line.number),
+                    span: op_span(index),
+                    kind: LintKind::Typo { protocol: op.name.clone(), suggestion },
+                });
+            }
+        }
+    }
+    warnings.sort_by_key(|w| w.span.start);
+    warnings
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse_proxy_rule;
+
+    const TODAY: Date = Date { year: 2025, month: 3, day: 1 };
+
+    #[test]
+    fn test_conflicts() {
+        let rule = parse_proxy_rule("a.com file://x reqHeaders://{a} redirect://b.com statusCode://404 reqHeaders://{b}")
+            .unwrap()
+            .1;
+        assert_eq!(
+            conflicts(&rule),
+            vec![
+                Conflict { group: "response", honored: 0, ignored: 2 },
+                Conflict { group: "response", honored: 0, ignored: 3 },
+            ]
+        );
+        let rule = parse_proxy_rule("a.com 127.0.0.1 method://post host://10.0.0.1 method://get").unwrap().1;
+        assert_eq!(
+            conflicts(&rule),
+            vec![
+                Conflict { group: "host", honored: 0, ignored: 2 },
+                Conflict { group: "method", honored: 1, ignored: 3 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lint() {
+        let file = RuleFile::parse("# x\na.com statusCode://500 file://x\n#off b.com file://x file://y\nc.com log://1");
+        let warnings = lint(&file, TODAY);
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(&file.text()[warnings[0].span.start..warnings[0].span.end], "file://x");
+        assert_eq!(
+            warnings[0].kind.to_string(),
+            "`file://` is ignored, whistle uses `statusCode://` from earlier on the line"
+        );
+        assert_eq!(warnings[1].kind, LintKind::DebugOperation("log".into()));
+        assert_eq!(&file.text()[warnings[1].span.start..warnings[1].span.end], "log://1");
+    }
+
+    #[test]
+    fn test_lint_typos_and_duplicates() {
+        let file = RuleFile::parse("a.com reqheaders://{a} resHeadres://{b} ua://x ua://x cors://x");
+        let kinds: Vec<_> = lint(&file, TODAY).into_iter().map(|w| w.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                LintKind::Typo { protocol: "reqheaders".into(), suggestion: "reqHeaders" },
+                LintKind::Typo { protocol: "resHeadres".into(), suggestion: "resHeaders" },
+                LintKind::Conflict { honored: "ua".into(), ignored: "ua".into() },
+            ]
+        );
+        let file = RuleFile::parse("a.com reqHeaders://{a} reqHeaders://{a}");
+        assert_eq!(lint(&file, TODAY)[0].kind, LintKind::Duplicate("reqHeaders".into()));
+        assert_eq!(suggest_protocol("xhttp"), None);
+    }
+
+    #[test]
+    fn test_expired() {
+        let file = RuleFile::parse(
+            "a.com 1.1.1.1 # owner:@a expires:2025-01-01
+#off b.com 1.1.1.1 # expires:2025-01-01
+c.com 1.1.1.1 # expires:2025-06-01",
+        );
+        let warnings = expired(&file, Date::parse("2025-03-01").unwrap());
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].kind.to_string(), "the rule expired on 2025-01-01");
+        assert_eq!(&file.text()[warnings[0].span.start..warnings[0].span.end], "expires:2025-01-01");
+        let file = RuleFile::parse("a.com 1.1.1.1 # expires:2000-01-01");
+        assert_eq!(lint(&file, TODAY)[0].kind, LintKind::Expired(Date { year: 2000, month: 1, day: 1 }));
+        assert!(lint(&file, Date { year: 1999, month: 12, day: 31 }).is_empty());
+    }
+}
@@ -0,0 +1,188 @@
+//! Local includes, `@./common.rules` or `@/etc/whistle/base.rules`, read
+//! from the filesystem.
+//!
+//! [`FsRuleLoader`] replaces each local include line with the lines of the
+//! file it names, resolved against the directory of the file including it,
+//! and remembers where every line of the combined [`RuleFile`] came from.
+//! Remote includes, `@https://…`, stay in the file as
+//! [`LineItem::Include`] for a resolver to fetch.
+use std::path::{Path, PathBuf};
+
+use crate::origin::Origin;
+use crate::rule_file::{split_lines, LineItem, RuleFile, RuleId};
+use crate::rule_set::RuleSet;
+use crate::{ParserOptions, WhistleParseError};
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io { path: PathBuf, error: std::io::Error },
+    /// `path` includes itself, directly or through other files.
+    Cycle { path: PathBuf },
+    /// A file is too big, or includes nest deeper than the
+    /// [limits](crate::options::Limits) allow.
+    Limit { path: PathBuf, error: WhistleParseError },
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FsRuleLoader {
+    pub options: ParserOptions,
+}
+
+/// The combined file and, for each of its lines, the file and zero-based
+/// line it was read from.
+#[derive(Debug, Clone)]
+pub struct LoadedRules {
+    pub file: RuleFile,
+    pub sources: Vec<PathBuf>,
+    lines: Vec<(usize, usize)>,
+}
+
+impl LoadedRules {
+    /// Where line `number` of the combined file was written.
+    pub fn source_of_line(&self, number: usize) -> Option<(&Path, usize)> {
+        let (source, line) = *self.lines.get(number)?;
+        Some((&self.sources[source], line))
+    }
+
+    /// Where the rule `id` of the combined file was written.
+    pub fn source_of(&self, id: RuleId) -> Option<(&Path, usize)> {
+        self.source_of_line(id.0)
+    }
+
+    /// The enabled rules, each with the file and line it came from as its
+    /// [origin](crate::ProxyRule::origin).
+    pub fn rule_set(&self) -> RuleSet {
+        self.file
+            .rules()
+            .filter(|(_, _, enabled)| *enabled)
+            .map(|(id, rule, _)| {
+                let mut rule = rule.clone();
+                rule.origin = self
+                    .source_of(id)
+                    .map(|(path, line)| Origin::new(path.display().to_string(), line));
+                rule
+            })
+            .collect()
+    }
+}
+
+/// The path a local include names, `None` for a remote one.
+fn local_include(item: &LineItem) -> Option<PathBuf> {
+    let LineItem::Include(uri) = item else {
+        return None;
+    };
+    if !uri.scheme.is_empty() && uri.scheme != "file" {
+        return None;
+    }
+    Some(PathBuf::from(format!("{}{}{}", uri.host, uri.path, uri.query)))
+}
+
+impl FsRuleLoader {
+    pub fn new(options: ParserOptions) -> Self {
+        FsRuleLoader { options }
+    }
+
+    pub fn load(&self, path: impl AsRef<Path>) -> Result<LoadedRules, LoadError> {
+        let mut loaded = Combined::default();
+        self.load_into(path.as_ref(), &mut vec![], &mut loaded)?;
+        let file = RuleFile::parse_with(loaded.text, self.options.clone())
+            .map_err(|error| LoadError::Limit { path: path.as_ref().to_path_buf(), error })?;
+        Ok(LoadedRules { file, sources: loaded.sources, lines: loaded.lines })
+    }
+
+    fn load_into(&self, path: &Path, stack: &mut Vec<PathBuf>, out: &mut Combined) -> Result<(), LoadError> {
+        let io = |error| LoadError::Io { path: path.to_path_buf(), error };
+        let canonical = path.canonicalize().map_err(io)?;
+        if stack.contains(&canonical) {
+            return Err(LoadError::Cycle { path: path.to_path_buf() });
+        }
+        let limit = |error| LoadError::Limit { path: path.to_path_buf(), error };
+        self.options.limits.check_include_depth(stack.len(), Default::default()).map_err(limit)?;
+        let text = std::fs::read_to_string(path).map_err(io)?;
+        self.options.limits.check_input_size(&text).map_err(limit)?;
+
+        let source = out.sources.len();
+        out.sources.push(path.to_path_buf());
+        stack.push(canonical);
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for line in RuleFile::parse(text.as_str()).lines() {
+            // the empty line after a final line break
+            if line.span.start == text.len() && line.item == LineItem::Blank {
+                continue;
+            }
+            let line_text = &text[line.span.start..line.span.end];
+            if let Some(include) = local_include(&line.item) {
+                self.load_into(&dir.join(include), stack, out)?;
+                continue;
+            }
+            // a value block is several physical lines
+            for (i, (_, physical)) in split_lines(line_text).into_iter().enumerate() {
+                out.text.push_str(physical);
+                out.text.push('\n');
+                out.lines.push((source, line.number + i));
+            }
+        }
+        stack.pop();
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Combined {
+    text: String,
+    sources: Vec<PathBuf>,
+    lines: Vec<(usize, usize)>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("whistle-loader-{}-{name}", std::process::id()));
+        std::fs::create_dir_all(dir.join("shared")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_load_includes() {
+        let dir = temp_dir("includes");
+        std::fs::write(dir.join("main.rules"), "# main\n@./shared/base.rules\n@https://a.com/r.txt\nc.com d.com\n").unwrap();
+        std::fs::write(dir.join("shared/base.rules"), "a.com b.com\n```v\nx\n```\n@../leaf.rules").unwrap();
+        std::fs::write(dir.join("leaf.rules"), "e.com f.com\n@./tail.rules # shared").unwrap();
+        std::fs::write(dir.join("tail.rules"), "g.com h.com").unwrap();
+
+        let loaded = FsRuleLoader::default().load(dir.join("main.rules")).unwrap();
+        assert_eq!(
+            loaded.file.text(),
+            "# main\na.com b.com\n```v\nx\n```\ne.com f.com\ng.com h.com\n@https://a.com/r.txt\nc.com d.com\n"
+        );
+        let rules: Vec<_> = loaded
+            .file
+            .rules()
+            .map(|(id, _, _)| {
+                let (path, line) = loaded.source_of(id).unwrap();
+                (path.file_name().unwrap().to_str().unwrap().to_string(), line)
+            })
+            .collect();
+        assert_eq!(
+            rules,
+            vec![("base.rules".into(), 0), ("leaf.rules".into(), 0), ("tail.rules".into(), 0), ("main.rules".into(), 3)]
+        );
+        assert_eq!(loaded.source_of_line(4).map(|(_, line)| line), Some(3));
+        let origin = loaded.rule_set().rules[1].origin.clone().unwrap();
+        assert!(origin.source.ends_with("leaf.rules") && origin.line == 0, "{origin}");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let dir = temp_dir("errors");
+        std::fs::write(dir.join("a.rules"), "@./shared/b.rules").unwrap();
+        std::fs::write(dir.join("shared/b.rules"), "@../a.rules").unwrap();
+        assert!(matches!(FsRuleLoader::default().load(dir.join("a.rules")), Err(LoadError::Cycle { .. })));
+        std::fs::write(dir.join("c.rules"), "@./missing.rules").unwrap();
+        assert!(matches!(FsRuleLoader::default().load(dir.join("c.rules")), Err(LoadError::Io { .. })));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,19 +1,30 @@
 /// fork from: https://github.com/hgm-king/prose
+use std::borrow::Cow;
+use std::fmt;
+
 use nom::{
     branch::alt,
-    bytes::complete::{is_not, tag, take},
-    combinator::{map, not},
+    bytes::complete::{tag, take, take_until, take_while, take_while_m_n},
+    character::complete::{char, one_of},
+    combinator::{map, not, opt},
     multi::{many0, many1},
     sequence::{delimited, preceded, terminated, tuple},
     IResult,
 };
 
+use crate::base64;
+use crate::rule_file::value_fence;
+use crate::sha256;
+use crate::{template_expr, OpValue, Span, TemplatePart};
+
 pub type MarkdownText = Vec<MarkdownInline>;
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Markdown {
     Line(MarkdownText),
     Codeblock(String, String),
+    /// `- item`, `* item` or `+ item`, `indent` spaces in.
+    ListItem { indent: usize, marker: char, text: MarkdownText },
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -21,43 +32,103 @@ pub enum MarkdownInline {
     Plaintext(String),
 }
 
+/// Parse a values document. `\r\n` and `\r` end lines like `\n` does, and
+/// code block bodies are returned with `\n` line breaks.
 pub fn parse_markdown(i: &str) -> IResult<&str, Vec<Markdown>> {
+    let i = i.strip_prefix('\u{feff}').unwrap_or(i);
     many1(alt((
         map(parse_code_block, |e| {
-            Markdown::Codeblock(e.0.to_string(), e.1.to_string())
+            Markdown::Codeblock(e.0.to_string(), e.1.replace("\r\n", "\n").replace('\r', "\n"))
         }),
-        map(parse_markdown_text, |e| Markdown::Line(e)),
+        parse_list_item,
+        map(parse_markdown_text, Markdown::Line),
     )))(i)
 }
 
+/// The element as it is written, so `parse_markdown` reads it back. A code
+/// block gets a fence longer than any backtick run in its body.
+impl fmt::Display for Markdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Markdown::Line(text) => writeln!(f, "{}", plaintext(text)),
+            Markdown::ListItem { indent, marker, text } => writeln!(f, "{:indent$}{marker} {}", "", plaintext(text)),
+            Markdown::Codeblock(lang, body) => {
+                let fence = value_fence(body);
+                let lang = if lang == "__UNKNOWN__" { "" } else { lang };
+                let newline = if body.is_empty() || body.ends_with('\n') { "" } else { "\n" };
+                write!(f, "{fence}{lang}\n{body}{newline}{fence}")
+            }
+        }
+    }
+}
+
+/// `value` in quotes, double unless it contains a double quote.
+fn quoted(value: &str) -> String {
+    let quote = if value.contains('"') { '\'' } else { '"' };
+    format!("{quote}{value}{quote}")
+}
+
+fn parse_list_item(i: &str) -> IResult<&str, Markdown> {
+    map(
+        tuple((take_while(|c| c == ' '), one_of("-*+"), char(' '), parse_markdown_text)),
+        |(indent, marker, _, text): (&str, char, char, MarkdownText)| Markdown::ListItem {
+            indent: indent.len(),
+            marker,
+            text,
+        },
+    )(i)
+}
+
+/// The text of each list item of a values document, in order, for
+/// documents that enumerate hosts or endpoints.
+pub fn list_items(input: &str) -> Vec<String> {
+    let (_, body) = parse_front_matter(input);
+    let Ok((_, md_arr)) = parse_markdown(&input[body..]) else {
+        return vec![];
+    };
+    md_arr
+        .into_iter()
+        .filter_map(|md| match md {
+            Markdown::ListItem { text, .. } => Some(plaintext(&text)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn plaintext(text: &MarkdownText) -> String {
+    text.iter().map(|MarkdownInline::Plaintext(s)| s.as_str()).collect()
+}
+
 fn parse_plaintext(i: &str) -> IResult<&str, String> {
     map(
-        many1(preceded(not(alt((tag("```"), tag("\n")))), take(1u8))),
+        many1(preceded(not(alt((tag("```"), tag("\n"), tag("\r")))), take(1u8))),
         |vec| vec.join(""),
     )(i)
 }
 
 fn parse_markdown_inline(i: &str) -> IResult<&str, MarkdownInline> {
-    alt((map(parse_plaintext, |s| MarkdownInline::Plaintext(s)),))(i)
+    alt((map(parse_plaintext, MarkdownInline::Plaintext),))(i)
 }
 
 fn parse_markdown_text(i: &str) -> IResult<&str, MarkdownText> {
-    terminated(many0(parse_markdown_inline), tag("\n"))(i)
+    terminated(many0(parse_markdown_inline), line_ending)(i)
 }
 
+/// A block opens with three or more backticks and closes at the next run
+/// of as many, so a longer fence can hold a body with ```` ``` ```` in it.
 fn parse_code_block(i: &str) -> IResult<&str, (String, &str)> {
-    tuple((parse_code_block_lang, parse_code_block_body))(i)
+    let (i, fence) = take_while_m_n(3, usize::MAX, |c| c == '`')(i)?;
+    let (i, lang) = parse_code_block_lang(i)?;
+    let (i, body) = delimited(line_ending, take_until(fence), tag(fence))(i)?;
+    Ok((i, (lang, body)))
 }
 
-fn parse_code_block_body(i: &str) -> IResult<&str, &str> {
-    delimited(tag("\n"), is_not("```"), tag("```"))(i)
+fn line_ending(i: &str) -> IResult<&str, &str> {
+    alt((tag("\r\n"), tag("\n"), tag("\r")))(i)
 }
 
 fn parse_code_block_lang(i: &str) -> IResult<&str, String> {
-    alt((
-        preceded(tag("```"), parse_plaintext),
-        map(tag("```"), |_| "__UNKNOWN__".to_string()),
-    ))(i)
+    map(opt(parse_plaintext), |lang| lang.unwrap_or_else(|| "__UNKNOWN__".to_string()))(i)
 }
 
 /// Break md_arr into (lines, codes)
@@ -79,7 +150,7 @@ pub fn into_parts(md_arr: Vec<Markdown>) -> (String, Vec<(String, String)>) {
                 }
                 match &v[0] {
                   MarkdownInline::Plaintext(s) => {
-                    lines.push_str(&s);
+                    lines.push_str(s);
                     lines.push('\n');
                   }
               }
@@ -87,14 +158,811 @@ pub fn into_parts(md_arr: Vec<Markdown>) -> (String, Vec<(String, String)>) {
             Markdown::Codeblock(name, value) => {
               codes.push((name.to_owned(), value.to_owned()));
             }
+            Markdown::ListItem { indent, marker, text } => {
+                lines.push_str(&format!("{}{marker} {}\n", " ".repeat(*indent), plaintext(text)));
+            }
         }
     });
     (lines, codes)
 }
 
+/// How many `{key}`/`${key}` indirections [`ValueMap::resolve`] follows
+/// before giving up.
+pub const MAX_REFERENCE_DEPTH: usize = 32;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ValueError {
+    Missing(String),
+    /// `chain` lists the keys in the order they were visited and ends with the
+    /// key that closed the cycle, e.g. `["a", "b", "a"]`.
+    CircularReference { chain: Vec<String> },
+    TooDeep { chain: Vec<String> },
+    /// A `base64` block with invalid base64 at this offset into its value.
+    InvalidBase64 { key: String, at: usize },
+}
+
+/// The attribute marking a block as base64, ```` ```img.png base64 ````.
+pub const BASE64_ATTR: &str = "base64";
+
+/// The attribute holding the hex SHA-256 of a block's value, checked when
+/// the document is parsed: ```` ```payload.pb base64 sha256=9f86d0… ````.
+pub const SHA256_ATTR: &str = "sha256";
+
+/// How a [`FrontMatter`] block was fenced.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrontMatterFormat {
+    /// `---` fences and `key: value` lines.
+    Yaml,
+    /// `+++` fences and `key = value` lines.
+    Toml,
+}
+
+/// The metadata block at the top of a values document:
+///
+/// ```text
+/// ---
+/// owner: web-team
+/// environment: staging
+/// version: 3
+/// ---
+/// ```
+///
+/// Only flat `key: value` (or `key = value`) lines are read; quotes around a
+/// value are dropped, and comments, blank lines and nested entries skipped.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrontMatter {
+    pub format: FrontMatterFormat,
+    pub fields: Vec<(String, String)>,
+    /// The block, both fences included.
+    pub span: Span,
+}
+
+impl FrontMatter {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    pub fn owner(&self) -> Option<&str> {
+        self.get("owner")
+    }
+
+    pub fn environment(&self) -> Option<&str> {
+        self.get("environment")
+    }
+
+    pub fn version(&self) -> Option<&str> {
+        self.get("version")
+    }
+}
+
+/// The block with its fences, quoting values that would not read back as
+/// they are. TOML values are quoted unless they are numbers or booleans.
+impl fmt::Display for FrontMatter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fence = match self.format {
+            FrontMatterFormat::Yaml => "---",
+            FrontMatterFormat::Toml => "+++",
+        };
+        writeln!(f, "{fence}")?;
+        for (key, value) in &self.fields {
+            match self.format {
+                FrontMatterFormat::Yaml if value.is_empty() => writeln!(f, "{key}:")?,
+                FrontMatterFormat::Yaml if value.starts_with(['"', '\'']) || value.trim() != value => {
+                    writeln!(f, "{key}: {}", quoted(value))?
+                }
+                FrontMatterFormat::Yaml => writeln!(f, "{key}: {value}")?,
+                FrontMatterFormat::Toml if value.parse::<f64>().is_ok() || value == "true" || value == "false" => {
+                    writeln!(f, "{key} = {value}")?
+                }
+                FrontMatterFormat::Toml => writeln!(f, "{key} = {}", quoted(value))?,
+            }
+        }
+        writeln!(f, "{fence}")
+    }
+}
+
+/// The front matter of `input`, if it starts with one after an optional
+/// BOM, and the offset where the rest of the document starts. An opening
+/// fence that is never closed isn't front matter.
+pub fn parse_front_matter(input: &str) -> (Option<FrontMatter>, usize) {
+    let start = if input.starts_with('\u{feff}') { '\u{feff}'.len_utf8() } else { 0 };
+    let mut lines = line_spans(input, start);
+    let Some((fence_start, (fence_end, _))) = lines.next() else {
+        return (None, start);
+    };
+    let fence = &input[fence_start..fence_end];
+    let (format, separator) = match fence {
+        "---" => (FrontMatterFormat::Yaml, ':'),
+        "+++" => (FrontMatterFormat::Toml, '='),
+        _ => return (None, start),
+    };
+    let mut fields = vec![];
+    for (line_start, (line_end, next)) in lines {
+        let line = &input[line_start..line_end];
+        if line == fence || (format == FrontMatterFormat::Yaml && line == "...") {
+            let matter = FrontMatter { format, fields, span: Span::new(start, line_end) };
+            return (Some(matter), next);
+        }
+        if line.starts_with([' ', '\t', '#']) {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once(separator) {
+            let value = value.trim();
+            let unquoted = ['"', '\''].iter().find_map(|q| value.strip_prefix(*q)?.strip_suffix(*q));
+            fields.push((key.trim().to_string(), unquoted.unwrap_or(value).to_string()));
+        }
+    }
+    (None, start)
+}
+
+/// Each line from `start` as its start, its end before the line ending,
+/// and where the next line starts.
+fn line_spans(input: &str, start: usize) -> impl Iterator<Item = (usize, (usize, usize))> + '_ {
+    let mut pos = start;
+    std::iter::from_fn(move || {
+        if pos >= input.len() {
+            return None;
+        }
+        let line_start = pos;
+        let end = input[pos..].find(['\n', '\r']).map_or(input.len(), |at| pos + at);
+        let ending = line_ending(&input[end..]).map_or(0, |(_, ending)| ending.len());
+        pos = end + ending;
+        Some((line_start, (end, pos)))
+    })
+}
+
+/// What [`ValueMap::parse_with`] reads besides fenced blocks, and how.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ValueMapOptions {
+    /// Read `` `key: value` `` code spans outside fenced blocks as one-line
+    /// values. Off by default, since prose often quotes code like that.
+    pub inline_values: bool,
+    pub duplicates: DuplicatePolicy,
+}
+
+/// Which value a key defined more than once gets.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// The later definition replaces the earlier one, as whistle does.
+    #[default]
+    KeepLast,
+    KeepFirst,
+}
+
+/// A code block reusing the key of an earlier one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DuplicateKey {
+    pub key: String,
+    /// The key of the first block defining it.
+    pub first: Span,
+    /// The key of the block repeating it.
+    pub duplicate: Span,
+}
+
+impl fmt::Display for DuplicateKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "value `{}` at {} is already defined at {}",
+            self.key, self.duplicate.start, self.first.start
+        )
+    }
+}
+
+/// Each code block of a values document whose key an earlier block already
+/// has, in order.
+pub fn duplicate_keys(input: &str) -> Vec<DuplicateKey> {
+    let blocks = value_blocks(input);
+    blocks
+        .iter()
+        .enumerate()
+        .filter_map(|(index, block)| {
+            let first = blocks[..index].iter().find(|b| b.key == block.key)?;
+            Some(DuplicateKey { key: block.key.clone(), first: first.key_span, duplicate: block.key_span })
+        })
+        .collect()
+}
+
+/// A block whose value doesn't have the [`SHA256_ATTR`] checksum it was
+/// annotated with, likely truncated or corrupted on the way.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChecksumMismatch {
+    pub key: String,
+    /// The key of the block.
+    pub span: Span,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl fmt::Display for ChecksumMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "value `{}` has sha256 {}, expected {}", self.key, self.actual, self.expected)
+    }
+}
+
+/// The hex SHA-256 of a block's value as written, references unresolved,
+/// or of its decoded bytes for a base64 block.
+fn value_digest(value: &str, attrs: &[(String, String)]) -> String {
+    let is_base64 = attrs.iter().any(|(name, _)| name == BASE64_ATTR);
+    match base64::decode(value) {
+        Ok(bytes) if is_base64 => sha256::hex_digest(&bytes),
+        _ => sha256::hex_digest(value.as_bytes()),
+    }
+}
+
+/// Each block of a values document whose checksum doesn't match its
+/// value, in order, see [`ValueMap::checksum`].
+pub fn checksum_mismatches(input: &str) -> Vec<ChecksumMismatch> {
+    value_blocks(input)
+        .into_iter()
+        .filter_map(|block| {
+            let (_, expected) = block.attrs.iter().find(|(name, _)| name == SHA256_ATTR)?;
+            let body = input[block.body.start..block.body.end].replace("\r\n", "\n").replace('\r', "\n");
+            let actual = value_digest(&body, &block.attrs);
+            (!actual.eq_ignore_ascii_case(expected)).then(|| ChecksumMismatch {
+                key: block.key,
+                span: block.key_span,
+                expected: expected.clone(),
+                actual,
+            })
+        })
+        .collect()
+}
+
+/// The `` `key: value` `` code spans of a line, in order. The key takes
+/// the characters of a `{key}` reference and the value is trimmed.
+pub fn inline_values(line: &str) -> Vec<(String, String)> {
+    let mut values = vec![];
+    let mut spans = line.split('`');
+    // the text before the first backtick isn't in a span
+    spans.next();
+    while let (Some(span), Some(_)) = (spans.next(), spans.next()) {
+        let Some((key, value)) = span.split_once(':') else {
+            continue;
+        };
+        if is_value_key(key) {
+            values.push((key.to_string(), value.trim().to_string()));
+        }
+    }
+    values
+}
+
+/// The info string after an opening fence. In a values document it names
+/// the value rather than a language: `mock.json type=json` is the key
+/// `mock.json` with a `type` attribute. Attributes are `name=value`, with
+/// optional quotes around the value, or a bare `name` with an empty value.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FenceInfo {
+    pub key: String,
+    pub attrs: Vec<(String, String)>,
+}
+
+impl FenceInfo {
+    pub fn parse(info: &str) -> FenceInfo {
+        let info = info.trim();
+        let key_end = info.find(char::is_whitespace).unwrap_or(info.len());
+        let mut rest = info[key_end..].trim_start();
+        let mut attrs = vec![];
+        while !rest.is_empty() {
+            let name_end = rest.find(|c: char| c == '=' || c.is_whitespace()).unwrap_or(rest.len());
+            let name = &rest[..name_end];
+            rest = &rest[name_end..];
+            let mut value = "";
+            if let Some(after) = rest.strip_prefix('=') {
+                let quote = after.chars().next().filter(|c| matches!(c, '"' | '\''));
+                let (start, end) = match quote {
+                    Some(q) => (1, after[1..].find(q).map_or(after.len(), |at| at + 1)),
+                    None => (0, after.find(char::is_whitespace).unwrap_or(after.len())),
+                };
+                value = &after[start..end];
+                rest = &after[(end + quote.map_or(0, |_| 1)).min(after.len())..];
+            }
+            attrs.push((name.to_string(), value.to_string()));
+            rest = rest.trim_start();
+        }
+        FenceInfo { key: info[..key_end].to_string(), attrs }
+    }
+
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+}
+
+/// The info string [`FenceInfo::parse`] reads back: bare names for empty
+/// values and quotes around values with spaces.
+impl fmt::Display for FenceInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.key)?;
+        for (name, value) in &self.attrs {
+            match value.as_str() {
+                "" => write!(f, " {name}")?,
+                v if v.contains(char::is_whitespace) || v.starts_with(['"', '\'']) => write!(f, " {name}={}", quoted(v))?,
+                v => write!(f, " {name}={v}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Entry {
+    key: String,
+    value: String,
+    attrs: Vec<(String, String)>,
+}
+
+/// Named values taken from the code blocks of a values document, keyed by the
+/// [key](FenceInfo::key) of the fence info string.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ValueMap {
+    entries: Vec<Entry>,
+    front_matter: Option<FrontMatter>,
+    duplicates: Vec<DuplicateKey>,
+    checksum_mismatches: Vec<ChecksumMismatch>,
+}
+
+impl ValueMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build the map from a values document. Blocks without a key are
+    /// skipped and the newline before the closing fence is not part of the
+    /// value.
+    pub fn parse(input: &str) -> Self {
+        Self::parse_with(input, &ValueMapOptions::default())
+    }
+
+    pub fn parse_with(input: &str, options: &ValueMapOptions) -> Self {
+        let (front_matter, body) = parse_front_matter(input);
+        let mut map = ValueMap {
+            front_matter,
+            duplicates: duplicate_keys(input),
+            checksum_mismatches: checksum_mismatches(input),
+            ..ValueMap::new()
+        };
+        let Ok((_, md_arr)) = parse_markdown(&input[body..]) else {
+            return map;
+        };
+        let mut insert = |key: String, value: String, attrs: Vec<(String, String)>| {
+            if options.duplicates == DuplicatePolicy::KeepLast || map.get(&key).is_none() {
+                map.insert_entry(Entry { key, value, attrs });
+            }
+        };
+        for md in md_arr {
+            match md {
+                Markdown::Codeblock(info, value) if info != "__UNKNOWN__" => {
+                    let FenceInfo { key, attrs } = FenceInfo::parse(&info);
+                    if key.is_empty() {
+                        continue;
+                    }
+                    let value = value.strip_suffix('\n').unwrap_or(&value).to_string();
+                    insert(key, value, attrs);
+                }
+                Markdown::Line(text) | Markdown::ListItem { text, .. } if options.inline_values => {
+                    for (key, value) in inline_values(&plaintext(&text)) {
+                        insert(key, value, vec![]);
+                    }
+                }
+                _ => {}
+            }
+        }
+        map
+    }
+
+    /// The code blocks that repeated an earlier key when the map was
+    /// parsed, whichever [`DuplicatePolicy`] decided the value.
+    pub fn duplicates(&self) -> &[DuplicateKey] {
+        &self.duplicates
+    }
+
+    /// The blocks that failed their [`SHA256_ATTR`] check when the map was
+    /// parsed. Their values are kept as written.
+    pub fn checksum_mismatches(&self) -> &[ChecksumMismatch] {
+        &self.checksum_mismatches
+    }
+
+    /// The hex SHA-256 to write as the value's [`SHA256_ATTR`] attribute:
+    /// of the value as written, without resolving references, or of the
+    /// decoded bytes of a base64 block.
+    pub fn checksum(&self, key: &str) -> Result<String, ValueError> {
+        let entry = self.entries.iter().find(|e| e.key == key).ok_or_else(|| ValueError::Missing(key.to_string()))?;
+        Ok(value_digest(&entry.value, &entry.attrs))
+    }
+
+    /// Insert or replace a value, keeping the position of the first insert.
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.insert_entry(Entry { key: key.into(), value: value.into(), attrs: vec![] });
+    }
+
+    fn insert_entry(&mut self, entry: Entry) {
+        match self.entries.iter_mut().find(|e| e.key == entry.key) {
+            Some(existing) => *existing = entry,
+            None => self.entries.push(entry),
+        }
+    }
+
+    /// A values document that [`ValueMap::parse`] reads back to this map:
+    /// the front matter, then a block per value in order, each fenced so
+    /// nothing in the value closes it early.
+    pub fn to_markdown(&self) -> String {
+        let mut out = self.front_matter.as_ref().map(FrontMatter::to_string).unwrap_or_default();
+        for (i, entry) in self.entries.iter().enumerate() {
+            if i > 0 || !out.is_empty() {
+                out.push('\n');
+            }
+            let info = FenceInfo { key: entry.key.clone(), attrs: entry.attrs.clone() };
+            let block = Markdown::Codeblock(info.to_string(), format!("{}\n", entry.value));
+            out.push_str(&format!("{block}\n"));
+        }
+        out
+    }
+
+    /// The metadata at the top of the document, see [`FrontMatter`].
+    pub fn front_matter(&self) -> Option<&FrontMatter> {
+        self.front_matter.as_ref()
+    }
+
+    /// The raw value, without resolving references.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|e| e.key == key)
+            .map(|e| e.value.as_str())
+    }
+
+    /// The [attributes](FenceInfo::attrs) of the block `key` was read from,
+    /// empty for values inserted or read inline.
+    pub fn attrs(&self, key: &str) -> &[(String, String)] {
+        self.entries.iter().find(|e| e.key == key).map_or(&[], |e| e.attrs.as_slice())
+    }
+
+    pub fn attr(&self, key: &str, name: &str) -> Option<&str> {
+        self.attrs(key).iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|e| (e.key.as_str(), e.value.as_str()))
+    }
+
+    /// The value as bytes: decoded when its block has the [`BASE64_ATTR`]
+    /// attribute, so images and protobuf payloads survive, otherwise the
+    /// [resolved](ValueMap::resolve) text.
+    pub fn bytes(&self, key: &str) -> Result<Cow<'_, [u8]>, ValueError> {
+        let entry = self.entries.iter().find(|e| e.key == key).ok_or_else(|| ValueError::Missing(key.to_string()))?;
+        if entry.attrs.iter().any(|(name, _)| name == BASE64_ATTR) {
+            return base64::decode(&entry.value)
+                .map(Cow::Owned)
+                .map_err(|at| ValueError::InvalidBase64 { key: key.to_string(), at });
+        }
+        self.resolve(key).map(|text| Cow::Owned(text.into_bytes()))
+    }
+
+    /// Insert or replace a binary value, kept as base64 lines with the
+    /// [`BASE64_ATTR`] attribute so [`ValueMap::to_markdown`] writes a block
+    /// [`ValueMap::bytes`] reads back.
+    pub fn insert_bytes(&mut self, key: impl Into<String>, bytes: &[u8]) {
+        let encoded = base64::encode(bytes);
+        let lines: Vec<&str> = encoded.as_bytes().chunks(76).map(|line| std::str::from_utf8(line).unwrap_or_default()).collect();
+        let attrs = vec![(BASE64_ATTR.to_string(), String::new())];
+        self.insert_entry(Entry { key: key.into(), value: lines.join("\n"), attrs });
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The value of `key` with references expanded: a value that is only
+    /// `{other}` is replaced by `other`'s value, and each `${other}` inside
+    /// a value is substituted.
+    pub fn resolve(&self, key: &str) -> Result<String, ValueError> {
+        resolve_chain(&|key| self.get(key), key, &mut vec![])
+    }
+}
+
+impl OpValue {
+    /// The operation's bytes with a `{key}` read from `values`, see
+    /// [`ValueMap::bytes`]. `None` for templates.
+    pub fn resolve_bytes<'a>(&'a self, values: &'a ValueMap) -> Option<Result<Cow<'a, [u8]>, ValueError>> {
+        match self {
+            OpValue::Value(key) => Some(values.bytes(key)),
+            value => value.as_bytes().map(Ok),
+        }
+    }
+}
+
+/// Resolve `key` with values from `get`, `chain` holding the keys being
+/// resolved around it.
+pub(crate) fn resolve_chain<'a>(
+    get: &dyn Fn(&str) -> Option<&'a str>,
+    key: &str,
+    chain: &mut Vec<String>,
+) -> Result<String, ValueError> {
+    if chain.iter().any(|k| k == key) {
+        chain.push(key.to_string());
+        return Err(ValueError::CircularReference { chain: chain.clone() });
+    }
+    if chain.len() >= MAX_REFERENCE_DEPTH {
+        chain.push(key.to_string());
+        return Err(ValueError::TooDeep { chain: chain.clone() });
+    }
+    let value = get(key).ok_or_else(|| ValueError::Missing(key.to_string()))?;
+    chain.push(key.to_string());
+
+    let resolved = match value_reference(value) {
+        Some(other) => resolve_chain(get, other, chain)?,
+        // only `${key}` is replaced, the rest is copied as written, escapes
+        // included, since values are json, scripts and paths
+        None => {
+            let mut out = String::new();
+            let mut rest = value;
+            while let Some((start, len)) = rest.find("${").and_then(|at| Some((at, rest[at + 2..].find('}')?))) {
+                out.push_str(&rest[..start]);
+                match template_expr(&rest[start + 2..start + 2 + len]) {
+                    TemplatePart::Value(name) => out.push_str(&resolve_chain(get, &name, chain)?),
+                    call => out.push_str(&call.to_string()),
+                }
+                rest = &rest[start + 3 + len..];
+            }
+            out.push_str(rest);
+            out
+        }
+    };
+    chain.pop();
+    Ok(resolved)
+}
+
+/// Where a keyed code block is in a values document.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValueBlock {
+    pub key: String,
+    /// The key after the opening fence, without the attributes after it.
+    pub key_span: Span,
+    pub attrs: Vec<(String, String)>,
+    /// The value, without the newline before the closing fence.
+    pub body: Span,
+}
+
+/// The keyed code blocks of a values document, in order, after its front
+/// matter. A block ends at the next fence as long as the one opening it,
+/// as in [`ValueMap::parse`].
+pub fn value_blocks(input: &str) -> Vec<ValueBlock> {
+    let mut blocks = vec![];
+    let mut pos = parse_front_matter(input).1;
+    while let Some(found) = input[pos..].find("```") {
+        let fence_start = pos + found;
+        let key_start = fence_start + input[fence_start..].len() - input[fence_start..].trim_start_matches('`').len();
+        let fence = &input[fence_start..key_start];
+        let key_end = input[key_start..]
+            .find(['\n', '\r'])
+            .map_or(input.len(), |at| key_start + at);
+        let Some(close) = input[key_end..].find(fence).map(|at| key_end + at) else {
+            break;
+        };
+        pos = close + fence.len();
+        let info = &input[key_start..key_end];
+        let FenceInfo { key, attrs } = FenceInfo::parse(info);
+        if key.is_empty() {
+            continue;
+        }
+        let key_start = key_start + (info.len() - info.trim_start().len());
+        let body_start = key_end + line_ending(&input[key_end..]).map_or(0, |(_, ending)| ending.len());
+        let body = &input[body_start.min(close)..close];
+        let body = body
+            .strip_suffix("\r\n")
+            .or_else(|| body.strip_suffix(['\n', '\r']))
+            .unwrap_or(body);
+        blocks.push(ValueBlock {
+            key_span: Span::new(key_start, key_start + key.len()),
+            key,
+            attrs,
+            body: Span::new(body_start.min(close), body_start.min(close) + body.len()),
+        });
+    }
+    blocks
+}
+
+/// `Some("key")` when `value` is exactly a `{key}` reference.
+pub(crate) fn value_reference(value: &str) -> Option<&str> {
+    let key = value.trim().strip_prefix('{')?.strip_suffix('}')?;
+    is_value_key(key).then_some(key)
+}
+
+/// Whether `key` can be referenced as `{key}`.
+fn is_value_key(key: &str) -> bool {
+    !key.is_empty() && key.chars().all(|c| c.is_alphanumeric() || matches!(c, '.' | '_' | '-'))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+
+    #[test]
+    fn test_value_map_resolve() {
+        let input = "```a\n{b}\n```\n```b\nx${c}y\n```\n```c\n1\n```\n```json\n{\"c\":1}\n```\n";
+        let values = ValueMap::parse(input);
+        assert_eq!(values.len(), 4);
+        assert_eq!(values.get("a"), Some("{b}"));
+        assert_eq!(values.resolve("a"), Ok("x1y".into()));
+        assert_eq!(values.resolve("json"), Ok("{\"c\":1}".into()));
+        assert_eq!(values.resolve("d"), Err(ValueError::Missing("d".into())));
+        let values = ValueMap::parse("```p.json\n{\"p\": \"C:\\\\x\", \"t\": \"\\`${h}\\$ ${md5(h)}\"}\n```\n```h\n1\n```\n");
+        assert_eq!(values.resolve("p.json"), Ok("{\"p\": \"C:\\\\x\", \"t\": \"\\`1\\$ ${md5(h)}\"}".into()));
+
+        let values = ValueMap::parse("\u{feff}# v\r\n```a\r\n1\r\n2\r\n```\r\n```b\r3\r```\r");
+        assert_eq!(values.get("a"), Some("1\n2"));
+        assert_eq!(values.get("b"), Some("3"));
+
+        let mut values = ValueMap::new();
+        values.insert("a", "{b}");
+        values.insert("b", "-${a}-");
+        assert_eq!(
+            values.resolve("a"),
+            Err(ValueError::CircularReference {
+                chain: vec!["a".into(), "b".into(), "a".into()]
+            })
+        );
+    }
+    #[test]
+    fn test_front_matter() {
+        let input = "\u{feff}---\r\nowner: web-team\r\n# note\r\nenvironment: \"staging\"\r\ntags:\r\n  - a\r\n---\r\n```a\r\n1\r\n```\r\n";
+        let values = ValueMap::parse(input);
+        let matter = values.front_matter().unwrap();
+        assert_eq!(matter.format, FrontMatterFormat::Yaml);
+        assert_eq!((matter.owner(), matter.environment(), matter.version()), (Some("web-team"), Some("staging"), None));
+        assert_eq!(matter.get("tags"), Some(""));
+        assert_eq!(&input[matter.span.start..matter.span.end], &input[3..input.find("```").unwrap() - 2]);
+        assert_eq!(values.get("a"), Some("1"));
+        assert_eq!(value_blocks(input).len(), 1);
+
+        let values = ValueMap::parse("+++\nversion = 3\nowner = 'ops'\n+++\n```b\nx\n```");
+        assert_eq!(values.front_matter().unwrap().format, FrontMatterFormat::Toml);
+        assert_eq!(values.front_matter().unwrap().version(), Some("3"));
+        assert_eq!(values.get("b"), Some("x"));
+
+        assert_eq!(parse_front_matter("---\nowner: x\n"), (None, 0));
+        assert_eq!(ValueMap::parse("```---\n1\n```").get("---"), Some("1"));
+    }
+
+    #[test]
+    fn test_inline_values() {
+        let input = "# hosts\nuse `api: 10.0.0.1` or `web:1.1.1.1`, not `x y: 1`\n```api\n10.0.0.2\n```\n` tail: x`\n";
+        assert_eq!(ValueMap::parse(input).len(), 1);
+        let values = ValueMap::parse_with(input, &ValueMapOptions { inline_values: true, ..Default::default() });
+        let found: Vec<_> = values.iter().collect();
+        assert_eq!(found, vec![("api", "10.0.0.2"), ("web", "1.1.1.1")]);
+        assert_eq!(inline_values("`a: 1` and `b: 2"), vec![("a".to_string(), "1".to_string())]);
+    }
+
+    #[test]
+    fn test_list_items() {
+        let input = "# hosts\n- api.a.com\n  * `web: b.com`\n+ c.com\n-no space\n**bold**\n";
+        assert_eq!(list_items(input), vec!["api.a.com", "`web: b.com`", "c.com"]);
+        let (_, md) = parse_markdown(input).unwrap();
+        assert_eq!(
+            md[2],
+            Markdown::ListItem {
+                indent: 2,
+                marker: '*',
+                text: vec![MarkdownInline::Plaintext("`web: b.com`".into())]
+            }
+        );
+        assert_eq!(into_parts(md).0, input);
+        let values = ValueMap::parse_with(input, &ValueMapOptions { inline_values: true, ..Default::default() });
+        assert_eq!(values.get("web"), Some("b.com"));
+    }
+
+    #[test]
+    fn test_duplicate_keys() {
+        let input = "```a\n1\n```\n```b\n2\n```\n```a\n3\n```\n";
+        let values = ValueMap::parse(input);
+        assert_eq!(values.get("a"), Some("3"));
+        assert_eq!(
+            values.duplicates(),
+            [DuplicateKey { key: "a".into(), first: Span::new(3, 4), duplicate: Span::new(25, 26) }]
+        );
+        assert_eq!(values.duplicates()[0].to_string(), "value `a` at 25 is already defined at 3");
+
+        let options = ValueMapOptions { duplicates: DuplicatePolicy::KeepFirst, ..Default::default() };
+        assert_eq!(ValueMap::parse_with(input, &options).get("a"), Some("1"));
+    }
+
+    #[test]
+    fn test_value_blocks() {
+        let input = "# values\r\n```a.json\r\n{}\r\n```\n```\nanon\n```\n```b\n```";
+        let blocks = value_blocks(input);
+        assert_eq!(
+            blocks,
+            vec![
+                ValueBlock {
+                    key: "a.json".into(),
+                    key_span: Span::new(13, 19),
+                    attrs: vec![],
+                    body: Span::new(21, 23),
+                },
+                ValueBlock { key: "b".into(), key_span: Span::new(45, 46), attrs: vec![], body: Span::new(47, 47) },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_to_markdown() {
+        let input = "+++\nowner = \"@a\"\nversion = 2\n+++\n````a.md title='x y'\n```js\nlet s = `${b}`;\n```\n````\n```b base64\n\n```\n";
+        let values = ValueMap::parse(input);
+        assert_eq!(values.get("a.md"), Some("```js\nlet s = `${b}`;\n```"));
+        let markdown = values.to_markdown();
+        assert_eq!(
+            markdown,
+            "+++\nowner = \"@a\"\nversion = 2\n+++\n\n````a.md title=\"x y\"\n```js\nlet s = `${b}`;\n```\n````\n\n```b base64\n\n```\n"
+        );
+        assert_eq!(ValueMap::parse(&markdown), values);
+
+        let (_, md_arr) = parse_markdown(input).unwrap();
+        assert_eq!(md_arr.iter().map(Markdown::to_string).collect::<String>(), input);
+    }
+
+    #[test]
+    fn test_binary_values() {
+        let png = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0xff];
+        let values = ValueMap::parse("```img.png base64\niVBORw0KGgoA\n/w==\n```\n```text\n{img.png}\n```\n```bad base64\nab!\n```\n");
+        assert_eq!(values.bytes("img.png").as_deref(), Ok(&png[..]));
+        assert_eq!(values.bytes("text").as_deref(), Ok(&b"iVBORw0KGgoA\n/w=="[..]));
+        assert_eq!(values.bytes("bad"), Err(ValueError::InvalidBase64 { key: "bad".into(), at: 2 }));
+        let file = OpValue::value_ref("img.png");
+        assert_eq!(file.resolve_bytes(&values).unwrap().as_deref(), Ok(&png[..]));
+        assert_eq!(OpValue::raw("x").resolve_bytes(&values).unwrap().as_deref(), Ok(&b"x"[..]));
+
+        let mut map = ValueMap::new();
+        map.insert_bytes("blob", &[0u8; 100]);
+        assert_eq!(map.get("blob").map(|b| b.lines().count()), Some(2));
+        assert_eq!(ValueMap::parse(&map.to_markdown()).bytes("blob").as_deref(), Ok(&[0u8; 100][..]));
+    }
+
+    #[test]
+    fn test_checksums() {
+        let abc = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        let input = format!(
+            "```a sha256={abc}\r\nabc\r\n```\n```b base64 sha256={}\nYWJj\n```\n```c sha256={abc}\nab\n```\n",
+            abc.to_uppercase()
+        );
+        let values = ValueMap::parse(&input);
+        assert_eq!(values.checksum("a"), Ok(abc.to_string()));
+        assert_eq!(values.checksum("b"), Ok(abc.to_string()));
+        let mismatches = values.checksum_mismatches();
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!((mismatches[0].key.as_str(), mismatches[0].span), ("c", Span::new(183, 184)));
+        assert_eq!(values.get("c"), Some("ab"));
+        assert!(mismatches[0].to_string().starts_with("value `c` has sha256 fb8e20fc"));
+
+        let values = ValueMap::parse("```a\n${b}\n```\n```b\nabc\n```\n");
+        let checksum = values.checksum("a").unwrap();
+        assert_ne!(checksum, abc);
+        let input = format!("```a sha256={checksum}\n${{b}}\n```\n```b\nabc\n```\n");
+        assert_eq!(ValueMap::parse(&input).checksum_mismatches(), []);
+        assert_eq!(values.checksum("x"), Err(ValueError::Missing("x".into())));
+    }
+
+    #[test]
+    fn test_fence_info() {
+        let info = FenceInfo::parse("mock.json type=json title=\"a b\" base64");
+        assert_eq!(info.key, "mock.json");
+        assert_eq!(info.attr("type"), Some("json"));
+        assert_eq!(info.attr("title"), Some("a b"));
+        assert_eq!(info.attr("base64"), Some(""));
+
+        let input = "``` mock.json type='json'\n{}\n```\n";
+        assert_eq!(value_blocks(input)[0].key_span, Span::new(4, 13));
+        let values = ValueMap::parse(input);
+        assert_eq!(values.get("mock.json"), Some("{}"));
+        assert_eq!(values.attr("mock.json", "type"), Some("json"));
+        assert_eq!(values.attrs("missing"), []);
+    }
+
     #[test]
     fn test_markdown() {
         let input = r#"
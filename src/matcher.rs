@@ -0,0 +1,340 @@
+//! Whether a [`Pattern`] applies to a request url.
+use std::borrow::Cow;
+use std::time::Duration;
+
+use crate::pattern::{Pattern, PortMatcher};
+use crate::Uri;
+use crate::normalize::{ascii_lowercase, lowercase, normalize_path};
+use crate::search::glob_match;
+
+/// How [`Pattern::matches_with`] compares urls. Hostnames are always
+/// compared ignoring case, as whistle does.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MatchOptions {
+    /// Compare paths ignoring case too, for mocks served from a case
+    /// insensitive filesystem.
+    pub case_insensitive_path: bool,
+    /// Resolve `.` and `..` segments and duplicate slashes in the path of
+    /// both the pattern and the url before comparing, so `/api//v1/../x`
+    /// is matched as `/api/x`.
+    pub normalize_path: bool,
+}
+
+/// What is known about a request when rules are resolved for it. Filters
+/// on parts left out don't match.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Request<'a> {
+    pub url: &'a str,
+    pub method: Option<&'a str>,
+    pub headers: Vec<(&'a str, &'a str)>,
+    /// The response status, once there is one.
+    pub status: Option<u16>,
+    /// The start of the body, as far as it was read.
+    pub body: Option<&'a str>,
+}
+
+impl<'a> Request<'a> {
+    pub fn new(url: &'a str) -> Self {
+        Request { url, ..Default::default() }
+    }
+}
+
+/// Told about every rule checked while resolving a request, for metrics
+/// such as per-rule hit rates. `rule` is the rule's position in the set and
+/// `elapsed` how long checking it took. It takes `&self` so one observer
+/// with atomic counters can be shared by all the threads matching requests.
+pub trait MatchObserver {
+    fn observe(&self, rule: usize, matched: bool, elapsed: Duration);
+}
+
+impl<F: Fn(usize, bool, Duration)> MatchObserver for F {
+    fn observe(&self, rule: usize, matched: bool, elapsed: Duration) {
+        self(rule, matched, elapsed)
+    }
+}
+
+/// A request url split into the parts patterns look at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestUrl<'a> {
+    /// Empty when the url has none, `a.com/x`.
+    pub scheme: &'a str,
+    pub host: &'a str,
+    pub port: Option<u16>,
+    /// The path, `/` when the url has none.
+    pub path: &'a str,
+    /// The query with its `?`, or empty.
+    pub query: &'a str,
+}
+
+impl<'a> RequestUrl<'a> {
+    pub fn parse(url: &'a str) -> RequestUrl<'a> {
+        let (scheme, rest) = url.split_once("://").unwrap_or(("", url));
+        let rest = rest.split('#').next().unwrap_or_default();
+        let authority_end = rest.find(['/', '?']).unwrap_or(rest.len());
+        let (authority, path_and_query) = rest.split_at(authority_end);
+        let authority = authority.rsplit('@').next().unwrap_or_default();
+        let (host, port) = split_host_port(authority);
+        let (path, query) = match path_and_query.find('?') {
+            Some(at) => path_and_query.split_at(at),
+            None => (path_and_query, ""),
+        };
+        RequestUrl {
+            scheme,
+            host,
+            port: port.and_then(|p| p.parse().ok()),
+            path: if path.is_empty() { "/" } else { path },
+            query,
+        }
+    }
+
+    /// The port, or the default port of the scheme.
+    pub fn effective_port(&self) -> Option<u16> {
+        let is = |scheme: &str| self.scheme.eq_ignore_ascii_case(scheme);
+        self.port.or(match () {
+            _ if is("http") || is("ws") => Some(80),
+            _ if is("https") || is("wss") => Some(443),
+            _ => None,
+        })
+    }
+}
+
+/// Split `host:port`, keeping an `[ipv6]` host in its brackets.
+pub(crate) fn split_host_port(authority: &str) -> (&str, Option<&str>) {
+    if authority.starts_with('[') {
+        return match authority.find("]:") {
+            Some(at) => (&authority[..at + 1], Some(&authority[at + 2..])),
+            None => (authority, None),
+        };
+    }
+    match authority.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') => (host, Some(port)),
+        _ => (authority, None),
+    }
+}
+
+/// Whether `host` matches the host of a pattern, ignoring case:
+///
+/// - `*.a.com` matches one level of subdomains, `x.a.com` but not `a.com`
+///   or `x.y.a.com`,
+/// - `**.a.com` matches subdomains at any level, not `a.com` itself,
+/// - `.a.com` matches `a.com` and all its subdomains,
+/// - `*` inside a label, `api-*.a.com`, matches within that label only.
+pub fn host_matches(pattern: &str, host: &str) -> bool {
+    let pattern = ascii_lowercase(pattern);
+    let host = ascii_lowercase(host);
+    if let Some(domain) = pattern.strip_prefix('.') {
+        return host == domain || host.ends_with(&*pattern);
+    }
+    labels_match(Some(&pattern), Some(&host))
+}
+
+/// The first label of `labels` and the ones after it, `None` once there
+/// are no more.
+fn split_label(labels: &str) -> (&str, Option<&str>) {
+    match labels.split_once('.') {
+        Some((first, rest)) => (first, Some(rest)),
+        None => (labels, None),
+    }
+}
+
+fn labels_match(pattern: Option<&str>, host: Option<&str>) -> bool {
+    let Some(pattern) = pattern else {
+        return host.is_none();
+    };
+    let (label, rest) = split_label(pattern);
+    if label == "**" {
+        let mut host = host;
+        while let Some(labels) = host {
+            host = split_label(labels).1;
+            if labels_match(rest, host) {
+                return true;
+            }
+        }
+        return false;
+    }
+    let Some(host) = host else {
+        return false;
+    };
+    let (first, others) = split_label(host);
+    let matches = if label.contains('*') { glob_match(label, first) } else { label == first };
+    matches && labels_match(rest, others)
+}
+
+impl Pattern {
+    pub fn matches(&self, url: &str) -> bool {
+        self.matches_with(url, &MatchOptions::default())
+    }
+
+    /// Whether the pattern applies to `url`: the scheme when the pattern has
+    /// one, the [host](host_matches), the [port](crate::pattern::PortMatcher),
+    /// and the path and query as a [prefix](Uri::path_starts_with).
+    pub fn matches_with(&self, url: &str, options: &MatchOptions) -> bool {
+        self.matches_parsed(&RequestUrl::parse(url), options)
+    }
+
+    /// [`Pattern::matches_with`] for a url already split, so matching one
+    /// request against many patterns splits it once.
+    pub(crate) fn matches_parsed(&self, url: &RequestUrl, options: &MatchOptions) -> bool {
+        self.scheme.matches(url.scheme) && uri_matches(&self.uri, self.port, url, options)
+    }
+}
+
+/// Whether the host, `port`, path and query of the pattern `uri` match
+/// `url`, the scheme being left to the caller. A rule's source is matched
+/// with it as it is, without building a [`Pattern`].
+pub(crate) fn uri_matches(uri: &Uri, port: PortMatcher, url: &RequestUrl, options: &MatchOptions) -> bool {
+    if !host_matches(split_host_port(&uri.host).0, url.host) || !port.matches(url.effective_port()) {
+        return false;
+    }
+    let (mut pattern_path, mut url_path) = match options.normalize_path {
+        true => (normalize_path(&uri.path, false), normalize_path(url.path, false)),
+        false => (Cow::Borrowed(uri.path.as_str()), Cow::Borrowed(url.path)),
+    };
+    let (mut pattern_query, mut url_query) = (Cow::Borrowed(uri.query.as_str()), Cow::Borrowed(url.query));
+    if options.case_insensitive_path {
+        for part in [&mut pattern_path, &mut url_path, &mut pattern_query, &mut url_query] {
+            if let Cow::Owned(lower) = lowercase(part) {
+                *part = Cow::Owned(lower);
+            }
+        }
+    }
+    path_prefix_matches(&pattern_path, &pattern_query, &url_path, &url_query)
+}
+
+/// Whether `prefix_path` and `prefix_query` are a prefix of `path` and
+/// `query` that ends at a segment boundary: `/api` covers `/api` and
+/// `/api/x` but not `/apix`, `/api/` only what is below it. With a query the
+/// paths must be equal and the query is a plain prefix.
+fn path_prefix_matches(prefix_path: &str, prefix_query: &str, path: &str, query: &str) -> bool {
+    if !prefix_query.is_empty() {
+        return prefix_path.trim_end_matches('/') == path.trim_end_matches('/') && query.starts_with(prefix_query);
+    }
+    let mut segments = path.trim_start_matches('/').split('/');
+    let prefix = prefix_path.trim_start_matches('/');
+    if prefix.is_empty() {
+        return true;
+    }
+    let mut expected = prefix.split('/').peekable();
+    while let Some(want) = expected.next() {
+        // a trailing slash on the prefix takes whatever follows it
+        if want.is_empty() && expected.peek().is_none() {
+            return segments.next().is_some();
+        }
+        if segments.next() != Some(want) {
+            return false;
+        }
+    }
+    true
+}
+
+impl Uri {
+    /// The segments of the path after its leading `/`, `/a/b/` giving `a`,
+    /// `b` and an empty last segment. A uri without a path has none.
+    pub fn path_segments(&self) -> impl Iterator<Item = &str> {
+        let path = self.path.strip_prefix('/').unwrap_or(&self.path);
+        path.split('/').filter(move |_| !self.path.is_empty())
+    }
+
+    /// Whether the path and query of `prefix` start this uri's at a segment
+    /// boundary, the way a pattern's path is matched.
+    pub fn path_starts_with(&self, prefix: &Uri) -> bool {
+        path_prefix_matches(&prefix.path, &prefix.query, &self.path, &self.query)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_request_url() {
+        let url = RequestUrl::parse("https://user@A.com:8443/x/y?q=1#h");
+        assert_eq!(
+            url,
+            RequestUrl { scheme: "https", host: "A.com", port: Some(8443), path: "/x/y", query: "?q=1" }
+        );
+        assert_eq!(RequestUrl::parse("http://[::1]").effective_port(), Some(80));
+        assert_eq!(RequestUrl::parse("http://[::1]:81").host, "[::1]");
+        assert_eq!(RequestUrl::parse("a.com?x").path, "/");
+    }
+
+    #[test]
+    fn test_host_matches() {
+        let cases = [
+            ("a.com", "a.com", true),
+            ("a.com", "A.COM", true),
+            ("a.com", "x.a.com", false),
+            ("*.a.com", "a.com", false),
+            ("*.a.com", "x.a.com", true),
+            ("*.a.com", "X.A.com", true),
+            ("*.a.com", "x.y.a.com", false),
+            ("*.a.com", "xa.com", false),
+            ("**.a.com", "a.com", false),
+            ("**.a.com", "x.a.com", true),
+            ("**.a.com", "x.y.z.a.com", true),
+            ("**.a.com", "x.b.com", false),
+            (".a.com", "a.com", true),
+            (".a.com", "x.y.a.com", true),
+            (".a.com", "xa.com", false),
+            ("api-*.a.com", "api-v2.a.com", true),
+            ("api-*.a.com", "api.a.com", false),
+            ("api-*.a.com", "api-v2.x.a.com", false),
+            ("x.*.a.com", "x.y.a.com", true),
+            ("x.**.a.com", "x.y.z.a.com", true),
+            ("x.**.a.com", "x.a.com", false),
+            ("*", "localhost", true),
+            ("*", "a.com", false),
+        ];
+        for (pattern, host, expected) in cases {
+            assert_eq!(host_matches(pattern, host), expected, "{pattern} vs {host}");
+        }
+        assert!(Pattern::parse("*.a.com/api").unwrap().matches("https://www.a.com/api/x"));
+        assert!(!Pattern::parse("*.a.com:8080").unwrap().matches("http://www.a.com/"));
+    }
+
+    #[test]
+    fn test_case_sensitivity() {
+        let pattern = Pattern::parse("WWW.A.com/Api").unwrap();
+        assert!(pattern.matches("https://www.a.COM/Api/users"));
+        assert!(!pattern.matches("https://www.a.com/api/users"));
+        let options = MatchOptions { case_insensitive_path: true, ..Default::default() };
+        assert!(pattern.matches_with("https://www.a.com/API/users", &options));
+        assert!(!pattern.matches("https://www.b.com/Api"));
+
+        let pattern = Pattern::parse("http://a.com:8080/x?id=1").unwrap();
+        assert!(pattern.matches("http://a.com:8080/x?id=1&y=2"));
+        assert!(!pattern.matches("https://a.com:8080/x?id=1"));
+        assert!(!pattern.matches("http://a.com/x?id=1"));
+        assert!(Pattern::parse("a.com:443").unwrap().matches("https://a.com/"));
+    }
+
+    #[test]
+    fn test_path_prefix() {
+        let uri = |s| crate::parse_uri(s).unwrap().1;
+        assert_eq!(uri("a.com/x/y/").path_segments().collect::<Vec<_>>(), vec!["x", "y", ""]);
+        assert_eq!(uri("a.com").path_segments().count(), 0);
+        let cases = [
+            ("a.com/api", "a.com/api", true),
+            ("a.com/api/users", "a.com/api", true),
+            ("a.com/apix", "a.com/api", false),
+            ("a.com/api", "a.com/api/", false),
+            ("a.com/api/x", "a.com/api/", true),
+            ("a.com/api?x=1&y", "a.com/api?x=1", true),
+            ("a.com/api/z?x=1", "a.com/api?x=1", false),
+            ("a.com/x", "a.com/", true),
+            ("a.com", "a.com", true),
+        ];
+        for (url, prefix, expected) in cases {
+            assert_eq!(uri(url).path_starts_with(&uri(prefix)), expected, "{url} vs {prefix}");
+        }
+        assert!(!Pattern::parse("a.com/api").unwrap().matches("http://a.com/apix"));
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        let pattern = Pattern::parse("a.com/api/x").unwrap();
+        let url = "http://a.com/api//v1/../x";
+        assert!(!pattern.matches(url));
+        assert!(pattern.matches_with(url, &MatchOptions { normalize_path: true, ..Default::default() }));
+    }
+}
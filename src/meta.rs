@@ -0,0 +1,240 @@
+//! Annotations in the trailing comment of a rule, `key:value` words such as
+//! `a.com 127.0.0.1 # expires:2025-01-01 owner:@alice local api`.
+//!
+//! `owner` names who to ask about the rule, `starts` and `expires` the days
+//! it is meant to apply between. Other annotations are kept as written and
+//! the remaining words are the note.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::rule_file::{RuleFile, RuleId};
+
+/// A day of the proleptic Gregorian calendar, written `YYYY-MM-DD`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    pub year: i32,
+    pub month: u8,
+    pub day: u8,
+}
+
+impl Date {
+    /// `None` unless `text` is `YYYY-MM-DD` naming a day that exists.
+    pub fn parse(text: &str) -> Option<Date> {
+        let mut parts = text.splitn(3, '-');
+        let mut part = |len: usize| {
+            let part = parts.next().filter(|p| p.len() == len && p.bytes().all(|b| b.is_ascii_digit()))?;
+            part.parse::<u32>().ok()
+        };
+        let (year, month, day) = (part(4)? as i32, part(2)? as u8, part(2)? as u8);
+        let date = Date { year, month, day };
+        ((1..=12).contains(&month) && (1..=date.days_in_month()).contains(&day)).then_some(date)
+    }
+
+    /// The current day in UTC.
+    pub fn today() -> Date {
+        let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        Date::from_days((seconds / 86_400) as i64)
+    }
+
+    /// The day `days` after 1970-01-01.
+    pub fn from_days(days: i64) -> Date {
+        // Howard Hinnant's civil_from_days, with years starting in March
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let day_of_era = z.rem_euclid(146_097);
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+        Date { year: year as i32, month: month as u8, day: day as u8 }
+    }
+
+    fn days_in_month(&self) -> u8 {
+        let leap = self.year % 4 == 0 && (self.year % 100 != 0 || self.year % 400 == 0);
+        match self.month {
+            2 if leap => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        }
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RuleMeta {
+    /// `owner:@alice`, without the `@`.
+    pub owner: Option<String>,
+    /// `starts:2025-01-01`, the first day the rule applies.
+    pub starts: Option<Date>,
+    /// `expires:2025-01-01`, the first day the rule no longer applies.
+    pub expires: Option<Date>,
+    /// Every other `key:value`, and `starts` or `expires` that aren't dates,
+    /// in order.
+    pub annotations: Vec<(String, String)>,
+    /// The words that aren't annotations.
+    pub note: String,
+}
+
+impl RuleMeta {
+    /// Read the annotations of `comment`, the text after its `#`. A word is
+    /// an annotation when a name of letters, digits, `-` or `_` and a value
+    /// are joined by `:`, leaving urls like `https://a.com` in the note.
+    pub fn parse(comment: &str) -> RuleMeta {
+        let mut meta = RuleMeta::default();
+        let mut note = vec![];
+        for word in comment.split_whitespace() {
+            let annotation = word.split_once(':').filter(|(key, value)| {
+                !key.is_empty()
+                    && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+                    && !value.is_empty()
+                    && !value.starts_with('/')
+            });
+            let Some((key, value)) = annotation else {
+                note.push(word);
+                continue;
+            };
+            match (key, Date::parse(value)) {
+                ("owner", _) => meta.owner = Some(value.strip_prefix('@').unwrap_or(value).to_string()),
+                ("starts", Some(date)) => meta.starts = Some(date),
+                ("expires", Some(date)) => meta.expires = Some(date),
+                _ => meta.annotations.push((key.to_string(), value.to_string())),
+            }
+        }
+        meta.note = note.join(" ");
+        meta
+    }
+
+    /// The value of annotation `key` other than `owner`, `starts` and
+    /// `expires`.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.annotations.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    /// Whether annotation `key` is `value`, for `owner`, `starts` and
+    /// `expires` too. Owners compare without the `@` and dates as days.
+    pub fn has(&self, key: &str, value: &str) -> bool {
+        match key {
+            "owner" => self.owner.as_deref() == Some(value.strip_prefix('@').unwrap_or(value)),
+            "starts" if self.starts.is_some() => self.starts == Date::parse(value),
+            "expires" if self.expires.is_some() => self.expires == Date::parse(value),
+            _ => self.annotations.iter().any(|(k, v)| k == key && v == value),
+        }
+    }
+
+    pub fn is_expired(&self, today: Date) -> bool {
+        self.expires.is_some_and(|expires| expires <= today)
+    }
+
+    /// Whether `today` is between `starts` and `expires`.
+    pub fn is_active(&self, today: Date) -> bool {
+        self.starts.is_none_or(|starts| starts <= today) && !self.is_expired(today)
+    }
+}
+
+impl RuleFile {
+    /// The annotations of the rule at `id`, empty for a rule without a
+    /// trailing comment and `None` when there is no rule at `id`.
+    pub fn meta(&self, id: RuleId) -> Option<RuleMeta> {
+        self.rules().find(|(rule, _, _)| *rule == id)?;
+        Some(self.comment(id).map(RuleMeta::parse).unwrap_or_default())
+    }
+
+    /// The annotations of every rule, disabled ones included, in document
+    /// order.
+    pub fn metas(&self) -> impl Iterator<Item = (RuleId, RuleMeta)> + '_ {
+        self.rules().map(|(id, _, _)| (id, self.comment(id).map(RuleMeta::parse).unwrap_or_default()))
+    }
+
+    /// The rules whose annotations satisfy `f`.
+    pub fn rules_where(&self, f: impl Fn(&RuleMeta) -> bool) -> Vec<RuleId> {
+        self.metas().filter(|(_, meta)| f(meta)).map(|(id, _)| id).collect()
+    }
+
+    /// The rules with annotation `key` set to `value`, see [`RuleMeta::has`].
+    pub fn rules_with(&self, key: &str, value: &str) -> Vec<RuleId> {
+        self.rules_where(|meta| meta.has(key, value))
+    }
+
+    /// The rules of each owner, in document order. Rules without an owner
+    /// are left out, [`RuleFile::unowned_rules`] lists them.
+    pub fn rules_by_owner(&self) -> BTreeMap<String, Vec<RuleId>> {
+        let mut owners: BTreeMap<String, Vec<RuleId>> = BTreeMap::new();
+        for (id, meta) in self.metas() {
+            if let Some(owner) = meta.owner {
+                owners.entry(owner).or_default().push(id);
+            }
+        }
+        owners
+    }
+
+    pub fn unowned_rules(&self) -> Vec<RuleId> {
+        self.rules_where(|meta| meta.owner.is_none())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rule_file::LineItem;
+
+    #[test]
+    fn test_date() {
+        assert_eq!(Date::parse("2024-02-29"), Some(Date { year: 2024, month: 2, day: 29 }));
+        assert_eq!(Date::parse("2023-02-29"), None);
+        assert_eq!(Date::parse("2024-1-01"), None);
+        assert_eq!(Date::parse("2024-01-01x"), None);
+        assert_eq!(Date::from_days(0).to_string(), "1970-01-01");
+        assert_eq!(Date::from_days(-1).to_string(), "1969-12-31");
+        assert_eq!(Date::from_days(19_782).to_string(), "2024-02-29");
+        assert_eq!(Date::from_days(20_000).to_string(), "2024-10-04");
+    }
+
+    #[test]
+    fn test_rule_meta() {
+        let file = RuleFile::parse(
+            "a.com 1.1.1.1 # expires:2025-01-01 owner:@alice ticket:OPS-1 see https://a.com\n#off b.com c.com # starts:2030-01-01\nc.com d.com",
+        );
+        assert!(matches!(file.lines()[0].item, LineItem::Rule(_)));
+        assert_eq!(file.comment(RuleId(0)), Some("expires:2025-01-01 owner:@alice ticket:OPS-1 see https://a.com"));
+        let meta = file.meta(RuleId(0)).unwrap();
+        assert_eq!(meta.owner.as_deref(), Some("alice"));
+        assert_eq!(meta.expires, Date::parse("2025-01-01"));
+        assert_eq!(meta.get("ticket"), Some("OPS-1"));
+        assert_eq!(meta.note, "see https://a.com");
+        assert!(meta.is_expired(Date::parse("2025-01-01").unwrap()));
+        assert!(meta.is_active(Date::parse("2024-12-31").unwrap()));
+
+        let disabled = file.meta(RuleId(1)).unwrap();
+        assert!(!disabled.is_active(Date::parse("2029-12-31").unwrap()));
+        assert_eq!(file.meta(RuleId(2)), Some(RuleMeta::default()));
+        assert_eq!(file.meta(RuleId(3)), None);
+    }
+
+    #[test]
+    fn test_meta_queries() {
+        let file = RuleFile::parse(
+            "a.com 127.0.0.1 # owner:@web team:front
+# owner:@api
+b.com 127.0.0.1 # owner:api expires:2030-01-01
+#off c.com 127.0.0.1 # owner:@web
+d.com 127.0.0.1",
+        );
+        let owners = file.rules_by_owner();
+        assert_eq!(owners.keys().collect::<Vec<_>>(), ["api", "web"]);
+        assert_eq!(owners["web"], [RuleId(0), RuleId(3)]);
+        assert_eq!(file.unowned_rules(), [RuleId(4)]);
+        assert_eq!(file.rules_with("owner", "@api"), [RuleId(2)]);
+        assert_eq!(file.rules_with("team", "front"), [RuleId(0)]);
+        assert_eq!(file.rules_with("expires", "2030-01-01"), [RuleId(2)]);
+        assert_eq!(file.rules_where(|meta| meta.expires.is_none()).len(), 3);
+    }
+}
@@ -0,0 +1,78 @@
+//! `method://` values, the method a request is sent with instead of its own.
+use std::fmt;
+
+/// An HTTP method, spelled as `http::Method` spells it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Method {
+    Get,
+    Head,
+    Post,
+    Put,
+    Delete,
+    Connect,
+    Options,
+    Trace,
+    Patch,
+    /// Any other valid token, uppercased, `PROPFIND`.
+    Extension(String),
+}
+
+impl Method {
+    /// Read a method ignoring case. `None` for text that isn't an HTTP
+    /// token, such as `get post` or `{key}`.
+    pub fn parse(value: &str) -> Option<Method> {
+        let is_tchar = |c: char| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c);
+        if value.is_empty() || !value.chars().all(is_tchar) {
+            return None;
+        }
+        let method = value.to_ascii_uppercase();
+        Some(match method.as_str() {
+            "GET" => Method::Get,
+            "HEAD" => Method::Head,
+            "POST" => Method::Post,
+            "PUT" => Method::Put,
+            "DELETE" => Method::Delete,
+            "CONNECT" => Method::Connect,
+            "OPTIONS" => Method::Options,
+            "TRACE" => Method::Trace,
+            "PATCH" => Method::Patch,
+            _ => Method::Extension(method),
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Method::Get => "GET",
+            Method::Head => "HEAD",
+            Method::Post => "POST",
+            Method::Put => "PUT",
+            Method::Delete => "DELETE",
+            Method::Connect => "CONNECT",
+            Method::Options => "OPTIONS",
+            Method::Trace => "TRACE",
+            Method::Patch => "PATCH",
+            Method::Extension(method) => method,
+        }
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_method() {
+        assert_eq!(Method::parse("post"), Some(Method::Post));
+        assert_eq!(Method::parse("PropFind"), Some(Method::Extension("PROPFIND".into())));
+        assert_eq!(Method::Patch.to_string(), "PATCH");
+        for invalid in ["", "get post", "{m}", "G(E)T", "é"] {
+            assert_eq!(Method::parse(invalid), None, "{invalid}");
+        }
+    }
+}
@@ -0,0 +1,85 @@
+//! `reqType://` and `resType://` values: a shorthand such as `json` or a
+//! full MIME type, with an optional charset.
+
+/// Shorthands and the MIME types they stand for.
+pub const MIME_SHORTHANDS: &[(&str, &str)] = &[
+    ("html", "text/html"),
+    ("htm", "text/html"),
+    ("text", "text/plain"),
+    ("txt", "text/plain"),
+    ("css", "text/css"),
+    ("js", "application/javascript"),
+    ("javascript", "application/javascript"),
+    ("json", "application/json"),
+    ("xml", "application/xml"),
+    ("form", "application/x-www-form-urlencoded"),
+    ("upload", "multipart/form-data"),
+    ("multipart", "multipart/form-data"),
+    ("stream", "application/octet-stream"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("svg", "image/svg+xml"),
+    ("ico", "image/x-icon"),
+    ("pdf", "application/pdf"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentType {
+    /// The lowercased MIME type, `application/json`.
+    pub mime: String,
+    pub charset: Option<String>,
+}
+
+impl ContentType {
+    /// Read `json`, `json;charset=utf8` or `text/plain; charset=gbk`.
+    /// `None` for an unknown shorthand or a malformed MIME type.
+    pub fn parse(value: &str) -> Option<ContentType> {
+        let (kind, params) = value.split_once(';').unwrap_or((value, ""));
+        let kind = kind.trim().to_ascii_lowercase();
+        let mime = match MIME_SHORTHANDS.iter().find(|(short, _)| *short == kind) {
+            Some((_, mime)) => mime.to_string(),
+            None => {
+                let (top, sub) = kind.split_once('/')?;
+                let is_token = |s: &str| {
+                    !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || "!#$&-^_.+".contains(c))
+                };
+                if !is_token(top) || !is_token(sub) {
+                    return None;
+                }
+                kind
+            }
+        };
+        let charset = params.split(';').find_map(|param| {
+            let (key, value) = param.split_once('=')?;
+            key.trim().eq_ignore_ascii_case("charset").then(|| value.trim().to_string())
+        });
+        Some(ContentType { mime, charset })
+    }
+
+    /// The `Content-Type` header value.
+    pub fn to_header(&self) -> String {
+        match &self.charset {
+            Some(charset) => format!("{}; charset={charset}", self.mime),
+            None => self.mime.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_content_type() {
+        assert_eq!(
+            ContentType::parse("json"),
+            Some(ContentType { mime: "application/json".into(), charset: None })
+        );
+        assert_eq!(ContentType::parse("HTML;charset=gbk").unwrap().to_header(), "text/html; charset=gbk");
+        assert_eq!(ContentType::parse("application/vnd.api+json").unwrap().mime, "application/vnd.api+json");
+        assert_eq!(ContentType::parse("weird"), None);
+        assert_eq!(ContentType::parse("text/"), None);
+    }
+}
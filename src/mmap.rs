@@ -0,0 +1,177 @@
+//! Rule files read through a memory map, for documents too big to copy
+//! around.
+//!
+//! The crate has no borrowed AST: a [`RuleFile`] owns its text so it can be
+//! edited, and [`RuleFile::parse_path`] reads the file into it. Only
+//! [`parse_events_mapped`] parses without a copy of the text, straight from
+//! a mapping, for callers that can promise nothing changes the file
+//! meanwhile.
+#[cfg(not(unix))]
+compile_error!("the `mmap` feature is only available on unix targets");
+
+use std::fs::File;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+use crate::events::{parse_events_with, RuleEventHandler};
+use crate::rule_file::RuleFile;
+use crate::{ParserOptions, Span, WhistleParseError};
+
+#[derive(Debug)]
+pub enum MmapError {
+    Io(std::io::Error),
+    Parse(WhistleParseError),
+}
+
+impl From<std::io::Error> for MmapError {
+    fn from(e: std::io::Error) -> Self {
+        MmapError::Io(e)
+    }
+}
+
+impl From<WhistleParseError> for MmapError {
+    fn from(e: WhistleParseError) -> Self {
+        MmapError::Parse(e)
+    }
+}
+
+/// A read-only mapping of a whole file.
+#[derive(Debug)]
+pub struct MappedFile {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+// the mapping is private and never written, so sharing it is sharing a `&[u8]`
+unsafe impl Send for MappedFile {}
+unsafe impl Sync for MappedFile {}
+
+impl MappedFile {
+    /// Map the file at `path`.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while the mapping lives,
+    /// by this process or any other: the mapping shows such changes, which
+    /// break the immutability of the `&[u8]` and `&str` it hands out, and a
+    /// truncated file kills the process with `SIGBUS` on most systems.
+    pub unsafe fn open(path: impl AsRef<Path>) -> std::io::Result<MappedFile> {
+        let file = File::open(path)?;
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "file too large to map"))?;
+        // mapping zero bytes is an error, and there is nothing to map anyway
+        if len == 0 {
+            return Ok(MappedFile { ptr: std::ptr::null_mut(), len });
+        }
+        let ptr = unsafe {
+            libc::mmap(std::ptr::null_mut(), len, libc::PROT_READ, libc::MAP_PRIVATE, file.as_raw_fd(), 0)
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(MappedFile { ptr, len })
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        if self.len == 0 {
+            return &[];
+        }
+        unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+
+    /// The contents, if they are UTF-8.
+    pub fn as_str(&self) -> Result<&str, WhistleParseError> {
+        let bytes = self.as_bytes();
+        std::str::from_utf8(bytes).map_err(|e| invalid_utf8(bytes, e))
+    }
+}
+
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            unsafe { libc::munmap(self.ptr, self.len) };
+        }
+    }
+}
+
+impl RuleFile {
+    /// Parse the file at `path`, which the rule file keeps a copy of.
+    pub fn parse_path(path: impl AsRef<Path>) -> Result<RuleFile, MmapError> {
+        RuleFile::parse_path_with(path, ParserOptions::default())
+    }
+
+    pub fn parse_path_with(path: impl AsRef<Path>, options: ParserOptions) -> Result<RuleFile, MmapError> {
+        let bytes = std::fs::read(path)?;
+        let text = String::from_utf8(bytes).map_err(|e| invalid_utf8(e.as_bytes(), e.utf8_error()))?;
+        Ok(RuleFile::parse_with(text, options)?)
+    }
+}
+
+/// [`parse_events`](crate::events::parse_events) over the file at `path`,
+/// straight from a mapping of it.
+///
+/// # Safety
+///
+/// The file must not change while it is parsed, see [`MappedFile::open`].
+pub unsafe fn parse_events_mapped(
+    path: impl AsRef<Path>,
+    options: &ParserOptions,
+    handler: &mut impl RuleEventHandler,
+) -> Result<(), MmapError> {
+    let map = MappedFile::open(path)?;
+    parse_events_with(map.as_str()?, options, handler)?;
+    Ok(())
+}
+
+fn invalid_utf8(bytes: &[u8], error: std::str::Utf8Error) -> WhistleParseError {
+    let start = error.valid_up_to();
+    let len = error.error_len().unwrap_or(bytes.len() - start);
+    WhistleParseError::InvalidUtf8 { span: Span::new(start, start + len) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ProxyRule;
+
+    fn temp_file(name: &str, contents: &[u8]) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("whistle-mmap-{}-{name}", std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_parse_path() {
+        let path = temp_file("rules.txt", b"# mocks\na.com b.com\n");
+        let file = RuleFile::parse_path(&path).unwrap();
+        assert_eq!(file.rules().count(), 1);
+
+        let empty = temp_file("empty.txt", b"");
+        assert_eq!(RuleFile::parse_path(&empty).unwrap().rules().count(), 0);
+        let binary = temp_file("binary.txt", b"a.com \xff");
+        assert!(matches!(
+            RuleFile::parse_path(&binary),
+            Err(MmapError::Parse(WhistleParseError::InvalidUtf8 { .. }))
+        ));
+        assert!(matches!(RuleFile::parse_path("/nonexistent/rules.txt"), Err(MmapError::Io(_))));
+        for path in [path, empty, binary] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_parse_events_mapped() {
+        struct Count(usize);
+        impl RuleEventHandler for Count {
+            fn on_rule(&mut self, _number: usize, _span: Span, _rule: ProxyRule, _enabled: bool) {
+                self.0 += 1;
+            }
+        }
+        let path = temp_file("events.txt", b"a.com b.com\n#off c.com d.com\n");
+        let mut count = Count(0);
+        // the file is only written before it is mapped
+        unsafe { parse_events_mapped(&path, &ParserOptions::default(), &mut count) }.unwrap();
+        assert_eq!(count.0, 2);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,126 @@
+//! Lines that share their operations across several patterns.
+//!
+//! Whistle reads `file://x a.com b.com` as the same operation for both
+//! `a.com` and `b.com`, but only when the operation comes first: in a
+//! pattern-first line the second token is already the target, so
+//! `a.com b.com c.com` can't mean three patterns and is reported as
+//! [`WhistleParseError::ExtraPattern`].
+use std::net::IpAddr;
+
+use nom::combinator::all_consuming;
+
+use crate::validate::{self, HOST_SCHEMES};
+use crate::{parse_proxy_rule_with, parse_rule, parse_uri, ParserOptions, ProxyRule, Span, Uri, WhistleParseError};
+
+/// Whether a token left over after a rule reads as another pattern rather
+/// than a mistyped operation: a host, a path, a wildcard or a regex.
+pub(crate) fn looks_like_pattern(token: &str) -> bool {
+    if let Some((scheme, _)) = token.split_once("://") {
+        return scheme == "*" || HOST_SCHEMES.contains(&scheme);
+    }
+    let token = token.trim_start_matches('!');
+    token.contains('.') || token.starts_with(['/', '^', '*']) || token == "localhost"
+}
+
+/// Whether the first token of a line is the operation: anything with a
+/// non-http scheme, or a bare IP address (`127.0.0.1 a.com`).
+pub(crate) fn is_operator(uri: &Uri) -> bool {
+    if uri.scheme.is_empty() {
+        return uri.path.is_empty() && uri.host.parse::<IpAddr>().is_ok();
+    }
+    uri.scheme != "*" && !HOST_SCHEMES.contains(&uri.scheme.as_str())
+}
+
+/// Parse a whole line into one rule per pattern. An operation-first line,
+/// `file://x a.com b.com reqHeaders://{h}`, gives a rule for `a.com` and
+/// one for `b.com`, each with every operation on the line; the rules are
+/// written operation first, the way [`parse_proxy_rule`](crate::parse_proxy_rule)
+/// reads such a line, so [`ProxyRule::normalize`] turns them pattern-first.
+/// Any other line parses like [`parse_proxy_rule_complete`](crate::parse_proxy_rule_complete).
+pub fn parse_multi_pattern(input: &str, options: &ParserOptions) -> Result<Vec<ProxyRule>, WhistleParseError> {
+    let (rest, rule) = parse_proxy_rule_with(input, options)?;
+    if rest.trim().is_empty() {
+        return Ok(vec![rule]);
+    }
+    if !is_operator(&rule.source) {
+        return crate::parse_proxy_rule_complete(input, options).map(|rule| vec![rule]);
+    }
+
+    let base = input.len() - rest.len();
+    let mut patterns = vec![rule.target];
+    let mut ops = rule.rules;
+    for (at, token) in crate::tokens(rest) {
+        let at = base + at;
+        if looks_like_pattern(token) {
+            let (_, uri) = all_consuming(parse_uri)(token)
+                .map_err(|e| WhistleParseError::from_nom(token, e).shifted(at))?;
+            if options.strict_uri {
+                validate::validate_uri(&uri, at, true)?;
+            }
+            patterns.push(uri);
+        } else if let Ok((_, op)) = all_consuming(parse_rule)(token) {
+            ops.push(op);
+        } else {
+            let text = input[at..].trim_end();
+            return Err(WhistleParseError::TrailingInput {
+                text: text.to_string(),
+                span: Span::new(at, at + text.len()),
+            });
+        }
+    }
+    Ok(patterns
+        .into_iter()
+        .map(|target| ProxyRule {
+            source: rule.source.clone(),
+            target,
+            rules: ops.clone(),
+            origin: None,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse_proxy_rule_complete;
+
+    #[test]
+    fn test_extra_pattern_error() {
+        let options = ParserOptions::default();
+        assert_eq!(
+            parse_proxy_rule_complete("a.com b.com c.com file://x", &options).unwrap_err(),
+            WhistleParseError::ExtraPattern { text: "c.com".into(), span: Span::new(12, 17) }
+        );
+        assert!(matches!(
+            parse_proxy_rule_complete("a.com file://x oops", &options),
+            Err(WhistleParseError::TrailingInput { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_multi_pattern() {
+        let options = ParserOptions::default();
+        let rules = parse_multi_pattern("file://x a.com reqHeaders://{h} *.b.com/api", &options).unwrap();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].target.host, "a.com");
+        assert_eq!(rules[1].target.host, "*.b.com");
+        assert!(rules.iter().all(|r| r.source.scheme == "file" && r.rules.len() == 1));
+        assert_eq!(rules[1].normalize().source.path, "/api");
+
+        let rules = parse_multi_pattern("127.0.0.1 a.com b.com", &options).unwrap();
+        assert_eq!(rules[1].source.host, "127.0.0.1");
+
+        assert_eq!(
+            parse_multi_pattern("file://x a.com", &options).unwrap(),
+            vec![crate::parse_proxy_rule("file://x a.com").unwrap().1]
+        );
+        assert!(matches!(
+            parse_multi_pattern("a.com b.com c.com", &options),
+            Err(WhistleParseError::ExtraPattern { .. })
+        ));
+        assert!(matches!(
+            parse_multi_pattern("file://x a.com oops", &options),
+            Err(WhistleParseError::TrailingInput { span, .. }) if span == Span::new(15, 19)
+        ));
+    }
+}
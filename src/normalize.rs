@@ -0,0 +1,155 @@
+//! One spelling for rules whistle reads the same way.
+use std::borrow::Cow;
+
+use crate::multi_pattern::is_operator;
+use crate::validate::HOST_SCHEMES;
+use crate::{ProxyRule, Uri};
+
+impl ProxyRule {
+    /// The rule with equivalent spellings made the same:
+    ///
+    /// - operator-first lines, `file://x a.com` and the hosts file style
+    ///   `127.0.0.1 a.com`, become pattern-first,
+    /// - a `*://` scheme on the pattern, which matches any scheme, is dropped,
+    /// - a lone `/` path on the pattern, `www.a.com/`, is dropped.
+    pub fn normalize(&self) -> ProxyRule {
+        let mut rule = self.clone();
+        if is_operator(&rule.source) && !is_operator(&rule.target) {
+            std::mem::swap(&mut rule.source, &mut rule.target);
+        }
+        if rule.source.scheme == "*" {
+            rule.source.scheme.clear();
+        }
+        if rule.source.path == "/" && rule.source.query.is_empty() {
+            rule.source.path.clear();
+        }
+        rule
+    }
+
+    /// Whether both rules are the same once [normalized](ProxyRule::normalize).
+    pub fn equivalent(&self, other: &ProxyRule) -> bool {
+        self.normalize() == other.normalize()
+    }
+}
+
+impl Uri {
+    /// The uri with equivalent spellings made the same: the scheme and host
+    /// lowercased, and in the path duplicate slashes collapsed, `.` and `..`
+    /// segments resolved and a trailing slash dropped. The host and path of
+    /// operations such as `file://D:\Mock` aren't a url and are kept.
+    pub fn normalized(&self) -> Uri {
+        let mut uri = self.clone();
+        uri.scheme.make_ascii_lowercase();
+        if uri.scheme.is_empty() || HOST_SCHEMES.contains(&uri.scheme.as_str()) {
+            uri.host.make_ascii_lowercase();
+            uri.path = normalize_path(&uri.path, true).into_owned();
+        }
+        uri
+    }
+}
+
+/// `path` with duplicate slashes collapsed and dot segments resolved, a
+/// `..` above the root staying at the root. With `trim_trailing` a trailing
+/// slash is dropped too, which makes `/` empty. A path that is already
+/// normal, as most request paths are, is borrowed.
+pub(crate) fn normalize_path(path: &str, trim_trailing: bool) -> Cow<'_, str> {
+    let changes = path.contains("//")
+        || path.split('/').any(|segment| segment == "." || segment == "..")
+        || (trim_trailing && path.ends_with('/'));
+    if !changes {
+        return Cow::Borrowed(path);
+    }
+    let mut segments: Vec<&str> = vec![];
+    let parts: Vec<&str> = path.split('/').collect();
+    for (index, segment) in parts.iter().enumerate() {
+        let last = index + 1 == parts.len();
+        match *segment {
+            "." | "" if !last => {}
+            ".." => {
+                segments.pop();
+                if last {
+                    segments.push("");
+                }
+            }
+            "." => segments.push(""),
+            segment => segments.push(segment),
+        }
+    }
+    let mut out = String::with_capacity(path.len());
+    if path.starts_with('/') {
+        out.push('/');
+    }
+    out.push_str(&segments.join("/"));
+    if trim_trailing {
+        while out.ends_with('/') {
+            out.pop();
+        }
+    }
+    Cow::Owned(out)
+}
+
+/// `s` with ASCII letters lowercased, borrowed when it has no uppercase
+/// ones, as hostnames usually don't.
+pub(crate) fn ascii_lowercase(s: &str) -> Cow<'_, str> {
+    match s.bytes().any(|b| b.is_ascii_uppercase()) {
+        true => Cow::Owned(s.to_ascii_lowercase()),
+        false => Cow::Borrowed(s),
+    }
+}
+
+/// `s` lowercased, borrowed when lowercasing wouldn't change it.
+pub(crate) fn lowercase(s: &str) -> Cow<'_, str> {
+    let unchanged = |c: char| {
+        let mut lower = c.to_lowercase();
+        lower.len() == 1 && lower.next() == Some(c)
+    };
+    match s.chars().all(unchanged) {
+        true => Cow::Borrowed(s),
+        false => Cow::Owned(s.to_lowercase()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::borrow::Cow;
+
+    use super::{ascii_lowercase, lowercase, normalize_path};
+    use crate::{parse_proxy_rule, parse_uri};
+
+    #[test]
+    fn test_normalize() {
+        let rule = |s| parse_proxy_rule(s).unwrap().1;
+        let canonical = rule("www.a.com file://x reqHeaders://{h}");
+        for spelling in [
+            "file://x www.a.com reqHeaders://{h}",
+            "www.a.com/ file://x reqHeaders://{h}",
+            "*://www.a.com/ file://x reqHeaders://{h}",
+            "file://x *://www.a.com reqHeaders://{h}",
+        ] {
+            assert_eq!(rule(spelling).normalize(), canonical, "{spelling}");
+        }
+        assert_eq!(canonical.normalize(), canonical);
+        assert!(rule("http://a.com/ b.com").equivalent(&rule("http://a.com b.com")));
+        assert!(!rule("http://a.com b.com").equivalent(&rule("a.com b.com")));
+        assert!(!rule("a.com/x b.com").equivalent(&rule("a.com b.com")));
+        assert!(rule("127.0.0.1 www.a.com").equivalent(&rule("www.a.com 127.0.0.1")));
+        assert_eq!(rule("127.0.0.1 10.0.0.1").normalize(), rule("127.0.0.1 10.0.0.1"));
+    }
+
+    #[test]
+    fn test_uri_normalized() {
+        let uri = |s| parse_uri(s).unwrap().1;
+        assert_eq!(uri("HTTPS://WWW.A.com//api/./v1/../users/").normalized().to_string(), "https://www.a.com/api/users");
+        assert_eq!(uri("a.com/").normalized().to_string(), "a.com");
+        assert_eq!(uri("file://D:/Mock//A.html").normalized().to_string(), "file://D:/Mock//A.html");
+        assert_eq!(normalize_path("/a/../../b//c/", false), "/b/c/");
+        assert_eq!(normalize_path("/a/b/..", false), "/a/");
+        assert_eq!(normalize_path("/a/.", false), "/a/");
+        assert_eq!(normalize_path("/", false), "/");
+        assert!(matches!(normalize_path("/a/b.c/", false), Cow::Borrowed("/a/b.c/")));
+        assert!(matches!(normalize_path("/a/", true), Cow::Owned(_)));
+        assert!(matches!(ascii_lowercase("a.com"), Cow::Borrowed(_)));
+        assert_eq!(lowercase("/Ä/x"), "/ä/x");
+        assert!(matches!(lowercase("/中文/ä"), Cow::Borrowed(_)));
+    }
+}
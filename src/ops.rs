@@ -0,0 +1,525 @@
+//! Operations typed by protocol, for code that acts on rules rather than
+//! edits them.
+use crate::attachment::AttachmentOp;
+use crate::cache::CacheOp;
+use crate::cookie::CookieSpec;
+use crate::cors::CorsOp;
+use crate::delete::DeleteOp;
+use crate::matcher::RequestUrl;
+use crate::method::Method;
+use crate::mime::ContentType;
+use crate::proxy::{ProxyTarget, ProxyTargetError};
+use crate::redirect::RedirectOp;
+use crate::registry::CustomValue;
+use crate::replace::ReplaceOp;
+use crate::rule_file::{RuleFile, RuleId};
+use crate::rule_set::{RuleSet, HOST_PROTOCOL};
+use crate::{OpValue, ProxyRule, Rule, Span};
+
+/// The protocols of the whistle documentation. Every one of them maps to a
+/// [`RuleOp`], typed or [`RuleOp::Other`].
+pub const DOCUMENTED_PROTOCOLS: &[&str] = &[
+    "host", "proxy", "http-proxy", "https-proxy", "socks", "pac", "http", "https", "ws", "wss",
+    "tunnel", "file", "xfile", "tpl", "xtpl", "rawfile", "xrawfile", "redirect", "locationHref",
+    "statusCode", "replaceStatus", "method", "referer", "auth", "ua", "forwardedFor",
+    "responseFor", "cache", "attachment", "reqDelay", "resDelay", "reqSpeed", "resSpeed",
+    "reqType", "resType", "reqCharset", "resCharset", "reqCookies", "resCookies", "reqCors",
+    "resCors", "reqHeaders", "resHeaders", "headerReplace", "reqBody", "resBody", "reqPrepend",
+    "resPrepend", "reqAppend", "resAppend", "reqReplace", "resReplace", "reqMerge", "resMerge",
+    "reqWrite", "resWrite", "reqWriteRaw", "resWriteRaw", "reqScript", "resScript",
+    "rulesFile", "rulesScript", "reqRules", "resRules", "urlParams", "params", "urlReplace",
+    "pathReplace", "htmlAppend", "htmlPrepend", "htmlBody", "jsAppend", "jsPrepend", "jsBody",
+    "cssAppend", "cssPrepend", "cssBody", "frameScript", "trailers", "enable", "disable",
+    "delete", "filter", "ignore", "skip", "style", "lineProps", "weinre", "log", "pipe",
+];
+
+/// Operations for debugging pages and traffic, usually not meant to stay in
+/// a shared rule file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DebugOp {
+    /// `weinre://id`, inject the weinre remote inspector with this id.
+    Weinre(String),
+    /// `log://id`, capture the page's console under this id.
+    Log(String),
+    /// `pipe://plugin`, stream the traffic through a plugin.
+    Pipe(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuleOp {
+    /// `proxy://`, `http-proxy://`, `https-proxy://`, `socks://` and `pac://`.
+    Proxy(ProxyTarget),
+    Debug(DebugOp),
+    Redirect(RedirectOp),
+    ReqCors(CorsOp),
+    ResCors(CorsOp),
+    ReqCookies(Vec<CookieSpec>),
+    ResCookies(Vec<CookieSpec>),
+    Cache(CacheOp),
+    Attachment(AttachmentOp),
+    Delete(DeleteOp),
+    /// `method://`, or its `replaceMethod://` spelling.
+    Method(Method),
+    ReqType(ContentType),
+    ResType(ContentType),
+    /// `pathReplace://`, or its older `urlReplace://` spelling.
+    PathReplace(ReplaceOp),
+    /// `reqReplace://`, replacing in the request body. A `{key}` value has
+    /// to be resolved first, see [`ReplaceOp::resolve_body`].
+    ReqReplace(ReplaceOp),
+    ResReplace(ReplaceOp),
+    /// A documented protocol without a typed form yet.
+    Other(Rule),
+    /// A protocol this crate doesn't know, such as a newer whistle one or a
+    /// plugin's. `raw` is the operation as written and `span` where it is;
+    /// only [`RuleFile::ops`] knows the document, elsewhere the span covers
+    /// just `raw`.
+    Unknown { name: String, raw: String, span: Span },
+    /// A protocol lowered by a [`ProtocolRegistry`](crate::registry::ProtocolRegistry).
+    Custom(CustomValue),
+}
+
+/// Why a rule's value doesn't fit its protocol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum OpError {
+    Proxy(ProxyTargetError),
+    /// The value can't be read the way the protocol needs, or is a `{key}`
+    /// that has to be resolved first.
+    InvalidValue { protocol: String, value: String },
+    /// A registered protocol parser rejected the value.
+    Custom { protocol: String, message: String },
+    /// A `/regexp/` of a replacing protocol that is invalid, or needs the
+    /// `regex` feature, see [`ReplaceOp::unsupported_regex`].
+    UnsupportedRegex { protocol: String, regex: String },
+}
+
+impl TryFrom<&Rule> for RuleOp {
+    type Error = OpError;
+
+    fn try_from(rule: &Rule) -> Result<Self, Self::Error> {
+        let text = match &rule.value {
+            OpValue::Raw(s) | OpValue::Inline(s) => s.clone(),
+            value => value.to_string(),
+        };
+        match rule.name.as_str() {
+            "weinre" => return Ok(RuleOp::Debug(DebugOp::Weinre(text))),
+            "log" => return Ok(RuleOp::Debug(DebugOp::Log(text))),
+            "pipe" => return Ok(RuleOp::Debug(DebugOp::Pipe(text))),
+            "redirect" => {
+                let op = match &rule.value {
+                    OpValue::Raw(s) | OpValue::Inline(s) => RedirectOp::parse(s),
+                    _ => None,
+                };
+                return op.map(RuleOp::Redirect).ok_or(OpError::InvalidValue {
+                    protocol: rule.name.clone(),
+                    value: text,
+                });
+            }
+            "reqCors" | "resCors" => {
+                let op = match &rule.value {
+                    OpValue::Raw(s) | OpValue::Inline(s) => CorsOp::parse(s),
+                    _ => None,
+                };
+                let op = op.ok_or(OpError::InvalidValue { protocol: rule.name.clone(), value: text })?;
+                return Ok(if rule.name == "reqCors" { RuleOp::ReqCors(op) } else { RuleOp::ResCors(op) });
+            }
+            "reqCookies" | "resCookies" => {
+                let cookies = rule.value.as_cookies();
+                let cookies = cookies.ok_or(OpError::InvalidValue { protocol: rule.name.clone(), value: text })?;
+                return Ok(match rule.name.as_str() {
+                    "reqCookies" => RuleOp::ReqCookies(cookies),
+                    _ => RuleOp::ResCookies(cookies),
+                });
+            }
+            "attachment" => {
+                return match &rule.value {
+                    OpValue::Raw(s) | OpValue::Inline(s) => Ok(RuleOp::Attachment(AttachmentOp::parse(s))),
+                    _ => Err(OpError::InvalidValue { protocol: rule.name.clone(), value: text }),
+                };
+            }
+            "delete" => {
+                let op = match &rule.value {
+                    OpValue::Raw(s) | OpValue::Inline(s) => DeleteOp::parse(s),
+                    _ => None,
+                };
+                return op.map(RuleOp::Delete).ok_or(OpError::InvalidValue {
+                    protocol: rule.name.clone(),
+                    value: text,
+                });
+            }
+            "method" | "replaceMethod" => {
+                let method = match &rule.value {
+                    OpValue::Raw(s) | OpValue::Inline(s) => Method::parse(s),
+                    _ => None,
+                };
+                return method.map(RuleOp::Method).ok_or(OpError::InvalidValue {
+                    protocol: rule.name.clone(),
+                    value: text,
+                });
+            }
+            "pathReplace" | "urlReplace" => {
+                let op = match &rule.value {
+                    OpValue::Raw(s) | OpValue::Inline(s) => ReplaceOp::parse(s),
+                    _ => None,
+                };
+                let op = op.ok_or(OpError::InvalidValue { protocol: rule.name.clone(), value: text })?;
+                return supported(&rule.name, op).map(RuleOp::PathReplace);
+            }
+            "reqReplace" | "resReplace" => {
+                let op = match &rule.value {
+                    OpValue::Raw(s) | OpValue::Inline(s) => ReplaceOp::parse_body(s),
+                    _ => None,
+                };
+                let op = supported(&rule.name, op.ok_or(OpError::InvalidValue { protocol: rule.name.clone(), value: text })?)?;
+                return Ok(if rule.name == "reqReplace" { RuleOp::ReqReplace(op) } else { RuleOp::ResReplace(op) });
+            }
+            "cache" | "reqType" | "resType" => {
+                let op = match (rule.name.as_str(), &rule.value) {
+                    ("cache", OpValue::Raw(s) | OpValue::Inline(s)) => CacheOp::parse(s).map(RuleOp::Cache),
+                    ("reqType", OpValue::Raw(s) | OpValue::Inline(s)) => ContentType::parse(s).map(RuleOp::ReqType),
+                    ("resType", OpValue::Raw(s) | OpValue::Inline(s)) => ContentType::parse(s).map(RuleOp::ResType),
+                    _ => None,
+                };
+                return op.ok_or(OpError::InvalidValue { protocol: rule.name.clone(), value: text });
+            }
+            _ => {}
+        }
+        match rule.proxy_target() {
+            Ok(target) => Ok(RuleOp::Proxy(target)),
+            Err(ProxyTargetError::NotProxy) if !DOCUMENTED_PROTOCOLS.contains(&rule.name.as_str()) => {
+                let raw = rule.to_string();
+                Ok(RuleOp::Unknown { name: rule.name.clone(), span: Span::new(0, raw.len()), raw })
+            }
+            Err(ProxyTargetError::NotProxy) => Ok(RuleOp::Other(rule.clone())),
+            Err(e) => Err(OpError::Proxy(e)),
+        }
+    }
+}
+
+impl RuleOp {
+    pub fn is_debug(&self) -> bool {
+        matches!(self, RuleOp::Debug(_))
+    }
+
+    /// The url a request to `original` ends up at with this operation
+    /// alone, `None` for operations that leave the url as it is or whose
+    /// value is a `{key}` to resolve first.
+    ///
+    /// - `redirect://` gives the `Location`, see [`RedirectOp::resolve`].
+    /// - `http://`, `https://`, `ws://` and `wss://` targets send it to
+    ///   their host with their path before the original one, as whistle
+    ///   does for a pattern without a path.
+    /// - `urlParams://` sets query parameters, from `a=1&b=2` or a json
+    ///   object, see [`merge_url_params`](crate::form::merge_url_params).
+    /// - `pathReplace://` and `urlReplace://` make their
+    ///   [replacements](ReplaceOp) in the path and query.
+    ///
+    /// `host://`, or a target without a scheme, keeps the url and only
+    /// connects elsewhere, see [`RuleOp::connect_address`].
+    pub fn rewrite_url(&self, original: &str) -> Option<String> {
+        // the scheme and authority as written, for ops that only touch the rest
+        let rest_start = original.find("://").map_or(0, |at| at + 3);
+        let base = &original[..original[rest_start..].find(['/', '?', '#']).map_or(original.len(), |at| rest_start + at)];
+        let url = RequestUrl::parse(original);
+        let rule = match self {
+            RuleOp::Redirect(op) => return Some(op.resolve(original)),
+            RuleOp::PathReplace(op) => {
+                let rest = format!("{}{}", url.path, url.query);
+                return Some(format!("{base}{}", op.apply_to(&rest)));
+            }
+            RuleOp::Other(rule) => rule,
+            _ => return None,
+        };
+        let (OpValue::Raw(value) | OpValue::Inline(value)) = &rule.value else {
+            return None;
+        };
+        match rule.name.as_str() {
+            "http" | "https" | "ws" | "wss" => {
+                let to = RequestUrl::parse(value);
+                let port = to.port.map(|p| format!(":{p}")).unwrap_or_default();
+                let path = format!("{}{}", to.path.trim_end_matches('/'), url.path);
+                let query = if to.query.is_empty() { url.query } else { to.query };
+                Some(format!("{}://{}{port}{path}{query}", rule.name, to.host))
+            }
+            "urlParams" => Some(crate::form::merge_url_params(original, &rule.value.as_form()?)),
+            _ => None,
+        }
+    }
+
+    /// The `host:port` a `host://` operation, or a target without a scheme,
+    /// connects a request to `original` to, keeping the url's port when the
+    /// operation has none. `None` for other operations and `{key}` values.
+    pub fn connect_address(&self, original: &str) -> Option<String> {
+        let RuleOp::Other(rule) = self else {
+            return None;
+        };
+        let (HOST_PROTOCOL, OpValue::Raw(value) | OpValue::Inline(value)) = (rule.name.as_str(), &rule.value) else {
+            return None;
+        };
+        let (to, url) = (RequestUrl::parse(value), RequestUrl::parse(original));
+        let port = to.port.or(url.port).map(|p| format!(":{p}")).unwrap_or_default();
+        Some(format!("{}{port}", to.host))
+    }
+}
+
+/// `op`, unless it has a regexp that can't run.
+fn supported(protocol: &str, op: ReplaceOp) -> Result<ReplaceOp, OpError> {
+    match op.unsupported_regex() {
+        Some(regex) => Err(OpError::UnsupportedRegex { protocol: protocol.to_string(), regex: regex.to_string() }),
+        None => Ok(op),
+    }
+}
+
+impl ProxyRule {
+    /// The typed [operations](ProxyRule::operations) of the rule.
+    pub fn ops(&self) -> Vec<Result<RuleOp, OpError>> {
+        self.operations().iter().map(RuleOp::try_from).collect()
+    }
+}
+
+/// How [`RuleSet::rewritten_url`] groups the operations changing a url:
+/// whistle uses the first of each group among the matching rules.
+fn rewrite_group(op: &RuleOp) -> Option<&'static str> {
+    match op {
+        RuleOp::Redirect(_) => Some("redirect"),
+        RuleOp::PathReplace(_) => Some("pathReplace"),
+        RuleOp::Other(rule) => match rule.name.as_str() {
+            "http" | "https" | "ws" | "wss" => Some("target"),
+            "urlParams" => Some("urlParams"),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+impl RuleSet {
+    /// Where a request to `url` would go, without making it: the first
+    /// operation of each kind among the rules matching `url` rewrites it,
+    /// see [`RuleOp::rewrite_url`]. A redirect answers the request before
+    /// anything else; otherwise the path is replaced, then the parameters
+    /// set, then the target applied. `None` when the url stays the same.
+    pub fn rewritten_url(&self, url: &str) -> Option<String> {
+        let mut first: Vec<(&str, RuleOp)> = vec![];
+        for rule in self.matching(url) {
+            for op in rule.ops().into_iter().flatten() {
+                let Some(group) = rewrite_group(&op) else {
+                    continue;
+                };
+                if op.rewrite_url(url).is_some() && !first.iter().any(|(g, _)| *g == group) {
+                    first.push((group, op));
+                }
+            }
+        }
+        let op = |group: &str| first.iter().find(|(g, _)| *g == group).map(|(_, op)| op);
+        if let Some(redirect) = op("redirect") {
+            return redirect.rewrite_url(url);
+        }
+        let mut current = url.to_string();
+        for group in ["pathReplace", "urlParams", "target"] {
+            if let Some(op) = op(group) {
+                current = op.rewrite_url(&current)?;
+            }
+        }
+        (current != url).then_some(current)
+    }
+
+    /// The address a request to `url` connects to, from the first `host://`
+    /// among the rules matching it, see [`RuleOp::connect_address`]. The
+    /// url itself is [`RuleSet::rewritten_url`].
+    pub fn connect_address(&self, url: &str) -> Option<String> {
+        self.matching(url).flat_map(|rule| rule.ops()).flatten().find_map(|op| op.connect_address(url))
+    }
+}
+
+impl RuleFile {
+    /// The typed operations of the rule `id`, with [`RuleOp::Unknown`]
+    /// keeping the text and document span it was written with.
+    pub fn ops(&self, id: RuleId) -> Vec<Result<RuleOp, OpError>> {
+        let Some((_, rule, _)) = self.rules().find(|(rule_id, _, _)| *rule_id == id) else {
+            return vec![];
+        };
+        let spans = self.op_spans(id);
+        let mut ops = rule.ops();
+        for (op, at) in ops.iter_mut().zip(spans) {
+            if let Ok(RuleOp::Unknown { raw, span, .. }) = op {
+                *raw = self.text()[at.start..at.end].to_string();
+                *span = at;
+            }
+        }
+        ops
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::proxy::ProxyServer;
+
+    #[test]
+    fn test_ops() {
+        let rule = crate::parse_proxy_rule("a.com proxy://b:8080 file://x socks://:1").unwrap().1;
+        let ops = rule.ops();
+        assert_eq!(
+            ops[0],
+            Ok(RuleOp::Proxy(ProxyTarget::Http(vec![ProxyServer {
+                host: "b".into(),
+                port: Some(8080),
+                auth: None,
+            }])))
+        );
+        assert!(matches!(&ops[1], Ok(RuleOp::Other(rule)) if rule.name == "file"));
+        assert_eq!(ops[2], Err(OpError::Proxy(ProxyTargetError::MissingHost(":1".into()))));
+
+        let rule = crate::parse_proxy_rule("a.com https-proxy://b").unwrap().1;
+        assert_eq!(rule.target.scheme, "https-proxy");
+        assert!(matches!(&rule.ops()[0], Ok(RuleOp::Proxy(ProxyTarget::Https(_)))));
+    }
+
+    #[test]
+    fn test_debug_ops() {
+        let rule = crate::parse_proxy_rule("a.com weinre://w1 log://{id} pipe://ws-check").unwrap().1;
+        assert_eq!(
+            rule.ops(),
+            vec![
+                Ok(RuleOp::Debug(DebugOp::Weinre("w1".into()))),
+                Ok(RuleOp::Debug(DebugOp::Log("{id}".into()))),
+                Ok(RuleOp::Debug(DebugOp::Pipe("ws-check".into()))),
+            ]
+        );
+        assert!(rule.ops().iter().flatten().all(RuleOp::is_debug));
+    }
+
+    #[test]
+    fn test_cors_ops() {
+        let rule = crate::parse_proxy_rule("a.com resCors://enable reqCors://(origin=*) resCors://{cors}").unwrap().1;
+        let ops = rule.ops();
+        assert!(matches!(&ops[0], Ok(RuleOp::ResCors(op)) if op.credentials));
+        assert_eq!(ops[1], Ok(RuleOp::ReqCors(CorsOp::default())));
+        assert!(matches!(&ops[2], Err(OpError::InvalidValue { .. })));
+    }
+
+    #[test]
+    fn test_cookie_ops() {
+        let rule = crate::parse_proxy_rule("a.com resCookies://(a=1) reqCookies://{c}").unwrap().1;
+        let ops = rule.ops();
+        assert_eq!(ops[0], Ok(RuleOp::ResCookies(vec![CookieSpec::new("a", "1")])));
+        assert!(matches!(&ops[1], Err(OpError::InvalidValue { .. })));
+    }
+
+    #[test]
+    fn test_cache_and_type_ops() {
+        let rule = crate::parse_proxy_rule("a.com cache://no resType://json reqType://x cache://soon").unwrap().1;
+        let ops = rule.ops();
+        assert_eq!(ops[0], Ok(RuleOp::Cache(CacheOp::NoCache)));
+        assert!(matches!(&ops[1], Ok(RuleOp::ResType(t)) if t.mime == "application/json"));
+        assert_eq!(ops[2], Err(OpError::InvalidValue { protocol: "reqType".into(), value: "x".into() }));
+        assert!(ops[3].is_err());
+    }
+
+    #[test]
+    fn test_method_ops() {
+        let rule = crate::parse_proxy_rule("a.com method://put replaceMethod://MKCOL method://a,b").unwrap().1;
+        let ops = rule.ops();
+        assert_eq!(ops[0], Ok(RuleOp::Method(Method::Put)));
+        assert_eq!(ops[1], Ok(RuleOp::Method(Method::Extension("MKCOL".into()))));
+        assert!(ops[2].is_err());
+    }
+
+    #[test]
+    fn test_delete_ops() {
+        let rule = crate::parse_proxy_rule("a.com delete://reqH.x-a|resBody delete://req.nope").unwrap().1;
+        let ops = rule.ops();
+        assert!(matches!(&ops[0], Ok(RuleOp::Delete(op)) if op.fields.len() == 2));
+        assert!(ops[1].is_err());
+    }
+
+    #[test]
+    fn test_attachment_ops() {
+        let rule = crate::parse_proxy_rule("a.com attachment://../a.txt attachment://{name}").unwrap().1;
+        let ops = rule.ops();
+        assert_eq!(ops[0], Ok(RuleOp::Attachment(AttachmentOp { filename: Some("a.txt".into()) })));
+        assert!(ops[1].is_err());
+    }
+
+    #[test]
+    fn test_unknown_ops() {
+        let file = RuleFile::parse("# mock\na.com  127.0.0.1 mock://(x=1) style://color=red");
+        let ops = file.ops(RuleId(1));
+        assert_eq!(
+            ops[1],
+            Ok(RuleOp::Unknown {
+                name: "mock".into(),
+                raw: "mock://(x=1)".into(),
+                span: Span::new(24, 36),
+            })
+        );
+        assert!(matches!(&ops[2], Ok(RuleOp::Other(rule)) if rule.name == "style"));
+        assert!(file.ops(RuleId(0)).is_empty());
+    }
+
+    #[test]
+    fn test_rewrite_url() {
+        let rewrite = |op: &str, url: &str| {
+            let rule = crate::parse_proxy_rule(&format!("a.com {op}")).unwrap().1;
+            rule.ops().remove(0).unwrap().rewrite_url(url)
+        };
+        let url = "http://a.com/x?q=1";
+        assert_eq!(rewrite("127.0.0.1:8080", url), None);
+        let connect = |op: &str, url: &str| {
+            let rule = crate::parse_proxy_rule(&format!("a.com {op}")).unwrap().1;
+            rule.ops().remove(0).unwrap().connect_address(url)
+        };
+        assert_eq!(connect("127.0.0.1:8080", url).as_deref(), Some("127.0.0.1:8080"));
+        assert_eq!(connect("host://10.0.0.1", "http://a.com:81/").as_deref(), Some("10.0.0.1:81"));
+        assert_eq!(connect("https://b.com", url), None);
+        assert_eq!(rewrite("https://b.com/v1/", url).as_deref(), Some("https://b.com/v1/x?q=1"));
+        assert_eq!(rewrite("urlParams://(q=2&r=3)", url).as_deref(), Some("http://a.com/x?q=2&r=3"));
+        let rule = crate::parse_proxy_rule("a.com 10.0.0.1 urlParams://(q=a%20b)").unwrap().1;
+        let url = "http://a.com/x?q=1&q=2#top";
+        let rewritten = [rule.clone()].into_iter().collect::<RuleSet>().rewritten_url(url);
+        assert_eq!(rewritten.as_deref(), Some("http://a.com/x?q=a%20b#top"));
+        assert_eq!(rewritten.unwrap(), crate::form::apply_url_params(url, &rule.rules[0]));
+        assert_eq!(rewrite(r#"urlParams://({"q":"é","n":1})"#, url).as_deref(), Some("http://a.com/x?q=%C3%A9&n=1#top"));
+        let url = "http://a.com/x?q=1";
+        let params = RuleOp::Other(Rule { name: "urlParams".into(), value: OpValue::value_ref("params") });
+        assert_eq!(params.rewrite_url(url), None);
+        assert_eq!(rewrite("pathReplace://(x=y)", url).as_deref(), Some("http://a.com/y?q=1"));
+        assert_eq!(rewrite("urlReplace://(/x=/z)", url).as_deref(), Some("http://a.com/z?q=1"));
+        let rule = crate::parse_proxy_rule("a.com pathReplace://(a=b&/c/d/) pathReplace://x").unwrap().1;
+        let ops = rule.ops();
+        assert!(matches!(&ops[0], Ok(RuleOp::PathReplace(op)) if op.replacements.len() == 2));
+        assert_eq!(ops[1], Err(OpError::InvalidValue { protocol: "pathReplace".into(), value: "x".into() }));
+        let rule = crate::parse_proxy_rule("a.com urlReplace://(/(/=x)").unwrap().1;
+        assert_eq!(
+            rule.ops()[0],
+            Err(OpError::UnsupportedRegex { protocol: "urlReplace".into(), regex: "(".into() })
+        );
+
+        let rule = crate::parse_proxy_rule("a.com resReplace://(a=b) reqReplace://{r}").unwrap().1;
+        let ops = rule.ops();
+        assert!(matches!(&ops[0], Ok(RuleOp::ResReplace(op)) if op.apply_to("aa") == "bb"));
+        assert!(ops[1].is_err());
+        assert_eq!(rewrite("file://x", url), None);
+
+        let rules: RuleSet = ["a.com 10.0.0.1 pathReplace://(/x=/y)", "a.com 10.0.0.2 urlParams://(d=1)", "b.com redirect://https://c.com"]
+            .iter()
+            .map(|line| crate::parse_proxy_rule(line).unwrap().1)
+            .collect();
+        assert_eq!(rules.rewritten_url("http://a.com/x").as_deref(), Some("http://a.com/y?d=1"));
+        assert_eq!(rules.connect_address("http://a.com/x").as_deref(), Some("10.0.0.1"));
+        assert_eq!(rules.rewritten_url("http://b.com/?a=1").as_deref(), Some("https://c.com?a=1"));
+        assert_eq!(rules.rewritten_url("http://d.com/"), None);
+    }
+
+    #[test]
+    fn test_redirect_op() {
+        let rule = crate::parse_proxy_rule("a.com redirect://https://b.com/x redirect://{to}").unwrap().1;
+        let ops = rule.ops();
+        assert!(matches!(&ops[0], Ok(RuleOp::Redirect(op)) if op.target.path == "/x"));
+        assert_eq!(
+            ops[1],
+            Err(OpError::InvalidValue { protocol: "redirect".into(), value: "{to}".into() })
+        );
+    }
+}
@@ -0,0 +1,119 @@
+//! Knobs for the `_with` parse entry points.
+use std::borrow::Cow;
+
+use crate::alias::ProtocolAliases;
+use crate::error::Limit;
+use crate::{Span, WhistleParseError};
+
+/// Characters that chat apps and editors slip into pasted rules and that
+/// aren't Unicode whitespace, so they'd otherwise end up inside tokens.
+pub const DEFAULT_EXTRA_SEPARATORS: &[char] = &['\u{200b}', '\u{2060}', '\u{feff}'];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParserOptions {
+    /// Reject schemes and hosts that can't be valid, like `???///!!!`,
+    /// instead of accepting anything between the separators.
+    pub strict_uri: bool,
+    /// Treated as whitespace in addition to the characters with the Unicode
+    /// `White_Space` property (which covers U+00A0 and U+3000).
+    pub extra_separators: Vec<char>,
+    pub limits: Limits,
+    /// Shorthand protocol names replaced while parsing.
+    pub aliases: ProtocolAliases,
+}
+
+impl Default for ParserOptions {
+    fn default() -> Self {
+        ParserOptions {
+            strict_uri: false,
+            extra_separators: DEFAULT_EXTRA_SEPARATORS.to_vec(),
+            limits: Limits::default(),
+            aliases: ProtocolAliases::default(),
+        }
+    }
+}
+
+impl ParserOptions {
+    pub fn strict() -> Self {
+        ParserOptions { strict_uri: true, ..Default::default() }
+    }
+
+    /// Read `alias://` as `protocol://`, see [`ProtocolAliases`].
+    pub fn with_alias(mut self, alias: impl Into<String>, protocol: impl Into<String>) -> Self {
+        self.aliases.insert(alias, protocol);
+        self
+    }
+
+    /// Replace each extra separator by as many ASCII spaces as it has bytes,
+    /// so byte offsets into the result are valid in `input` too.
+    pub fn normalize_separators<'a>(&self, input: &'a str) -> Cow<'a, str> {
+        if !input.contains(self.extra_separators.as_slice()) {
+            return Cow::Borrowed(input);
+        }
+        let mut out = String::with_capacity(input.len());
+        for c in input.chars() {
+            if self.extra_separators.contains(&c) {
+                out.extend(std::iter::repeat_n(' ', c.len_utf8()));
+            } else {
+                out.push(c);
+            }
+        }
+        Cow::Owned(out)
+    }
+}
+
+/// Upper bounds on what a parse may have to handle, for services parsing
+/// rules from untrusted users. `None` means unlimited, the default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// Bytes in a whole document.
+    pub max_input_size: Option<usize>,
+    /// Bytes in a single line.
+    pub max_line_length: Option<usize>,
+    /// Parts of a single `` `…${x}…` `` template value.
+    pub max_template_parts: Option<usize>,
+    /// How deep includes may nest, for loaders that follow them.
+    pub max_include_depth: Option<usize>,
+}
+
+impl Limits {
+    /// Conservative bounds for input from unknown users.
+    pub fn untrusted() -> Self {
+        Limits {
+            max_input_size: Some(1 << 20),
+            max_line_length: Some(8 << 10),
+            max_template_parts: Some(64),
+            max_include_depth: Some(4),
+        }
+    }
+
+    pub fn check_input_size(&self, input: &str) -> Result<(), WhistleParseError> {
+        check_len(Limit::InputSize, self.max_input_size, input.len())
+    }
+
+    pub fn check_line_length(&self, line: &str) -> Result<(), WhistleParseError> {
+        check_len(Limit::LineLength, self.max_line_length, line.len())
+    }
+
+    /// `span` is where the template is, a count has no position of its own.
+    pub fn check_template_parts(&self, parts: usize, span: Span) -> Result<(), WhistleParseError> {
+        check(Limit::TemplateParts, self.max_template_parts, parts, span)
+    }
+
+    pub fn check_include_depth(&self, depth: usize, span: Span) -> Result<(), WhistleParseError> {
+        check(Limit::IncludeDepth, self.max_include_depth, depth, span)
+    }
+}
+
+fn check_len(limit: Limit, max: Option<usize>, len: usize) -> Result<(), WhistleParseError> {
+    // for sizes, point at the bytes past the limit
+    let span = Span::new(max.unwrap_or(len).min(len), len);
+    check(limit, max, len, span)
+}
+
+fn check(limit: Limit, max: Option<usize>, actual: usize, span: Span) -> Result<(), WhistleParseError> {
+    match max {
+        Some(max) if actual > max => Err(WhistleParseError::LimitExceeded { limit, max, span }),
+        _ => Ok(()),
+    }
+}
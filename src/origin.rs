@@ -0,0 +1,65 @@
+//! Where a rule was written, for rule sets merged from several files or
+//! groups.
+//!
+//! A [`RuleFile`] knows its own lines; once rules from several of them are
+//! put in one [`RuleSet`] only [`ProxyRule::origin`](crate::ProxyRule::origin)
+//! says which file or group to point a diagnostic or match explanation at.
+use std::fmt;
+
+use crate::rule_file::RuleFile;
+use crate::rule_set::RuleSet;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Origin {
+    /// A file path or group name.
+    pub source: String,
+    /// The zero-based line within `source`.
+    pub line: usize,
+}
+
+impl Origin {
+    pub fn new(source: impl Into<String>, line: usize) -> Self {
+        Origin { source: source.into(), line }
+    }
+}
+
+/// `rules.txt:3`, with the line counted from one as editors do.
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.source, self.line + 1)
+    }
+}
+
+impl RuleFile {
+    /// Like [`RuleFile::rule_set`], with each rule's origin set to its line
+    /// in `source`. For a group of [`parse_grouped`], pass the group name;
+    /// the lines count from the group's first line.
+    ///
+    /// [`parse_grouped`]: crate::rule_file::parse_grouped
+    pub fn rule_set_from(&self, source: &str) -> RuleSet {
+        self.rules()
+            .filter(|(_, _, enabled)| *enabled)
+            .map(|(id, rule, _)| {
+                let mut rule = rule.clone();
+                rule.origin = Some(Origin::new(source, id.0));
+                rule
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rule_file::parse_grouped;
+
+    #[test]
+    fn test_rule_set_from() {
+        let groups = parse_grouped("a.com b.com\n\n# api\nc.com d.com\ne.com f.com");
+        let set: RuleSet = groups.iter().flat_map(|(name, file)| file.rule_set_from(name).rules).collect();
+        let origins: Vec<String> = set.iter().map(|rule| rule.origin.as_ref().unwrap().to_string()).collect();
+        assert_eq!(origins, vec!["Default:1", "api:1", "api:2"]);
+        // the origin doesn't change what the rule is
+        assert_eq!(set.rules[0], groups[0].1.rule_set().rules[0]);
+    }
+}
@@ -0,0 +1,244 @@
+//! The pattern a rule applies to, the first token of a whistle line.
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use crate::matcher::split_host_port;
+use crate::{parse_uri, Span, Uri, WhistleParseError};
+
+/// The ports a pattern applies to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PortMatcher {
+    /// No port in the pattern, or `a.com:*`.
+    #[default]
+    Any,
+    Exact(u16),
+    /// `a.com:8000-8999`, both ends included.
+    Range(u16, u16),
+    /// A port that isn't a number, range or `*`, which matches nothing.
+    Never,
+}
+
+impl PortMatcher {
+    /// Read a port as written after the `:`, `None` if it isn't valid.
+    pub fn parse(port: &str) -> Option<PortMatcher> {
+        if port == "*" {
+            return Some(PortMatcher::Any);
+        }
+        match port.split_once('-') {
+            Some((start, end)) => {
+                let (start, end) = (start.parse().ok()?, end.parse().ok()?);
+                (start <= end).then_some(PortMatcher::Range(start, end))
+            }
+            None => port.parse().ok().map(PortMatcher::Exact),
+        }
+    }
+
+    /// The ports of a pattern host such as `a.com:8080`, [`PortMatcher::Never`]
+    /// for an invalid one and [`PortMatcher::Any`] without one.
+    pub(crate) fn of_host(host: &str) -> PortMatcher {
+        match split_host_port(host).1 {
+            Some(port) => PortMatcher::parse(port).unwrap_or(PortMatcher::Never),
+            None => PortMatcher::Any,
+        }
+    }
+
+    /// Whether `port` is one of the ports. A url without a port, in a
+    /// scheme without a default one, only matches [`PortMatcher::Any`].
+    pub fn matches(&self, port: Option<u16>) -> bool {
+        match (self, port) {
+            (PortMatcher::Any, _) => true,
+            (PortMatcher::Exact(p), Some(port)) => *p == port,
+            (PortMatcher::Range(start, end), Some(port)) => (*start..=*end).contains(&port),
+            _ => false,
+        }
+    }
+}
+
+/// The schemes a pattern applies to.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub enum SchemeMatcher {
+    /// No scheme in the pattern, or `*://`.
+    #[default]
+    Any,
+    /// Lowercased, compared ignoring case.
+    Exact(String),
+}
+
+impl SchemeMatcher {
+    /// Read a scheme as written before the `://`, empty when there is none.
+    pub fn parse(scheme: &str) -> SchemeMatcher {
+        match scheme {
+            "" | "*" => SchemeMatcher::Any,
+            scheme => SchemeMatcher::Exact(scheme.to_ascii_lowercase()),
+        }
+    }
+
+    pub fn matches(&self, scheme: &str) -> bool {
+        match self {
+            SchemeMatcher::Any => true,
+            SchemeMatcher::Exact(s) => s.eq_ignore_ascii_case(scheme),
+        }
+    }
+
+    /// [`SchemeMatcher::matches`] for a scheme as written, without reading it.
+    pub(crate) fn written_matches(written: &str, scheme: &str) -> bool {
+        matches!(written, "" | "*") || written.eq_ignore_ascii_case(scheme)
+    }
+}
+
+/// The explicit spelling, `*` for any scheme.
+impl fmt::Display for SchemeMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemeMatcher::Any => f.write_str("*"),
+            SchemeMatcher::Exact(scheme) => f.write_str(scheme),
+        }
+    }
+}
+
+/// Patterns compare, hash and sort by their [`Uri`], so spellings that
+/// only differ in the case of the scheme or host are the same pattern.
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    /// The pattern as written.
+    pub raw: String,
+    pub uri: Uri,
+    pub scheme: SchemeMatcher,
+    pub port: PortMatcher,
+}
+
+impl Pattern {
+    pub fn parse(input: &str) -> Result<Pattern, WhistleParseError> {
+        let (_, uri) = nom::combinator::all_consuming(parse_uri)(input)
+            .map_err(|e| WhistleParseError::from_nom(input, e))?;
+        let port = match split_host_port(&uri.host) {
+            (host, Some(port)) => PortMatcher::parse(port).ok_or_else(|| {
+                let scheme = if uri.scheme.is_empty() { 0 } else { uri.scheme.len() + 3 };
+                let at = scheme + host.len() + 1;
+                WhistleParseError::InvalidPort {
+                    port: port.to_string(),
+                    span: Span::new(at, at + port.len()),
+                }
+            })?,
+            (_, None) => PortMatcher::Any,
+        };
+        let scheme = SchemeMatcher::parse(&uri.scheme);
+        Ok(Pattern { raw: input.to_string(), uri, scheme, port })
+    }
+
+    /// The host without its port.
+    pub fn host(&self) -> &str {
+        split_host_port(&self.uri.host).0
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.uri == other.uri
+    }
+}
+
+impl Eq for Pattern {}
+
+impl Hash for Pattern {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.uri.hash(state);
+    }
+}
+
+impl Ord for Pattern {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.uri.cmp(&other.uri)
+    }
+}
+
+impl PartialOrd for Pattern {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Pattern {
+    /// Whether the pattern matches more than one host or url: `*.a.com`,
+    /// `.a.com`, `^a.com/*`, `/regex/` and the like.
+    pub fn is_wildcard(&self) -> bool {
+        let raw = self.raw.trim_start_matches('!');
+        let raw = raw.strip_prefix("*://").unwrap_or(raw);
+        raw.starts_with(['^', '/', '.']) || raw.contains('*')
+    }
+}
+
+/// An invalid port becomes [`PortMatcher::Never`] rather than widening
+/// the pattern to any port.
+impl From<Uri> for Pattern {
+    fn from(uri: Uri) -> Self {
+        let port = PortMatcher::of_host(&uri.host);
+        let scheme = SchemeMatcher::parse(&uri.scheme);
+        Pattern { raw: uri.to_string(), uri, scheme, port }
+    }
+}
+
+impl crate::ProxyRule {
+    pub fn pattern(&self) -> Pattern {
+        Pattern::from(self.source.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pattern_ord() {
+        let mut patterns: Vec<Pattern> = ["b.com", "HTTPS://A.com/x", "https://a.com/x", "a.com:8080"]
+            .into_iter()
+            .map(|p| Pattern::parse(p).unwrap())
+            .collect();
+        patterns.sort();
+        patterns.dedup();
+        let raw: Vec<_> = patterns.iter().map(|p| p.raw.as_str()).collect();
+        assert_eq!(raw, ["a.com:8080", "b.com", "HTTPS://A.com/x"]);
+        let set: std::collections::HashSet<_> = patterns.into_iter().collect();
+        assert!(set.contains(&Pattern::parse("https://a.COM/x").unwrap()));
+        assert!(!set.contains(&Pattern::parse("https://a.com/X").unwrap()));
+    }
+
+    #[test]
+    fn test_port_matcher() {
+        assert_eq!(Pattern::parse("a.com").unwrap().port, PortMatcher::Any);
+        assert_eq!(Pattern::parse("a.com:*").unwrap().port, PortMatcher::Any);
+        assert_eq!(Pattern::parse("http://a.com:8000-8999/x").unwrap().port, PortMatcher::Range(8000, 8999));
+        assert_eq!(Pattern::parse("[::1]:8080").unwrap().port, PortMatcher::Exact(8080));
+        assert_eq!(
+            Pattern::parse("http://a.com:99-1"),
+            Err(WhistleParseError::InvalidPort { port: "99-1".into(), span: Span::new(13, 17) })
+        );
+        let uri = crate::parse_uri("a.com:x").unwrap().1;
+        assert_eq!(Pattern::from(uri).port, PortMatcher::Never);
+
+        let pattern = Pattern::parse("a.com:8000-8999").unwrap();
+        assert!(pattern.matches("http://a.com:8000/"));
+        assert!(pattern.matches("http://a.com:8999/"));
+        assert!(!pattern.matches("http://a.com:9000/"));
+        assert!(!pattern.matches("http://a.com/"));
+        assert!(Pattern::parse("a.com:*").unwrap().matches("a.com/x"));
+        assert!(!PortMatcher::Never.matches(Some(80)));
+    }
+
+    #[test]
+    fn test_scheme_matcher() {
+        assert_eq!(Pattern::parse("a.com").unwrap().scheme, SchemeMatcher::Any);
+        assert_eq!(Pattern::parse("*://a.com").unwrap().scheme, SchemeMatcher::Any);
+        let pattern = Pattern::parse("HTTPS://a.com").unwrap();
+        assert_eq!(pattern.scheme, SchemeMatcher::Exact("https".into()));
+        assert_eq!(pattern.scheme.to_string(), "https");
+        assert_eq!(SchemeMatcher::Any.to_string(), "*");
+        assert!(pattern.matches("https://a.com/"));
+        assert!(!pattern.matches("http://a.com/"));
+        for url in ["http://a.com/", "https://a.com/", "ws://a.com/"] {
+            assert!(Pattern::parse("*://a.com").unwrap().matches(url), "{url}");
+        }
+        assert!(!Pattern::parse("*://a.com").unwrap().is_wildcard());
+    }
+}
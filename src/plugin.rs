@@ -0,0 +1,116 @@
+//! Values of plugin protocols, `whistle.vase://tplName?x=1`.
+//!
+//! Whistle hands the value to the plugin as written. Plugins read it the
+//! same way, as an entry such as a template or rule name, then `?` and
+//! form-urlencoded arguments.
+use std::fmt;
+
+use crate::form::{parse_form, percent_encode};
+use crate::ops::DOCUMENTED_PROTOCOLS;
+use crate::registry::PLUGIN_PREFIX;
+use crate::{OpValue, ProxyRule, Rule};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PluginOp {
+    /// The plugin's name, without [`PLUGIN_PREFIX`].
+    pub plugin: String,
+    /// The value before the `?`, empty for `whistle.vase://?x=1`.
+    pub entry: String,
+    /// The decoded arguments after the `?`, in order. A name without `=`
+    /// has an empty value.
+    pub args: Vec<(String, String)>,
+}
+
+/// Whether whistle hands operations of `protocol` to a plugin: a
+/// `whistle.name` protocol, or an undocumented `name`. Plugin names are
+/// lowercase letters, digits, `-` and `_`.
+pub fn is_plugin_protocol(protocol: &str) -> bool {
+    let name = protocol.strip_prefix(PLUGIN_PREFIX);
+    let valid = |name: &str| {
+        !name.is_empty() && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+    };
+    match name {
+        Some(name) => valid(name),
+        None => valid(protocol) && !DOCUMENTED_PROTOCOLS.contains(&protocol),
+    }
+}
+
+impl PluginOp {
+    /// `None` when `protocol` isn't a [plugin protocol](is_plugin_protocol).
+    pub fn parse(protocol: &str, value: &str) -> Option<PluginOp> {
+        if !is_plugin_protocol(protocol) {
+            return None;
+        }
+        let (entry, args) = value.split_once('?').unwrap_or((value, ""));
+        Some(PluginOp {
+            plugin: protocol.strip_prefix(PLUGIN_PREFIX).unwrap_or(protocol).to_string(),
+            entry: entry.to_string(),
+            args: parse_form(args),
+        })
+    }
+
+    /// The operation as a plugin's, `None` for other protocols and a
+    /// `{key}` or template value that has to be resolved first.
+    pub fn from_rule(rule: &Rule) -> Option<PluginOp> {
+        match &rule.value {
+            OpValue::Raw(value) | OpValue::Inline(value) => PluginOp::parse(&rule.name, value),
+            _ => None,
+        }
+    }
+
+    /// The value of the first argument `name`.
+    pub fn arg(&self, name: &str) -> Option<&str> {
+        self.args.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+    }
+}
+
+/// `whistle.plugin://entry?args`, arguments encoded again.
+impl fmt::Display for PluginOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{PLUGIN_PREFIX}{}://{}", self.plugin, self.entry)?;
+        for (i, (name, value)) in self.args.iter().enumerate() {
+            let separator = if i == 0 { '?' } else { '&' };
+            write!(f, "{separator}{}", percent_encode(name))?;
+            if !value.is_empty() {
+                write!(f, "={}", percent_encode(value))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ProxyRule {
+    /// The operations of the rule handed to plugins, in order.
+    pub fn plugin_ops(&self) -> Vec<PluginOp> {
+        self.operations().iter().filter_map(PluginOp::from_rule).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse_proxy_rule;
+
+    #[test]
+    fn test_plugin_op() {
+        let op = PluginOp::parse("whistle.vase", "tplName?x=1&name=a%20b&flag").unwrap();
+        assert_eq!(op.plugin, "vase");
+        assert_eq!(op.entry, "tplName");
+        assert_eq!(op.arg("name"), Some("a b"));
+        assert_eq!(op.arg("flag"), Some(""));
+        assert_eq!(op.to_string(), "whistle.vase://tplName?x=1&name=a%20b&flag");
+        assert_eq!(PluginOp::parse("vase", "?x=1").unwrap().entry, "");
+        assert_eq!(PluginOp::parse("file", "/x"), None);
+        assert_eq!(PluginOp::parse("whistle.Vase", "x"), None);
+    }
+
+    #[test]
+    fn test_plugin_ops() {
+        let rule = parse_proxy_rule("a.com whistle.vase://tpl?x=1 file://x mock://{v} inspect://page").unwrap().1;
+        let ops = rule.plugin_ops();
+        assert_eq!(ops.len(), 2);
+        assert_eq!(ops[1], PluginOp { plugin: "inspect".into(), entry: "page".into(), args: vec![] });
+        assert!(is_plugin_protocol("whistle.my-plugin"));
+        assert!(!is_plugin_protocol("reqHeaders"));
+    }
+}
@@ -0,0 +1,283 @@
+//! Text transforms applied to a rule file before it is parsed.
+//!
+//! Snippets stop teams from copy-pasting the same block of rules for every
+//! service. A snippet is defined once and expanded wherever it is called,
+//! with `{{param}}` replaced by the call's arguments:
+//!
+//! ```text
+//! @define mock(host, dir)
+//! {{host}}/api file://{{dir}}/api
+//! {{host}}/static file://{{dir}}/static
+//! @end
+//!
+//! @mock(a.com, /mocks/a)
+//! @mock(b.com, /mocks/b)
+//! ```
+//!
+//! Spans of the parsed rules point into the expanded text.
+//!
+//! Conditional sections let one shared file serve every stage. They are
+//! `#` lines, so whistle itself reads them as comments:
+//!
+//! ```text
+//! #if STAGE=dev
+//! api.example.com 127.0.0.1:8080
+//! #else
+//! api.example.com 10.0.0.2
+//! #endif
+//! ```
+//!
+//! `#if KEY!=value` and a bare `#if KEY`, true when the variable is set and
+//! not empty, work too, and sections nest. Other comments starting with
+//! `#if`, like `#if you need this, ask ops`, are left alone, and so are
+//! the lines of ```` ``` ```` value blocks.
+use std::collections::HashMap;
+
+use crate::rule_file::split_fence;
+
+/// The nesting of snippets calling snippets is cut off here.
+pub const MAX_SNIPPET_DEPTH: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreprocessError {
+    /// `@define` on zero-based line `line` has no `@end`.
+    UnterminatedDefine { name: String, line: usize },
+    /// A call has a different number of arguments than the snippet has
+    /// parameters.
+    Arity { name: String, expected: usize, found: usize, line: usize },
+    /// Snippets call each other deeper than [`MAX_SNIPPET_DEPTH`], which
+    /// usually means a snippet calls itself.
+    TooDeep { name: String, line: usize },
+    /// An `#if` without its `#endif`.
+    UnterminatedIf { line: usize },
+    /// An `#else` or `#endif` without an `#if`.
+    UnmatchedDirective { directive: String, line: usize },
+}
+
+/// Evaluate the conditional sections of `text` against `vars`, then expand
+/// its snippets.
+pub fn preprocess(text: &str, vars: &HashMap<String, String>) -> Result<String, PreprocessError> {
+    expand_snippets(&evaluate_conditions(text, vars)?)
+}
+
+/// Keep the lines of the sections whose condition holds. Directives and
+/// dropped lines become empty lines, so every kept line stays on its line
+/// number.
+pub fn evaluate_conditions(text: &str, vars: &HashMap<String, String>) -> Result<String, PreprocessError> {
+    // per open `#if`: its line, whether the current branch is taken, and
+    // whether an `#else` was seen, after which no branch is taken again
+    let mut open: Vec<(usize, bool, bool)> = vec![];
+    // the fence of the value block being read, whose lines are all kept
+    let mut fence: Option<&str> = None;
+    let mut out = String::with_capacity(text.len());
+    for (number, line) in text.lines().enumerate() {
+        let trimmed = line.trim();
+        let active = open.iter().all(|(_, taken, _)| *taken);
+        if let Some(open_fence) = fence {
+            if trimmed == open_fence {
+                fence = None;
+            }
+            if active {
+                out.push_str(line);
+            }
+            out.push('\n');
+            continue;
+        }
+        fence = split_fence(trimmed).map(|(fence, _)| fence);
+        let directive = trimmed.split_whitespace().next().unwrap_or_default();
+        let condition = (directive == "#if").then(|| Condition::parse(trimmed["#if".len()..].trim())).flatten();
+        match (directive, condition) {
+            (_, Some(condition)) => open.push((number, condition.holds(vars), false)),
+            ("#else" | "#endif", _) => {
+                let Some(last) = open.last_mut() else {
+                    return Err(PreprocessError::UnmatchedDirective { directive: directive.to_string(), line: number });
+                };
+                if directive == "#else" {
+                    *last = (last.0, !last.1 && !last.2, true);
+                } else {
+                    open.pop();
+                }
+            }
+            _ if active => out.push_str(line),
+            _ => {}
+        }
+        out.push('\n');
+    }
+    match open.first() {
+        Some((line, ..)) => Err(PreprocessError::UnterminatedIf { line: *line }),
+        None => Ok(out),
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Snippet {
+    params: Vec<String>,
+    body: Vec<String>,
+}
+
+/// The condition of an `#if`: `KEY`, `KEY=value` or `KEY!=value`.
+enum Condition<'a> {
+    Set(&'a str),
+    Equals(&'a str, &'a str),
+    NotEquals(&'a str, &'a str),
+}
+
+impl<'a> Condition<'a> {
+    fn parse(text: &'a str) -> Option<Condition<'a>> {
+        let is_key = |key: &str| {
+            !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+        };
+        let is_value = |value: &str| !value.contains(char::is_whitespace);
+        let condition = match text.split_once("!=") {
+            Some((key, value)) => Condition::NotEquals(key.trim(), value.trim()),
+            None => match text.split_once('=') {
+                Some((key, value)) => Condition::Equals(key.trim(), value.trim()),
+                None => Condition::Set(text),
+            },
+        };
+        match condition {
+            Condition::Set(key) => is_key(key),
+            Condition::Equals(key, value) | Condition::NotEquals(key, value) => is_key(key) && is_value(value),
+        }
+        .then_some(condition)
+    }
+
+    fn holds(&self, vars: &HashMap<String, String>) -> bool {
+        match self {
+            Condition::Set(key) => vars.get(*key).is_some_and(|value| !value.is_empty()),
+            Condition::Equals(key, value) => vars.get(*key).map(String::as_str) == Some(*value),
+            Condition::NotEquals(key, value) => vars.get(*key).map(String::as_str) != Some(*value),
+        }
+    }
+}
+
+/// `name(a, b)` split into the name and its trimmed arguments.
+fn call(text: &str) -> Option<(&str, Vec<&str>)> {
+    let (name, args) = text.trim().strip_suffix(')')?.split_once('(')?;
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
+        return None;
+    }
+    let args = args.split(',').map(str::trim).collect::<Vec<_>>();
+    Some((name, if args == [""] { vec![] } else { args }))
+}
+
+/// Expand the snippets of `text`. Calls of names without a `@define` are
+/// left alone, so `@url` includes pass through.
+pub fn expand_snippets(text: &str) -> Result<String, PreprocessError> {
+    let mut snippets = HashMap::new();
+    let mut rest = vec![];
+    let mut lines = text.lines().enumerate();
+    while let Some((number, line)) = lines.next() {
+        let Some((name, params)) = line.trim().strip_prefix("@define ").and_then(call) else {
+            rest.push((number, line));
+            continue;
+        };
+        let mut body = vec![];
+        let mut closed = false;
+        for (_, line) in lines.by_ref() {
+            if line.trim() == "@end" {
+                closed = true;
+                break;
+            }
+            body.push(line.to_string());
+        }
+        if !closed {
+            return Err(PreprocessError::UnterminatedDefine { name: name.to_string(), line: number });
+        }
+        let params = params.into_iter().map(str::to_string).collect();
+        snippets.insert(name.to_string(), Snippet { params, body });
+    }
+
+    let mut out = String::with_capacity(text.len());
+    for (number, line) in rest {
+        expand_line(line, number, &snippets, 0, &mut out)?;
+    }
+    Ok(out)
+}
+
+fn expand_line(
+    line: &str,
+    number: usize,
+    snippets: &HashMap<String, Snippet>,
+    depth: usize,
+    out: &mut String,
+) -> Result<(), PreprocessError> {
+    let called = line.trim().strip_prefix('@').and_then(call);
+    let Some((name, (snippet, args))) = called.and_then(|(name, args)| Some((name, (snippets.get(name)?, args)))) else {
+        out.push_str(line);
+        out.push('\n');
+        return Ok(());
+    };
+    if args.len() != snippet.params.len() {
+        return Err(PreprocessError::Arity {
+            name: name.to_string(),
+            expected: snippet.params.len(),
+            found: args.len(),
+            line: number,
+        });
+    }
+    if depth >= MAX_SNIPPET_DEPTH {
+        return Err(PreprocessError::TooDeep { name: name.to_string(), line: number });
+    }
+    for body in &snippet.body {
+        let mut body = body.clone();
+        for (param, arg) in snippet.params.iter().zip(&args) {
+            body = body.replace(&format!("{{{{{param}}}}}"), arg);
+        }
+        expand_line(&body, number, snippets, depth + 1, out)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_expand_snippets() {
+        let text = "@define mock(host, dir)\n{{host}}/api file://{{dir}}/api\n@log({{host}})\n@end\n\
+                    @define log(host)\n{{host}} log://{{host}}\n@end\n\
+                    # services\n@mock(a.com, /m/a)\n@https://b.com/rules.txt\n@other(x)\n";
+        assert_eq!(
+            expand_snippets(text).unwrap(),
+            "# services\na.com/api file:///m/a/api\na.com log://a.com\n@https://b.com/rules.txt\n@other(x)\n"
+        );
+    }
+
+    #[test]
+    fn test_evaluate_conditions() {
+        let text = "a.com 1\n#if STAGE=dev\nb.com 2\n#if DEBUG\nc.com log://x\n#endif\n#else\nb.com 3\n#endif\n#if STAGE != prod\nd.com 4\n#endif";
+        let vars = HashMap::from([("STAGE".to_string(), "dev".to_string())]);
+        assert_eq!(evaluate_conditions(text, &vars).unwrap(), "a.com 1\n\nb.com 2\n\n\n\n\n\n\n\nd.com 4\n\n");
+        let vars = HashMap::from([("STAGE".to_string(), "prod".to_string())]);
+        assert_eq!(evaluate_conditions(text, &vars).unwrap(), "a.com 1\n\n\n\n\n\n\nb.com 3\n\n\n\n\n");
+
+        assert_eq!(
+            evaluate_conditions("#if X\na b", &HashMap::new()),
+            Err(PreprocessError::UnterminatedIf { line: 0 })
+        );
+        assert_eq!(
+            evaluate_conditions("a b\n#endif", &HashMap::new()),
+            Err(PreprocessError::UnmatchedDirective { directive: "#endif".into(), line: 1 })
+        );
+
+        let text = "#if you need this, ask ops\na b\n#if X=a b\n```x\n#if X\n#endif\n```";
+        assert_eq!(evaluate_conditions(text, &HashMap::new()).unwrap(), format!("{text}\n"));
+    }
+
+    #[test]
+    fn test_snippet_errors() {
+        assert_eq!(
+            expand_snippets("a.com b.com\n@define x(a)\n{{a}} b.com"),
+            Err(PreprocessError::UnterminatedDefine { name: "x".into(), line: 1 })
+        );
+        assert_eq!(
+            expand_snippets("@define x(a, b)\n@end\n@x(1)"),
+            Err(PreprocessError::Arity { name: "x".into(), expected: 2, found: 1, line: 2 })
+        );
+        assert!(matches!(
+            expand_snippets("@define x()\n@x()\n@end\n@x()"),
+            Err(PreprocessError::TooDeep { line: 3, .. })
+        ));
+    }
+}
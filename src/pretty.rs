@@ -0,0 +1,84 @@
+//! Render a [`Diagnostic`] for a terminal, with the offending line, a caret
+//! under the bad span and the suggested fix.
+//!
+//! ```text
+//! error[trailing-input]: `oops` is not an operation
+//!  --> rules.txt:1:13
+//!   |
+//! 1 | a.com b.com oops
+//!   |             ^^^^
+//! ```
+use std::fmt::Write;
+
+use crate::diagnostic::Diagnostic;
+use crate::rule_file::split_lines;
+
+/// `diagnostic` rendered against the `text` it was reported on. `name` is
+/// shown in the location line, usually the path of the rule file.
+pub fn render(diagnostic: &Diagnostic, name: &str, text: &str) -> String {
+    // lines end the way the parser ends them, at `\n`, `\r\n` or `\r`
+    let lines = split_lines(text);
+    let start = diagnostic.span.start.min(text.len());
+    let index = lines.iter().rposition(|(offset, _)| *offset <= start).unwrap_or(0);
+    let (line_start, line) = lines[index];
+    let start = start.max(line_start);
+    let line_no = index + 1;
+    let end = diagnostic.span.end.clamp(start, line_start + line.len());
+
+    // keep tabs so the caret lines up however the terminal expands them
+    let before = &text[line_start..start];
+    let pad: String = before.chars().map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+    let carets = "^".repeat(text[start..end].chars().count().max(1));
+    let column = before.chars().count() + 1;
+    let gutter = " ".repeat(line_no.to_string().len());
+
+    let mut out = String::new();
+    let _ = writeln!(out, "{}[{}]: {}", diagnostic.severity, diagnostic.code, diagnostic.message);
+    let _ = writeln!(out, "{gutter}--> {name}:{line_no}:{column}");
+    let _ = writeln!(out, "{gutter} |");
+    let _ = writeln!(out, "{line_no} | {line}");
+    let _ = writeln!(out, "{gutter} | {pad}{carets}");
+    if let Some(fix) = &diagnostic.fix {
+        let old = text.get(fix.span.start..fix.span.end).unwrap_or_default().trim();
+        let help = match (old.is_empty(), fix.new_text.is_empty()) {
+            (_, true) => format!("remove `{old}`"),
+            (true, false) => format!("insert `{}`", fix.new_text),
+            (false, false) => format!("replace `{old}` with `{}`", fix.new_text),
+        };
+        let _ = writeln!(out, "{gutter} = help: {help}");
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::diagnostic::diagnostics;
+    use crate::meta::Date;
+    use crate::rule_file::RuleFile;
+
+    #[test]
+    fn test_render() {
+        let text = "a.com b.com oops\n\tc.com file://x  file://y\n";
+        let found = diagnostics(&RuleFile::parse(text), Date { year: 2025, month: 3, day: 1 });
+        assert_eq!(
+            render(&found[0], "rules.txt", text),
+            "error[trailing-input]: `oops` is not an operation\n \
+             --> rules.txt:1:13\n  |\n1 | a.com b.com oops\n  |             ^^^^\n"
+        );
+        assert_eq!(
+            render(&found[1], "rules.txt", text),
+            "warning[conflict]: `file://` is ignored, whistle uses `file://` from earlier on the line\n \
+             --> rules.txt:2:18\n  |\n2 | \tc.com file://x  file://y\n  | \t                ^^^^^^^^\n  \
+             = help: remove `file://y`\n"
+        );
+
+        let text = "a.com 127.0.0.1\rb.com b.com oops\r\nc.com 127.0.0.1";
+        let found = diagnostics(&RuleFile::parse(text), Date { year: 2025, month: 3, day: 1 });
+        assert_eq!(
+            render(&found[0], "rules.txt", text),
+            "error[trailing-input]: `oops` is not an operation\n \
+             --> rules.txt:2:13\n  |\n2 | b.com b.com oops\n  |             ^^^^\n"
+        );
+    }
+}
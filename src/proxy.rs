@@ -0,0 +1,129 @@
+//! Upstream proxies of `proxy://`, `http-proxy://`, `https-proxy://`,
+//! `socks://` and `pac://` values.
+use crate::{OpValue, Rule};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProxyAuth {
+    pub username: String,
+    pub password: String,
+}
+
+/// One `user:pass@host:port` of a proxy value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProxyServer {
+    /// The host, an IPv6 address without its brackets.
+    pub host: String,
+    /// `None` to use the protocol's default port.
+    pub port: Option<u16>,
+    pub auth: Option<ProxyAuth>,
+}
+
+/// Where a proxy operation sends traffic. Servers are a fallback chain,
+/// `proxy://a:8080|b:8080` tries `b` when `a` can't be reached.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProxyTarget {
+    /// `proxy://` and `http-proxy://`.
+    Http(Vec<ProxyServer>),
+    Https(Vec<ProxyServer>),
+    Socks(Vec<ProxyServer>),
+    /// The url or path of a PAC script.
+    Pac(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProxyTargetError {
+    /// Not one of the proxy protocols.
+    NotProxy,
+    /// The value is empty or a `{key}`, which has to be resolved first.
+    MissingValue,
+    /// An empty server between `|`s or before a `:port`.
+    MissingHost(String),
+    InvalidPort(String),
+}
+
+impl Rule {
+    pub fn proxy_target(&self) -> Result<ProxyTarget, ProxyTargetError> {
+        let value = match &self.value {
+            OpValue::Raw(s) | OpValue::Inline(s) if !s.is_empty() => s.as_str(),
+            OpValue::Raw(_) | OpValue::Inline(_) | OpValue::Value(_) => {
+                return Err(ProxyTargetError::MissingValue)
+            }
+            OpValue::TemplateString(_) => return Err(ProxyTargetError::MissingValue),
+        };
+        let chain = || value.split('|').map(parse_server).collect::<Result<Vec<_>, _>>();
+        match self.name.as_str() {
+            "proxy" | "http-proxy" => chain().map(ProxyTarget::Http),
+            "https-proxy" => chain().map(ProxyTarget::Https),
+            "socks" => chain().map(ProxyTarget::Socks),
+            "pac" => Ok(ProxyTarget::Pac(value.to_string())),
+            _ => Err(ProxyTargetError::NotProxy),
+        }
+    }
+}
+
+fn parse_server(input: &str) -> Result<ProxyServer, ProxyTargetError> {
+    let input = input.trim();
+    let (auth, address) = match input.rsplit_once('@') {
+        Some((auth, address)) => {
+            let (username, password) = auth.split_once(':').unwrap_or((auth, ""));
+            let auth = ProxyAuth { username: username.to_string(), password: password.to_string() };
+            (Some(auth), address)
+        }
+        None => (None, input),
+    };
+    let (host, port) = match address.strip_prefix('[') {
+        Some(rest) => match rest.split_once(']') {
+            Some((host, port)) => (host, port.strip_prefix(':')),
+            None => return Err(ProxyTargetError::MissingHost(input.to_string())),
+        },
+        None => match address.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (address, None),
+        },
+    };
+    if host.is_empty() {
+        return Err(ProxyTargetError::MissingHost(input.to_string()));
+    }
+    let port = port
+        .map(|port| port.parse().map_err(|_| ProxyTargetError::InvalidPort(port.to_string())))
+        .transpose()?;
+    Ok(ProxyServer { host: host.to_string(), port, auth })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn target(s: &str) -> Result<ProxyTarget, ProxyTargetError> {
+        crate::parse_rule(s).unwrap().1.proxy_target()
+    }
+
+    #[test]
+    fn test_proxy_target() {
+        let server = |host: &str, port| ProxyServer { host: host.into(), port, auth: None };
+        assert_eq!(
+            target("proxy://127.0.0.1:8080|[::1]:8888|backup"),
+            Ok(ProxyTarget::Http(vec![
+                server("127.0.0.1", Some(8080)),
+                server("::1", Some(8888)),
+                server("backup", None),
+            ]))
+        );
+        assert_eq!(
+            target("socks://user:p@ss@10.0.0.1:1080"),
+            Ok(ProxyTarget::Socks(vec![ProxyServer {
+                host: "10.0.0.1".into(),
+                port: Some(1080),
+                auth: Some(ProxyAuth { username: "user".into(), password: "p@ss".into() }),
+            }]))
+        );
+        assert_eq!(
+            target("pac://http://a.com/proxy.pac"),
+            Ok(ProxyTarget::Pac("http://a.com/proxy.pac".into()))
+        );
+        assert_eq!(target("https-proxy://a:99999"), Err(ProxyTargetError::InvalidPort("99999".into())));
+        assert_eq!(target("proxy://a||b"), Err(ProxyTargetError::MissingHost("".into())));
+        assert_eq!(target("proxy://{p}"), Err(ProxyTargetError::MissingValue));
+        assert_eq!(target("file://a"), Err(ProxyTargetError::NotProxy));
+    }
+}
@@ -0,0 +1,59 @@
+//! `redirect://` targets and the url a redirect sends the client to.
+use nom::combinator::all_consuming;
+
+use crate::matcher::RequestUrl;
+use crate::{parse_uri, Uri};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RedirectOp {
+    pub target: Uri,
+    /// The `#hash` of the target, without the `#`.
+    pub fragment: Option<String>,
+}
+
+impl RedirectOp {
+    pub fn parse(value: &str) -> Option<RedirectOp> {
+        let (url, fragment) = match value.split_once('#') {
+            Some((url, fragment)) => (url, Some(fragment.to_string())),
+            None => (value, None),
+        };
+        let (_, target) = all_consuming(parse_uri)(url).ok()?;
+        Some(RedirectOp { target, fragment })
+    }
+
+    /// The `Location` for a request to `original_url`. The original query
+    /// is kept unless the target has its own, and so is the original hash.
+    pub fn resolve(&self, original_url: &str) -> String {
+        let (original, original_fragment) = match original_url.split_once('#') {
+            Some((url, fragment)) => (url, Some(fragment)),
+            None => (original_url, None),
+        };
+        let mut location = self.target.clone();
+        if location.query.is_empty() {
+            location.query = RequestUrl::parse(original).query.to_string();
+        }
+        let mut location = location.to_string();
+        if let Some(fragment) = self.fragment.as_deref().or(original_fragment) {
+            location.push('#');
+            location.push_str(fragment);
+        }
+        location
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_redirect_resolve() {
+        let op = RedirectOp::parse("https://b.com/login").unwrap();
+        assert_eq!(op.target.host, "b.com");
+        assert_eq!(op.resolve("http://a.com/x?from=1#top"), "https://b.com/login?from=1#top");
+        assert_eq!(op.resolve("http://a.com/x"), "https://b.com/login");
+
+        let op = RedirectOp::parse("https://b.com/?lang=en#main").unwrap();
+        assert_eq!(op.fragment.as_deref(), Some("main"));
+        assert_eq!(op.resolve("http://a.com/?from=1#top"), "https://b.com/?lang=en#main");
+    }
+}
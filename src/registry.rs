@@ -0,0 +1,152 @@
+//! Typed values for plugin protocols such as `whistle.mycompany://`.
+//!
+//! The crate only knows the protocols whistle ships with. A
+//! [`ProtocolRegistry`] lets callers lower their own protocols into
+//! [`RuleOp::Custom`] the same way, instead of matching on
+//! [`RuleOp::Unknown`] themselves.
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::ops::{OpError, RuleOp};
+use crate::{OpValue, ProxyRule, Rule};
+
+/// How whistle spells plugin protocols, `whistle.name://`. Plugins can also
+/// be written as just `name://`.
+pub const PLUGIN_PREFIX: &str = "whistle.";
+
+/// The value a registered parser made of an operation.
+#[derive(Clone)]
+pub struct CustomValue {
+    /// The protocol as written in the rule.
+    pub protocol: String,
+    /// The text the value was parsed from.
+    pub raw: String,
+    value: Arc<dyn Any + Send + Sync>,
+}
+
+impl CustomValue {
+    /// Wrap a parsed value. The registry fills in the protocol and text.
+    pub fn new(value: impl Any + Send + Sync) -> Self {
+        CustomValue { protocol: String::new(), raw: String::new(), value: Arc::new(value) }
+    }
+
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.value.downcast_ref()
+    }
+}
+
+impl fmt::Debug for CustomValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CustomValue")
+            .field("protocol", &self.protocol)
+            .field("raw", &self.raw)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Values compare by protocol and text, since one parser makes the same
+/// value of the same text.
+impl PartialEq for CustomValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.protocol == other.protocol && self.raw == other.raw
+    }
+}
+
+/// A parser for one protocol's values. An `Err` is the message of the
+/// [`OpError::Custom`] the operation lowers to.
+pub type ProtocolParser = Arc<dyn Fn(&str) -> Result<CustomValue, String> + Send + Sync>;
+
+#[derive(Clone, Default)]
+pub struct ProtocolRegistry {
+    parsers: HashMap<String, ProtocolParser>,
+}
+
+impl fmt::Debug for ProtocolRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProtocolRegistry")
+            .field("protocols", &self.parsers.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl ProtocolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse the values of `protocol` with `f`. A plugin registered as
+    /// `whistle.name` also handles `name://` and the other way around.
+    pub fn register<F>(&mut self, protocol: impl Into<String>, f: F) -> &mut Self
+    where
+        F: Fn(&str) -> Result<CustomValue, String> + Send + Sync + 'static,
+    {
+        let protocol = protocol.into();
+        let protocol = protocol.strip_prefix(PLUGIN_PREFIX).map(str::to_string).unwrap_or(protocol);
+        self.parsers.insert(protocol, Arc::new(f));
+        self
+    }
+
+    pub fn get(&self, protocol: &str) -> Option<&ProtocolParser> {
+        self.parsers.get(protocol.strip_prefix(PLUGIN_PREFIX).unwrap_or(protocol))
+    }
+
+    /// Lower `rule` with its registered parser, or as [`RuleOp::try_from`]
+    /// does when there is none.
+    pub fn lower(&self, rule: &Rule) -> Result<RuleOp, OpError> {
+        let Some(parser) = self.get(&rule.name) else {
+            return RuleOp::try_from(rule);
+        };
+        let (OpValue::Raw(raw) | OpValue::Inline(raw)) = &rule.value else {
+            return Err(OpError::InvalidValue { protocol: rule.name.clone(), value: rule.value.to_string() });
+        };
+        let mut value = parser(raw).map_err(|message| OpError::Custom {
+            protocol: rule.name.clone(),
+            message,
+        })?;
+        value.protocol = rule.name.clone();
+        value.raw = raw.clone();
+        Ok(RuleOp::Custom(value))
+    }
+
+    /// Like [`ProxyRule::ops`], with the registered protocols typed.
+    pub fn ops(&self, rule: &ProxyRule) -> Vec<Result<RuleOp, OpError>> {
+        rule.operations().iter().map(|rule| self.lower(rule)).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Mock {
+        delay: u32,
+    }
+
+    #[test]
+    fn test_registry() {
+        let mut registry = ProtocolRegistry::new();
+        registry.register("whistle.mycompany", |value| {
+            let delay = value.parse().map_err(|_| format!("`{value}` isn't a delay"))?;
+            Ok(CustomValue::new(Mock { delay }))
+        });
+        let rule = crate::parse_proxy_rule("a.com whistle.mycompany://20 mycompany://x mycompany://{v} log://1")
+            .unwrap()
+            .1;
+        let ops = registry.ops(&rule);
+        let Ok(RuleOp::Custom(value)) = &ops[0] else {
+            panic!("{:?}", ops[0]);
+        };
+        assert_eq!(value.protocol, "whistle.mycompany");
+        assert_eq!(value.downcast_ref::<Mock>(), Some(&Mock { delay: 20 }));
+        assert_eq!(
+            ops[1],
+            Err(OpError::Custom { protocol: "mycompany".into(), message: "`x` isn't a delay".into() })
+        );
+        assert!(matches!(ops[2], Err(OpError::InvalidValue { .. })));
+        assert!(matches!(ops[3], Ok(RuleOp::Debug(_))));
+        assert!(matches!(&rule.ops()[0], Ok(RuleOp::Unknown { name, .. }) if name == "whistle.mycompany"));
+    }
+}
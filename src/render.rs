@@ -0,0 +1,177 @@
+//! Render a [`TemplateString`] into its final text.
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::{TemplatePart, TemplateString};
+
+/// Prefix of the `${env.NAME}` namespace.
+pub const ENV_PREFIX: &str = "env.";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenderError {
+    /// `${name}` has no value in the context.
+    Missing(String),
+    /// `${name()}` calls a function that isn't registered.
+    UnknownFunction(String),
+    /// A registered function returned an error.
+    Function { name: String, message: String },
+}
+
+/// A function callable as `${name(args)}`. It gets the raw argument strings
+/// and the context, so it can decide itself whether an argument is a literal
+/// or the name of a value (`${base64(key)}`).
+pub type TemplateFn = Arc<dyn Fn(&[String], &RenderContext) -> Result<String, String> + Send + Sync>;
+
+/// Values available to `${…}` while rendering.
+#[derive(Clone, Default)]
+pub struct RenderContext {
+    pub values: HashMap<String, String>,
+    /// Resolve `${env.NAME}` from the process environment. Off by default so
+    /// rendering a shared rule file never leaks the local environment unless
+    /// asked to.
+    pub env: bool,
+    /// The variables `${env.NAME}` reads instead of the process
+    /// environment, when set.
+    pub env_vars: Option<HashMap<String, String>>,
+    pub functions: HashMap<String, TemplateFn>,
+}
+
+impl fmt::Debug for RenderContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RenderContext")
+            .field("values", &self.values)
+            .field("env", &self.env)
+            .field("env_vars", &self.env_vars)
+            .field("functions", &self.functions.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl RenderContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_env(mut self, env: bool) -> Self {
+        self.env = env;
+        self
+    }
+
+    /// Resolve `${env.NAME}` from `vars` rather than the process
+    /// environment.
+    pub fn with_env_vars<K: Into<String>, V: Into<String>>(mut self, vars: impl IntoIterator<Item = (K, V)>) -> Self {
+        self.env = true;
+        self.env_vars = Some(vars.into_iter().map(|(k, v)| (k.into(), v.into())).collect());
+        self
+    }
+
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) -> &mut Self {
+        self.values.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn register_fn<F>(&mut self, name: impl Into<String>, f: F) -> &mut Self
+    where
+        F: Fn(&[String], &RenderContext) -> Result<String, String> + Send + Sync + 'static,
+    {
+        self.functions.insert(name.into(), Arc::new(f));
+        self
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<Cow<'_, str>> {
+        if let Some(value) = self.values.get(name) {
+            return Some(Cow::Borrowed(value));
+        }
+        match name.strip_prefix(ENV_PREFIX) {
+            Some(var) if self.env => match &self.env_vars {
+                Some(vars) => vars.get(var).map(|value| Cow::Borrowed(value.as_str())),
+                None => std::env::var(var).ok().map(Cow::Owned),
+            },
+            _ => None,
+        }
+    }
+}
+
+impl TemplateString {
+    pub fn render(&self, ctx: &RenderContext) -> Result<String, RenderError> {
+        let mut out = String::new();
+        for part in &self.parts {
+            match part {
+                TemplatePart::RawString(s) => out.push_str(s),
+                TemplatePart::Value(name) => {
+                    let value = ctx
+                        .lookup(name)
+                        .ok_or_else(|| RenderError::Missing(name.clone()))?;
+                    out.push_str(&value);
+                }
+                TemplatePart::Call { name, args } => {
+                    let f = ctx
+                        .functions
+                        .get(name)
+                        .ok_or_else(|| RenderError::UnknownFunction(name.clone()))?;
+                    let value = f(args, ctx).map_err(|message| RenderError::Function {
+                        name: name.clone(),
+                        message,
+                    })?;
+                    out.push_str(&value);
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse_template_string;
+
+    #[test]
+    fn test_render() {
+        let (_, ts) = parse_template_string("/mock/${name}.json").unwrap();
+        let mut ctx = RenderContext::new();
+        ctx.insert("name", "user");
+        assert_eq!(ts.render(&ctx), Ok("/mock/user.json".into()));
+        assert_eq!(
+            ts.render(&RenderContext::new()),
+            Err(RenderError::Missing("name".into()))
+        );
+    }
+
+    #[test]
+    fn test_render_call() {
+        let mut ctx = RenderContext::new();
+        ctx.insert("key", "abc");
+        ctx.register_fn("upper", |args, ctx| {
+            let arg = args.first().ok_or("upper() takes one argument")?;
+            let value = ctx.lookup(arg).unwrap_or(arg.into());
+            Ok(value.to_uppercase())
+        });
+        let (_, ts) = parse_template_string("${upper(key)}-${upper(x)}").unwrap();
+        assert_eq!(ts.render(&ctx), Ok("ABC-X".into()));
+
+        let (_, ts) = parse_template_string("${upper()}").unwrap();
+        assert_eq!(
+            ts.render(&ctx),
+            Err(RenderError::Function {
+                name: "upper".into(),
+                message: "upper() takes one argument".into()
+            })
+        );
+        let (_, ts) = parse_template_string("${uuid()}").unwrap();
+        assert_eq!(ts.render(&ctx), Err(RenderError::UnknownFunction("uuid".into())));
+    }
+
+    #[test]
+    fn test_render_env() {
+        let (_, ts) = parse_template_string("${env.HOME}/mock").unwrap();
+        let vars = [("HOME", "/home/dev")];
+        assert_eq!(ts.render(&RenderContext::new()), Err(RenderError::Missing("env.HOME".into())));
+        let ctx = RenderContext::new().with_env_vars(vars);
+        assert_eq!(ts.render(&ctx), Ok("/home/dev/mock".into()));
+        let ctx = RenderContext::new().with_env_vars([("USER", "dev")]);
+        assert_eq!(ts.render(&ctx), Err(RenderError::Missing("env.HOME".into())));
+    }
+}
@@ -0,0 +1,308 @@
+//! Values of the replacing protocols, `pathReplace://` and `urlReplace://`
+//! for the url, `reqReplace://` and `resReplace://` for bodies.
+//!
+//! Whistle writes them as `from=to` pairs joined by `&`, where `from` is a
+//! string or a `/regexp/flags` literal: `pathReplace://(/v\d+\//i=v2/&a=b)`.
+//! The sed-like `/regexp/replacement/flags` is read too. Inside a regexp and
+//! a sed-like replacement `\/` is a `/` rather than the end of it. Body
+//! replacements usually live in a value, as a json object or one
+//! `from: to` per line, see [`ReplaceOp::parse_body`].
+use std::borrow::Cow;
+
+use crate::json::{parse_json, JsonValue};
+use crate::markdown_values::ValueMap;
+use crate::OpValue;
+
+#[derive(Debug, Clone)]
+pub enum ReplacePattern {
+    /// Replaces its first occurrence, as a string pattern does in
+    /// JavaScript, or every one in a [body](ReplaceOp::parse_body).
+    Text(String),
+    /// `/regexp/flags`. Every match is replaced with the `g` flag, the
+    /// first one otherwise; the replacement can use `$1`, `$&`, `$<name>`
+    /// and `$$` as in JavaScript.
+    #[cfg(feature = "regex")]
+    Regex { regex: regex::Regex, global: bool },
+    /// A regexp this build can't run, invalid or without the `regex`
+    /// feature, as written. It never replaces anything.
+    Unsupported(String),
+}
+
+/// Regexps compare by their source and flags.
+impl PartialEq for ReplacePattern {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (ReplacePattern::Text(a), ReplacePattern::Text(b)) => a == b,
+            #[cfg(feature = "regex")]
+            (ReplacePattern::Regex { regex: a, global: ga }, ReplacePattern::Regex { regex: b, global: gb }) => {
+                a.as_str() == b.as_str() && ga == gb
+            }
+            (ReplacePattern::Unsupported(a), ReplacePattern::Unsupported(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replacement {
+    pub pattern: ReplacePattern,
+    pub replacement: String,
+}
+
+/// The replacements of one operation, applied in order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplaceOp {
+    pub replacements: Vec<Replacement>,
+    /// Whether [`ReplacePattern::Text`] replaces every occurrence, as
+    /// whistle does in bodies, rather than the first.
+    pub every_occurrence: bool,
+}
+
+impl ReplaceOp {
+    /// `None` when a pair has no `=` or there are no pairs at all.
+    pub fn parse(value: &str) -> Option<ReplaceOp> {
+        let mut replacements = vec![];
+        let mut rest = value;
+        while !rest.is_empty() {
+            // `/x=/y` is a string pair, not having a regexp's closing `/`
+            let regexp = rest.strip_prefix('/').and_then(parse_regexp);
+            let (replacement, after) = match regexp {
+                Some(regexp) => regexp,
+                None => {
+                    let (pair, after) = rest.split_once('&').unwrap_or((rest, ""));
+                    let (from, to) = pair.split_once('=')?;
+                    let pattern = ReplacePattern::Text(from.to_string());
+                    (Replacement { pattern, replacement: to.to_string() }, after)
+                }
+            };
+            replacements.push(replacement);
+            rest = after;
+        }
+        (!replacements.is_empty()).then_some(ReplaceOp { replacements, every_occurrence: false })
+    }
+
+    /// The value of `reqReplace://` or `resReplace://`: a json object of
+    /// `from` to `to`, `from: to` lines when there is more than one line,
+    /// or the pairs [`ReplaceOp::parse`] reads. Strings replace every
+    /// occurrence.
+    pub fn parse_body(value: &str) -> Option<ReplaceOp> {
+        let pairs: Vec<(String, String)> = if value.trim_start().starts_with('{') {
+            let JsonValue::Object(members) = parse_json(value).ok()? else {
+                return None;
+            };
+            let text = |v: &JsonValue| v.as_str().map_or_else(|| v.to_string(), str::to_string);
+            members.iter().map(|(k, v)| (k.clone(), text(v))).collect()
+        } else if value.trim().contains('\n') {
+            value
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| {
+                    let (from, to) = line.split_once(':')?;
+                    Some((from.trim().to_string(), to.trim().to_string()))
+                })
+                .collect::<Option<_>>()?
+        } else {
+            return ReplaceOp::parse(value).map(|op| ReplaceOp { every_occurrence: true, ..op });
+        };
+        let replacements: Vec<_> = pairs
+            .into_iter()
+            .map(|(from, replacement)| Replacement { pattern: pattern_of(&from), replacement })
+            .collect();
+        (!replacements.is_empty()).then_some(ReplaceOp { replacements, every_occurrence: true })
+    }
+
+    /// Like [`ReplaceOp::parse_body`] for an operation's value, reading a
+    /// `{key}` from `values`.
+    pub fn resolve_body(value: &OpValue, values: &ValueMap) -> Option<ReplaceOp> {
+        match value {
+            OpValue::Raw(s) | OpValue::Inline(s) => ReplaceOp::parse_body(s),
+            OpValue::Value(key) => ReplaceOp::parse_body(&values.resolve(key).ok()?),
+            OpValue::TemplateString(_) => None,
+        }
+    }
+
+    /// The first regexp, as written, that this build can't run, see
+    /// [`ReplacePattern::Unsupported`].
+    pub fn unsupported_regex(&self) -> Option<&str> {
+        self.replacements.iter().find_map(|r| match &r.pattern {
+            ReplacePattern::Unsupported(regex) => Some(regex.as_str()),
+            _ => None,
+        })
+    }
+
+    /// `text` with every replacement applied, borrowed when none matched.
+    pub fn apply_to<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut out = Cow::Borrowed(text);
+        for Replacement { pattern, replacement } in &self.replacements {
+            let replaced = match pattern {
+                ReplacePattern::Text(from) if !from.is_empty() && out.contains(from.as_str()) => {
+                    out.replacen(from.as_str(), replacement, if self.every_occurrence { usize::MAX } else { 1 })
+                }
+                #[cfg(feature = "regex")]
+                ReplacePattern::Regex { regex, global } => {
+                    let limit = if *global { 0 } else { 1 };
+                    match regex.replacen(&out, limit, regex_replacement(replacement, regex.captures_len()).as_str()) {
+                        Cow::Owned(replaced) => replaced,
+                        Cow::Borrowed(_) => continue,
+                    }
+                }
+                _ => continue,
+            };
+            out = Cow::Owned(replaced);
+        }
+        out
+    }
+}
+
+/// A JavaScript replacement for a regexp of `groups` groups, the whole
+/// match included, in the syntax of the `regex` crate, where `$1rem` would
+/// name a group `1rem`. As in JavaScript `$12` is group 1 and a `2` when
+/// there are fewer than 12 groups, and a `$` starting none of these is
+/// kept as written.
+#[cfg(feature = "regex")]
+fn regex_replacement(replacement: &str, groups: usize) -> String {
+    let mut out = String::new();
+    let mut rest = replacement;
+    while let Some(at) = rest.find('$') {
+        out.push_str(&rest[..at]);
+        rest = &rest[at + 1..];
+        let group = |len: usize| {
+            let digits = rest.get(..len).filter(|d| d.bytes().all(|b| b.is_ascii_digit()))?;
+            digits.parse::<usize>().ok().filter(|n| (1..groups).contains(n)).map(|n| (n, len))
+        };
+        if let Some((n, len)) = group(2).or_else(|| group(1)) {
+            out.push_str(&format!("${{{n}}}"));
+            rest = &rest[len..];
+        } else if let Some(after) = rest.strip_prefix('&') {
+            out.push_str("${0}");
+            rest = after;
+        } else if let Some((name, after)) = rest.strip_prefix('<').and_then(|r| r.split_once('>')) {
+            out.push_str(&format!("${{{name}}}"));
+            rest = after;
+        } else {
+            rest = rest.strip_prefix('$').unwrap_or(rest);
+            out.push_str("$$");
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The end of the `/`-delimited part at the start of `input`, skipping
+/// `\/`, and the part with those escapes undone.
+fn delimited(input: &str) -> Option<(String, usize)> {
+    let mut part = String::new();
+    let mut chars = input.char_indices();
+    while let Some((at, c)) = chars.next() {
+        match c {
+            '/' => return Some((part, at)),
+            '\\' => match chars.next() {
+                Some((_, '/')) => part.push('/'),
+                Some((_, next)) => {
+                    part.push('\\');
+                    part.push(next);
+                }
+                None => part.push('\\'),
+            },
+            c => part.push(c),
+        }
+    }
+    None
+}
+
+/// A regexp pair after its opening `/`, and the input after the pair.
+fn parse_regexp(input: &str) -> Option<(Replacement, &str)> {
+    let (source, end) = delimited(input)?;
+    let rest = &input[end + 1..];
+    let flags_end = rest.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len());
+    let (replacement, flags, after) = match rest[flags_end..].strip_prefix('=') {
+        Some(after_eq) => {
+            let (to, after) = after_eq.split_once('&').unwrap_or((after_eq, ""));
+            (to.to_string(), &rest[..flags_end], after)
+        }
+        // sed-like, `/regexp/replacement/flags`
+        None => {
+            let (to, end) = delimited(rest)?;
+            let tail = &rest[end + 1..];
+            let (flags, after) = tail.split_once('&').unwrap_or((tail, ""));
+            (to, flags, after)
+        }
+    };
+    let pattern = regexp_pattern(&source, flags, &input[..end]);
+    Some((Replacement { pattern, replacement }, after))
+}
+
+/// `from` as a regexp when it is a whole `/regexp/flags` literal.
+fn pattern_of(from: &str) -> ReplacePattern {
+    let literal = from.strip_prefix('/').and_then(|rest| {
+        let (source, end) = delimited(rest)?;
+        let flags = &rest[end + 1..];
+        flags.chars().all(|c| c.is_ascii_alphabetic()).then(|| regexp_pattern(&source, flags, &rest[..end]))
+    });
+    literal.unwrap_or_else(|| ReplacePattern::Text(from.to_string()))
+}
+
+#[cfg(feature = "regex")]
+fn regexp_pattern(source: &str, flags: &str, written: &str) -> ReplacePattern {
+    regex::RegexBuilder::new(source)
+        .case_insensitive(flags.contains('i'))
+        .multi_line(flags.contains('m'))
+        .dot_matches_new_line(flags.contains('s'))
+        .build()
+        .map_or_else(
+            |_| ReplacePattern::Unsupported(written.to_string()),
+            |regex| ReplacePattern::Regex { regex, global: flags.contains('g') },
+        )
+}
+
+#[cfg(not(feature = "regex"))]
+fn regexp_pattern(_: &str, _: &str, written: &str) -> ReplacePattern {
+    ReplacePattern::Unsupported(written.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_text_replace() {
+        let op = ReplaceOp::parse("a=b&/x=/y").unwrap();
+        assert_eq!(op.replacements.len(), 2);
+        assert_eq!(op.apply_to("/a/x/a"), "/b/y/a");
+        assert!(matches!(op.apply_to("/c"), Cow::Borrowed("/c")));
+        assert!(ReplaceOp::parse("no-pair").is_none());
+        assert!(ReplaceOp::parse("/open").is_none());
+    }
+
+    #[test]
+    fn test_body_replace() {
+        let op = ReplaceOp::parse_body(r#"{"a": "b", "n": 1}"#).unwrap();
+        assert_eq!(op.apply_to("a-a-n"), "b-b-1");
+        let op = ReplaceOp::parse_body("a: b\nc: d\n").unwrap();
+        assert_eq!(op.apply_to("ac ac"), "bd bd");
+        assert_eq!(ReplaceOp::parse_body("x=y").unwrap().apply_to("xx"), "yy");
+
+        let values = ValueMap::parse("```r.json\n{\"old\": \"new\"}\n```\n");
+        let op = ReplaceOp::resolve_body(&OpValue::value_ref("r.json"), &values).unwrap();
+        assert!(matches!(op.apply_to("old body"), Cow::Owned(body) if body == "new body"));
+        assert!(ReplaceOp::resolve_body(&OpValue::value_ref("missing"), &values).is_none());
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_regex_replace() {
+        let op = ReplaceOp::parse(r"/v\d+\//i=v2/&/a\/b/c/g").unwrap();
+        let ReplacePattern::Regex { regex, global } = &op.replacements[1].pattern else {
+            panic!("not a regex");
+        };
+        assert_eq!((regex.as_str(), *global), ("a/b", true));
+        assert_eq!(op.apply_to("/api/V1/a/b/a/b"), "/api/v2/c/c");
+        let op = ReplaceOp::parse("/(\\w+)-(\\w+)/$2-$1/").unwrap();
+        assert_eq!(op.apply_to("/ab-cd"), "/cd-ab");
+        assert!(matches!(ReplaceOp::parse("/(/x/").unwrap().replacements[0].pattern, ReplacePattern::Unsupported(_)));
+        let op = ReplaceOp::parse_body(r#"{"/(\\d+)px/g": "$1rem", "/a\\/b/": "c"}"#).unwrap();
+        assert_eq!(op.apply_to("1px 2px a/b"), "1rem 2rem c");
+        let op = ReplaceOp::parse(r"/(?<v>v\d)(\d)?/[$&|$<v>|$12|$$1|$3]/").unwrap();
+        assert_eq!(op.apply_to("/v1/x"), "/[v1|v1|v12|$1|$3]/x");
+    }
+}
@@ -0,0 +1,201 @@
+//! Async resolution of `{key}` values and remote includes.
+//!
+//! The trait only relies on `std::future`, so any backend (HTTP, consul,
+//! etcd…) can implement it on top of whatever client it already uses.
+//! `tokio` is only needed for the timeout in [`CachingResolver`].
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::{OpValue, Rule};
+
+pub type ResolveFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<Option<String>, ResolveError>> + Send + 'a>>;
+
+/// What needs to be looked up remotely.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ValueSource {
+    /// A `{key}` value reference.
+    Key(String),
+    /// A remote include, identified by its url.
+    Include(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResolveError {
+    Timeout(ValueSource),
+    Missing(ValueSource),
+    Backend(String),
+}
+
+pub trait AsyncValueResolver: Send + Sync {
+    /// Resolve `source`, `Ok(None)` means the backend has no such entry.
+    fn resolve<'a>(&'a self, source: &'a ValueSource) -> ResolveFuture<'a>;
+}
+
+/// What to do when the backend doesn't answer in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnTimeout {
+    /// Return [`ResolveError::Timeout`].
+    Fail,
+    /// Fall back to an expired cache entry when there is one.
+    UseStale,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeoutPolicy {
+    pub timeout: Duration,
+    pub on_timeout: OnTimeout,
+}
+
+impl Default for TimeoutPolicy {
+    fn default() -> Self {
+        TimeoutPolicy {
+            timeout: Duration::from_secs(5),
+            on_timeout: OnTimeout::UseStale,
+        }
+    }
+}
+
+/// Wraps a resolver with a ttl cache and a timeout policy.
+pub struct CachingResolver<R> {
+    inner: R,
+    ttl: Duration,
+    policy: TimeoutPolicy,
+    cache: Mutex<HashMap<ValueSource, (Instant, String)>>,
+}
+
+impl<R: AsyncValueResolver> CachingResolver<R> {
+    pub fn new(inner: R, ttl: Duration, policy: TimeoutPolicy) -> Self {
+        CachingResolver {
+            inner,
+            ttl,
+            policy,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn clear(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+
+    fn cached(&self, source: &ValueSource, allow_stale: bool) -> Option<String> {
+        let cache = self.cache.lock().unwrap();
+        cache
+            .get(source)
+            .filter(|(at, _)| allow_stale || at.elapsed() < self.ttl)
+            .map(|(_, value)| value.clone())
+    }
+}
+
+impl<R: AsyncValueResolver> AsyncValueResolver for CachingResolver<R> {
+    fn resolve<'a>(&'a self, source: &'a ValueSource) -> ResolveFuture<'a> {
+        Box::pin(async move {
+            if let Some(value) = self.cached(source, false) {
+                return Ok(Some(value));
+            }
+            match tokio::time::timeout(self.policy.timeout, self.inner.resolve(source)).await {
+                Ok(Ok(Some(value))) => {
+                    self.cache
+                        .lock()
+                        .unwrap()
+                        .insert(source.clone(), (Instant::now(), value.clone()));
+                    Ok(Some(value))
+                }
+                Ok(result) => result,
+                Err(_) => match self.policy.on_timeout {
+                    OnTimeout::UseStale => self
+                        .cached(source, true)
+                        .map(Some)
+                        .ok_or_else(|| ResolveError::Timeout(source.clone())),
+                    OnTimeout::Fail => Err(ResolveError::Timeout(source.clone())),
+                },
+            }
+        })
+    }
+}
+
+/// Resolve every `{key}` used by `rules`, keyed by the value name.
+pub async fn resolve_rule_values<R: AsyncValueResolver + ?Sized>(
+    rules: &[Rule],
+    resolver: &R,
+) -> Result<HashMap<String, String>, ResolveError> {
+    let mut values = HashMap::new();
+    for rule in rules {
+        if let OpValue::Value(key) = &rule.value {
+            if values.contains_key(key) {
+                continue;
+            }
+            let source = ValueSource::Key(key.clone());
+            match resolver.resolve(&source).await? {
+                Some(value) => values.insert(key.clone(), value),
+                None => return Err(ResolveError::Missing(source)),
+            };
+        }
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Counting(AtomicUsize);
+
+    impl AsyncValueResolver for Counting {
+        fn resolve<'a>(&'a self, source: &'a ValueSource) -> ResolveFuture<'a> {
+            Box::pin(async move {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                Ok(match source {
+                    ValueSource::Key(k) if k == "res" => Some("ok".to_string()),
+                    _ => None,
+                })
+            })
+        }
+    }
+
+    struct Slow;
+
+    impl AsyncValueResolver for Slow {
+        fn resolve<'a>(&'a self, _: &'a ValueSource) -> ResolveFuture<'a> {
+            Box::pin(async {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                Ok(None)
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_caching_resolver() {
+        let resolver = CachingResolver::new(
+            Counting(AtomicUsize::new(0)),
+            Duration::from_secs(60),
+            TimeoutPolicy::default(),
+        );
+        let rules = crate::get_rules(" file://{res} resHeaders://{res}").unwrap().1;
+        let values = resolve_rule_values(&rules, &resolver).await.unwrap();
+        assert_eq!(values.get("res"), Some(&"ok".to_string()));
+        resolve_rule_values(&rules, &resolver).await.unwrap();
+        assert_eq!(resolver.inner.0.load(Ordering::SeqCst), 1);
+
+        let missing = ValueSource::Key("nope".into());
+        assert_eq!(resolver.resolve(&missing).await, Ok(None));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_timeout_policy() {
+        let policy = TimeoutPolicy {
+            timeout: Duration::from_millis(10),
+            on_timeout: OnTimeout::Fail,
+        };
+        let resolver = CachingResolver::new(Slow, Duration::from_secs(60), policy);
+        let key = ValueSource::Key("res".into());
+        assert_eq!(
+            resolver.resolve(&key).await,
+            Err(ResolveError::Timeout(key.clone()))
+        );
+    }
+}
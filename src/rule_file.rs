@@ -0,0 +1,857 @@
+//! Parsing a whole rules document, line by line.
+use std::collections::HashSet;
+
+use crate::{get_part, parse_proxy_rule_complete, parse_uri, ParserOptions, ProxyRule, Span, Uri, WhistleParseError};
+use crate::edit::TextEdit;
+use crate::line_index::{LineIndex, Position};
+use crate::pattern::Pattern;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LineItem {
+    Rule(ProxyRule),
+    /// A line with a pattern but no operation yet, usually one being edited.
+    PatternOnly(Pattern),
+    /// A rule switched off with a leading [`DISABLED_MARKER`].
+    Disabled(ProxyRule),
+    /// `# text`, holding the text after `#`.
+    Comment(String),
+    Blank,
+    /// `@url`, rules to load from another file or url.
+    Include(Uri),
+    /// A value defined in the rules themselves, from a line with ```` ```key ````
+    /// to one with ```` ``` ````. Its [`Line`] spans the whole block; the
+    /// body has `\n` line breaks.
+    ValueBlock { key: String, body: String },
+    Invalid { text: String, error: WhistleParseError },
+}
+
+/// One line of the document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    /// Zero-based line number, of the first line for a value block.
+    pub number: usize,
+    /// Where the line is in the document, without its line break.
+    pub span: Span,
+    pub item: LineItem,
+}
+
+/// Marks a rule that is switched off but kept in the file, `#off a.com b.com`.
+///
+/// Plain comments are never read as disabled rules, as prose like
+/// `# see docs` parses as one; a commented-out rule can still be switched on
+/// with [`RuleFile::set_enabled`].
+pub const DISABLED_MARKER: &str = "#off";
+
+pub fn parse_lines(input: &str) -> Vec<Line> {
+    lines_of(input, &ParserOptions::default())
+}
+
+/// Parse every line of `input`. Errors in a line don't stop the parse, the
+/// line becomes [`LineItem::Invalid`] with spans relative to the document;
+/// only an input over [`Limits::max_input_size`](crate::Limits) is rejected
+/// as a whole.
+pub fn parse_lines_with(input: &str, options: &ParserOptions) -> Result<Vec<Line>, WhistleParseError> {
+    options.limits.check_input_size(input)?;
+    Ok(lines_of(input, options))
+}
+
+/// Parse untrusted bytes. Never panics: invalid UTF-8 and exceeded limits
+/// are errors, everything else ends up in the returned lines.
+pub fn parse_any_bytes(bytes: &[u8], options: &ParserOptions) -> Result<Vec<Line>, WhistleParseError> {
+    let input = std::str::from_utf8(bytes).map_err(|e| {
+        let start = e.valid_up_to();
+        let len = e.error_len().unwrap_or(bytes.len() - start);
+        WhistleParseError::InvalidUtf8 { span: Span::new(start, start + len) }
+    })?;
+    parse_lines_with(input, options)
+}
+
+/// Opens and closes a [`LineItem::ValueBlock`]. A longer run of backticks
+/// also opens a block, which the same run closes.
+pub const VALUE_FENCE: &str = "```";
+
+/// The shortest fence that can hold `body`: one backtick longer than any
+/// run inside it, and never shorter than [`VALUE_FENCE`].
+pub fn value_fence(body: &str) -> String {
+    let longest = body.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    "`".repeat(longest.max(VALUE_FENCE.len() - 1) + 1)
+}
+
+/// The fence opening `line` and the info string after it.
+pub(crate) fn split_fence(line: &str) -> Option<(&str, &str)> {
+    let run = line.len() - line.trim_start_matches('`').len();
+    (run >= VALUE_FENCE.len()).then(|| line.split_at(run))
+}
+
+fn lines_of(input: &str, options: &ParserOptions) -> Vec<Line> {
+    let mut lines = vec![];
+    each_line(input, options, |line| lines.push(line));
+    lines
+}
+
+/// Read `input` one [`Line`] at a time, without keeping them.
+pub(crate) fn each_line(input: &str, options: &ParserOptions, mut f: impl FnMut(Line)) {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("parse_file", bytes = input.len()).entered();
+    let mut split = lines_with_offsets(input).enumerate();
+    while let Some((number, (offset, text))) = split.next() {
+        // the key is the first word of the info string, attributes follow it
+        let fence = split_fence(text.trim()).and_then(|(fence, info)| Some((fence, info.split_whitespace().next()?)));
+        let Some((fence, key)) = fence else {
+            let span = Span::new(offset, offset + text.len());
+            let item = parse_line(text, offset, options);
+            #[cfg(feature = "tracing")]
+            if let LineItem::Invalid { error, .. } = &item {
+                tracing::warn!(line = number, %error, "invalid rule line");
+            }
+            f(Line { number, span, item });
+            continue;
+        };
+        // an unclosed block runs to the end of the document
+        let mut body = vec![];
+        let mut end = offset + text.len();
+        for (_, (at, line)) in split.by_ref() {
+            end = at + line.len();
+            if line.trim() == fence {
+                break;
+            }
+            body.push(line);
+        }
+        f(Line {
+            number,
+            span: Span::new(offset, end),
+            item: LineItem::ValueBlock { key: key.to_string(), body: body.join("\n") },
+        });
+    }
+}
+
+/// Split `input` at `\n`, `\r\n` and `\r`, giving each line's offset and
+/// its text without the terminator. A leading UTF-8 BOM is skipped.
+pub fn split_lines(input: &str) -> Vec<(usize, &str)> {
+    lines_with_offsets(input).collect()
+}
+
+/// The lines of [`split_lines`], found as they are read.
+fn lines_with_offsets(input: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut start = if input.starts_with('\u{feff}') { '\u{feff}'.len_utf8() } else { 0 };
+    let mut done = false;
+    std::iter::from_fn(move || {
+        if done {
+            return None;
+        }
+        let line_start = start;
+        let bytes = input.as_bytes();
+        match bytes[start..].iter().position(|b| matches!(b, b'\n' | b'\r')) {
+            Some(len) => {
+                let end = start + len;
+                start = end + 1;
+                if bytes[end] == b'\r' && bytes.get(end + 1) == Some(&b'\n') {
+                    start += 1;
+                }
+                Some((line_start, &input[line_start..end]))
+            }
+            None => {
+                done = true;
+                Some((line_start, &input[line_start..]))
+            }
+        }
+    })
+}
+
+fn parse_line(original: &str, offset: usize, options: &ParserOptions) -> LineItem {
+    if let Err(error) = options.limits.check_line_length(original) {
+        return LineItem::Invalid { text: original.to_string(), error: error.shifted(offset) };
+    }
+    let text = &options.normalize_separators(original);
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        return LineItem::Blank;
+    }
+    if let Some(rule) = disabled_rule(trimmed, options) {
+        return LineItem::Disabled(rule);
+    }
+    if let Some(comment) = trimmed.strip_prefix('#') {
+        return LineItem::Comment(comment.trim().to_string());
+    }
+    let text = split_comment(text).0;
+    let trimmed = text.trim();
+    let invalid = |error: WhistleParseError| LineItem::Invalid {
+        text: original.to_string(),
+        error: error.shifted(offset),
+    };
+
+    let single_token = matches!(get_part(text), Ok((rest, _)) if rest.trim().is_empty());
+    if let Some(include) = trimmed.strip_prefix('@').filter(|_| single_token) {
+        let at = text.len() - text.trim_start().len() + 1;
+        if include.is_empty() {
+            // a lone `@` includes nothing
+            return invalid(WhistleParseError::Syntax { span: Span::new(at, at), kind: nom::error::ErrorKind::Eof });
+        }
+        return match nom::combinator::all_consuming(parse_uri)(include) {
+            Ok((_, uri)) => LineItem::Include(uri),
+            Err(error) => invalid(WhistleParseError::from_nom(include, error).shifted(at)),
+        };
+    }
+    if single_token {
+        return match Pattern::parse(trimmed) {
+            Ok(pattern) => LineItem::PatternOnly(pattern),
+            Err(error) => invalid(error.shifted(text.len() - text.trim_start().len())),
+        };
+    }
+    match parse_proxy_rule_complete(text, options) {
+        Ok(rule) => LineItem::Rule(rule),
+        Err(error) => invalid(error),
+    }
+}
+
+/// Name of the rules before the first named group, whistle's own first tab.
+pub const DEFAULT_GROUP: &str = "Default";
+
+/// Split an export of several rule tabs into `(name, rules)` in document
+/// order. A group starts with a comment line at the start of the text or
+/// after a blank line, the comment being its name; the group runs until the
+/// next one, so blank lines inside a tab are kept. Rules before the first
+/// group are in [`DEFAULT_GROUP`].
+pub fn parse_grouped(text: &str) -> Vec<(String, RuleFile)> {
+    let lines = split_lines(text);
+    // the line of each group's name, none for the default group
+    let mut starts: Vec<(Option<usize>, String)> = vec![];
+    let mut after_blank = true;
+    for (index, (_, line)) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        match trimmed.strip_prefix('#') {
+            Some(name) if after_blank && !trimmed.starts_with(DISABLED_MARKER) => {
+                starts.push((Some(index), name.trim().to_string()))
+            }
+            _ => {}
+        }
+        after_blank = trimmed.is_empty();
+    }
+    let first_group = starts.first().and_then(|(index, _)| *index).unwrap_or(lines.len());
+    let has_default = lines[..first_group].iter().any(|(_, line)| !line.trim().is_empty());
+    if has_default || starts.is_empty() {
+        starts.insert(0, (None, DEFAULT_GROUP.to_string()));
+    }
+    let mut groups = vec![];
+    for (at, (start, name)) in starts.iter().enumerate() {
+        let first = start.map_or(0, |index| index + 1);
+        let end = starts.get(at + 1).and_then(|(next, _)| *next).unwrap_or(lines.len());
+        let body: Vec<&(usize, &str)> = lines[first..end]
+            .iter()
+            .skip_while(|(_, line)| line.trim().is_empty())
+            .collect();
+        let last = body.iter().rposition(|(_, line)| !line.trim().is_empty());
+        let text = match (body.first(), last) {
+            (Some((from, _)), Some(last)) => {
+                let (to, line) = body[last];
+                &text[*from..to + line.len()]
+            }
+            _ => "",
+        };
+        groups.push((name.clone(), RuleFile::parse(text)));
+    }
+    groups
+}
+
+/// The groups as one text that [`parse_grouped`] reads back, each under a
+/// `# name` line and separated by a blank line.
+pub fn to_grouped_text(groups: &[(String, RuleFile)]) -> String {
+    let sections: Vec<String> = groups
+        .iter()
+        .map(|(name, file)| format!("# {name}\n{}", file.text()))
+        .collect();
+    sections.join("\n\n") + "\n"
+}
+
+fn disabled_rule(trimmed: &str, options: &ParserOptions) -> Option<ProxyRule> {
+    let rest = trimmed.strip_prefix(DISABLED_MARKER)?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    parse_proxy_rule_complete(split_comment(rest).0, options).ok()
+}
+
+/// `text` without its trailing comment, which starts at a token after the
+/// first one beginning with `#`, and the comment after that `#`.
+fn split_comment(text: &str) -> (&str, Option<&str>) {
+    let comment = crate::tokens(text).into_iter().skip(1).find(|(_, token)| token.starts_with('#'));
+    match comment {
+        Some((at, _)) => (text[..at].trim_end(), Some(text[at + 1..].trim())),
+        None => (text, None),
+    }
+}
+
+/// Identifies a rule of a [`RuleFile`] by its zero-based line number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RuleId(pub usize);
+
+/// Order for [`RuleFile::sort_by`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    /// The host of the pattern.
+    Host,
+    /// The protocol of the target, host rules like `a.com 127.0.0.1` first.
+    Protocol,
+    /// Document order.
+    Original,
+}
+
+/// A rules document that keeps its text, so edits rewrite single lines and
+/// leave comments, spacing and line endings everywhere else untouched.
+#[derive(Debug, Clone)]
+pub struct RuleFile {
+    text: String,
+    lines: Vec<Line>,
+    index: LineIndex,
+    options: ParserOptions,
+}
+
+impl RuleFile {
+    pub fn parse(text: impl Into<String>) -> RuleFile {
+        let text = text.into();
+        let options = ParserOptions::default();
+        RuleFile::from_parts(lines_of(&text, &options), text, options)
+    }
+
+    pub fn parse_with(text: impl Into<String>, options: ParserOptions) -> Result<RuleFile, WhistleParseError> {
+        let text = text.into();
+        let lines = parse_lines_with(&text, &options)?;
+        Ok(RuleFile::from_parts(lines, text, options))
+    }
+
+    /// The file parsed by `rules!` into `lines`, which must be those of
+    /// `text` read with the default options.
+    #[doc(hidden)]
+    pub fn __from_lines(text: &str, lines: Vec<Line>) -> RuleFile {
+        RuleFile::from_parts(lines, text.to_string(), ParserOptions::default())
+    }
+
+    fn from_parts(lines: Vec<Line>, text: String, options: ParserOptions) -> RuleFile {
+        RuleFile { index: LineIndex::new(&text), text, lines, options }
+    }
+
+    /// The entry for line `id`, which a value block has only for its first
+    /// line.
+    fn line(&self, id: RuleId) -> Option<&Line> {
+        self.position(id.0).map(|at| &self.lines[at])
+    }
+
+    fn position(&self, number: usize) -> Option<usize> {
+        self.lines.binary_search_by_key(&number, |line| line.number).ok()
+    }
+
+    pub fn line_index(&self) -> &LineIndex {
+        &self.index
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn options(&self) -> &ParserOptions {
+        &self.options
+    }
+
+    pub fn lines(&self) -> &[Line] {
+        &self.lines
+    }
+
+    /// Where the rule at `id` is written, without the indentation, a
+    /// [`DISABLED_MARKER`] or a trailing comment.
+    pub(crate) fn rule_span(&self, id: RuleId) -> Option<Span> {
+        let line = self.line(id)?;
+        let mut text = self.text[line.span.start..line.span.end].trim();
+        match line.item {
+            LineItem::Rule(_) => {}
+            LineItem::Disabled(_) => text = text[DISABLED_MARKER.len()..].trim_start(),
+            _ => return None,
+        }
+        let text = split_comment(text).0;
+        let start = crate::error::offset_in(&self.text, text);
+        Some(Span::new(start, start + text.len()))
+    }
+
+    /// The trailing comment of the rule at `id`, `a.com 1.1.1.1 # note`,
+    /// without its `#`.
+    pub fn comment(&self, id: RuleId) -> Option<&str> {
+        let span = self.rule_span(id)?;
+        let line = self.line(id)?;
+        split_comment(&self.text[span.start..line.span.end]).1
+    }
+
+    /// Every rule with whether it is enabled, in document order.
+    pub fn rules(&self) -> impl Iterator<Item = (RuleId, &ProxyRule, bool)> {
+        self.lines.iter().filter_map(|line| match &line.item {
+            LineItem::Rule(rule) => Some((RuleId(line.number), rule, true)),
+            LineItem::Disabled(rule) => Some((RuleId(line.number), rule, false)),
+            _ => None,
+        })
+    }
+
+    /// Switch the rule at `id` on or off, rewriting only its line: disabling
+    /// puts [`DISABLED_MARKER`] after the indentation, enabling removes it or
+    /// the `#` of a commented-out rule. Returns `false` when there is no rule
+    /// at `id`; the line count never changes, so ids stay valid.
+    pub fn set_enabled(&mut self, id: RuleId, enabled: bool) -> bool {
+        let Some(line) = self.line(id) else {
+            return false;
+        };
+        let text = &self.text[line.span.start..line.span.end];
+        let body = text.trim_start();
+        let indent = &text[..text.len() - body.len()];
+        let rewritten = match (&line.item, enabled) {
+            (LineItem::Rule(_), true) | (LineItem::Disabled(_), false) => return true,
+            (LineItem::Rule(_), false) => format!("{indent}{DISABLED_MARKER} {body}"),
+            (LineItem::Disabled(_), true) => {
+                let rule = &body[DISABLED_MARKER.len()..];
+                let rule = rule.strip_prefix(char::is_whitespace).unwrap_or(rule);
+                format!("{indent}{rule}")
+            }
+            (LineItem::Comment(_), true) => {
+                let rule = body.strip_prefix('#').unwrap_or(body).trim_start();
+                let rewritten = format!("{indent}{rule}");
+                if !matches!(parse_line(&rewritten, 0, &self.options), LineItem::Rule(_)) {
+                    return false;
+                }
+                rewritten
+            }
+            _ => return false,
+        };
+        let span = line.span;
+        let mut text = std::mem::take(&mut self.text);
+        text.replace_range(span.start..span.end, &rewritten);
+        *self = RuleFile::from_parts(lines_of(&text, &self.options), text, self.options.clone());
+        true
+    }
+
+    /// Change the rule at `id` with `f` and rewrite its text. Only a rule
+    /// that `f` changed is rewritten, the rest of the document keeps its
+    /// formatting. Returns the edit made to the old text, `None` when there
+    /// is no rule at `id` or nothing changed.
+    pub fn update_rule(&mut self, id: RuleId, f: impl FnOnce(&mut ProxyRule)) -> Option<TextEdit> {
+        let line = self.line(id)?;
+        let (LineItem::Rule(rule) | LineItem::Disabled(rule)) = &line.item else {
+            return None;
+        };
+        let mut updated = rule.clone();
+        f(&mut updated);
+        // `==` ignores the case of hosts, which an edit may be changing
+        if updated.to_string() == rule.to_string() {
+            return None;
+        }
+        let edit = TextEdit::new(self.rule_span(id)?, updated.to_string());
+        let text = crate::edit::apply_edits(&self.text, std::slice::from_ref(&edit));
+        *self = RuleFile::from_parts(lines_of(&text, &self.options), text, self.options.clone());
+        Some(edit)
+    }
+
+    /// A copy without rules that repeat an earlier rule with the same
+    /// enabled state. Everything else is kept as written.
+    pub fn dedupe(&self) -> RuleFile {
+        let mut seen: HashSet<(&ProxyRule, bool)> = HashSet::new();
+        let keep = self.lines.iter().filter(|line| match &line.item {
+            LineItem::Rule(rule) => seen.insert((rule, true)),
+            LineItem::Disabled(rule) => seen.insert((rule, false)),
+            _ => true,
+        });
+        self.rebuild(keep.filter_map(|line| self.position(line.number)).collect())
+    }
+
+    /// A copy with each run of consecutive rules sorted by `key`. Comments
+    /// and blank lines stay where they are, so sections of a file keep their
+    /// rules; the sort is stable.
+    pub fn sort_by(&self, key: SortKey) -> RuleFile {
+        let sort_key = |line: &Line| match (&line.item, key) {
+            (LineItem::Rule(rule) | LineItem::Disabled(rule), SortKey::Host) => rule.source.host.clone(),
+            (LineItem::Rule(rule) | LineItem::Disabled(rule), SortKey::Protocol) => rule.target.scheme.clone(),
+            _ => String::new(),
+        };
+        let is_rule = |line: &Line| matches!(line.item, LineItem::Rule(_) | LineItem::Disabled(_));
+        let mut order = vec![];
+        for run in self.lines.chunk_by(|a, b| is_rule(a) == is_rule(b)) {
+            let mut run: Vec<&Line> = run.iter().collect();
+            if is_rule(run[0]) && key != SortKey::Original {
+                run.sort_by_cached_key(|line| sort_key(line));
+            }
+            order.extend(run.iter().filter_map(|line| self.position(line.number)));
+        }
+        self.rebuild(order)
+    }
+
+    /// A file of the lines at `order`, positions in [`RuleFile::lines`]. Each position keeps the line break it
+    /// had and the last one keeps the file's ending, so moved lines don't
+    /// change how the file ends.
+    fn rebuild(&self, order: Vec<usize>) -> RuleFile {
+        let start = self.lines.first().map_or(0, |line| line.span.start);
+        let mut text = self.text[..start].to_string();
+        for (position, &at) in order.iter().enumerate() {
+            let span = self.lines[at].span;
+            text.push_str(&self.text[span.start..span.end]);
+            let ending = if position + 1 == order.len() { self.lines.len() - 1 } else { position };
+            let end = self.lines.get(ending + 1).map_or(self.text.len(), |next| next.span.start);
+            text.push_str(&self.text[self.lines[ending].span.end..end]);
+        }
+        RuleFile::from_parts(lines_of(&text, &self.options), text, self.options.clone())
+    }
+
+    /// The pattern, protocol names and values of the rule at `id`, disabled
+    /// rules included.
+    pub fn nodes(&self, id: RuleId) -> Vec<Node> {
+        let Some(span) = self.rule_span(id) else {
+            return vec![];
+        };
+        let text = &self.text[span.start..span.end];
+        let mut nodes = vec![];
+        let mut add = |kind, start: usize, len: usize| {
+            let span = Span::new(start, start + len).shifted(span.start);
+            nodes.push(Node { rule: id, kind, span });
+        };
+        for (index, (start, token)) in crate::tokens(text).into_iter().enumerate() {
+            if index == 0 {
+                add(NodeKind::Pattern, start, token.len());
+                continue;
+            }
+            match token.split_once("://") {
+                Some((name, value)) if !name.is_empty() && name.chars().all(crate::is_protocol_char) => {
+                    add(NodeKind::Protocol, start, name.len());
+                    if !value.is_empty() {
+                        add(NodeKind::Value, start + name.len() + 3, value.len());
+                    }
+                }
+                _ => add(NodeKind::Value, start, token.len()),
+            }
+        }
+        nodes
+    }
+
+    /// The token of each of [`ProxyRule::operations`] of the rule at `id`,
+    /// the target first.
+    pub fn op_spans(&self, id: RuleId) -> Vec<Span> {
+        let Some(span) = self.rule_span(id) else {
+            return vec![];
+        };
+        // the pattern comes before the operations
+        crate::tokens(&self.text[span.start..span.end])
+            .iter()
+            .skip(1)
+            .map(|(at, token)| Span::new(*at, at + token.len()).shifted(span.start))
+            .collect()
+    }
+
+    /// The node under the cursor at `offset`. A cursor right after a node,
+    /// as at the end of a word being typed, counts as on it.
+    pub fn node_at_position(&self, offset: usize) -> Option<Node> {
+        let line = self.index.offset_to_position(offset).line;
+        self.nodes(RuleId(line))
+            .into_iter()
+            .find(|node| node.span.start <= offset && offset <= node.span.end)
+    }
+
+    /// The edits replacing host `old` with `new` in patterns, targets and
+    /// the values of [routing operations](crate::rule_set::ROUTING_PROTOCOLS),
+    /// disabled rules included.
+    pub fn rename_host(&self, old: &str, new: &str) -> Vec<TextEdit> {
+        let mut edits = vec![];
+        for line in &self.lines {
+            let mut protocol = None;
+            for node in self.nodes(RuleId(line.number)) {
+                let text = &self.text[node.span.start..node.span.end];
+                if node.kind == NodeKind::Protocol {
+                    protocol = Some(text);
+                    continue;
+                }
+                let routed = match protocol.take() {
+                    Some(protocol) => crate::rule_set::ROUTING_PROTOCOLS.contains(&protocol),
+                    None => true,
+                };
+                if !routed {
+                    continue;
+                }
+                let Ok((_, uri)) = crate::parse_uri(text) else {
+                    continue;
+                };
+                if uri.host != old {
+                    continue;
+                }
+                let at = if uri.scheme.is_empty() { 0 } else { uri.scheme.len() + 3 };
+                let start = node.span.start + at;
+                edits.push(TextEdit::new(Span::new(start, start + old.len()), new));
+            }
+        }
+        edits
+    }
+
+    pub fn offset_to_position(&self, offset: usize) -> Position {
+        self.index.offset_to_position(offset)
+    }
+
+    pub fn position_to_offset(&self, position: Position) -> Option<usize> {
+        self.index.position_to_offset(position)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    Pattern,
+    /// The `name` of `name://value`.
+    Protocol,
+    /// The `value` of `name://value`, or a target without a scheme.
+    Value,
+}
+
+/// A part of a rule line, with where it is in the document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Node {
+    pub rule: RuleId,
+    pub kind: NodeKind,
+    pub span: Span,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Limit, Limits};
+
+    #[test]
+    fn test_parse_lines() {
+        let input = "# mocks\n\nwww.a.com file:///mock\n  www.b.com  \nwww.c.com b.com oops";
+        let lines = parse_lines(input);
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0].item, LineItem::Comment("mocks".into()));
+        assert_eq!(lines[1].item, LineItem::Blank);
+        assert!(matches!(&lines[2].item, LineItem::Rule(rule) if rule.target.path == "/mock"));
+        assert_eq!(lines[2].span, Span::new(9, 31));
+        match &lines[3].item {
+            LineItem::PatternOnly(pattern) => assert_eq!(pattern.raw, "www.b.com"),
+            item => panic!("expected a pattern, got {item:?}"),
+        }
+        assert_eq!(
+            lines[4].item,
+            LineItem::Invalid {
+                text: "www.c.com b.com oops".into(),
+                error: WhistleParseError::TrailingInput {
+                    text: "oops".into(),
+                    span: Span::new(62, 66)
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn test_limits() {
+        let options = ParserOptions { limits: Limits::untrusted(), ..Default::default() };
+        let huge = "a.com b.com\n".repeat(100_000);
+        assert!(matches!(
+            parse_lines_with(&huge, &options),
+            Err(WhistleParseError::LimitExceeded { limit: Limit::InputSize, max: 1048576, .. })
+        ));
+
+        let long_line = format!("a.com b.com\nc.com file://{}", "x".repeat(9000));
+        let lines = parse_lines_with(&long_line, &options).unwrap();
+        assert!(matches!(lines[0].item, LineItem::Rule(_)));
+        assert_eq!(
+            lines[1].item,
+            LineItem::Invalid {
+                text: long_line[12..].to_string(),
+                error: WhistleParseError::LimitExceeded {
+                    limit: Limit::LineLength,
+                    max: 8192,
+                    span: Span::new(12 + 8192, long_line.len()),
+                },
+            }
+        );
+
+        let template = format!("a.com b.com file://`{}`", "${x}-".repeat(40));
+        assert!(matches!(
+            crate::parse_proxy_rule_with(&template, &options),
+            Err(WhistleParseError::LimitExceeded { limit: Limit::TemplateParts, .. })
+        ));
+        assert!(crate::parse_proxy_rule_with(&template, &ParserOptions::default()).is_ok());
+        assert!(Limits::untrusted().check_include_depth(5, Span::default()).is_err());
+    }
+
+    #[test]
+    fn test_parse_any_bytes() {
+        assert_eq!(
+            parse_any_bytes(b"a.com b.com\n\xff\xfe", &ParserOptions::default()),
+            Err(WhistleParseError::InvalidUtf8 { span: Span::new(12, 13) })
+        );
+
+        // random soups of syntax fragments must never panic
+        let fragments = [
+            "a.com", " ", "\t", "\n", "\r", "file://", "`", "${", "}", "(", ")", "{", "\"", "\\",
+            "#", "://", "?", "/", "é", "\u{3000}", "\u{200b}", "\u{feff}", "x", "```", ":",
+        ];
+        let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed as usize
+        };
+        for _ in 0..2000 {
+            let len = next() % 40;
+            let input: String = (0..len).map(|_| fragments[next() % fragments.len()]).collect();
+            let lines = parse_any_bytes(input.as_bytes(), &ParserOptions::strict()).unwrap();
+            // value blocks take several lines
+            assert!(lines.len() <= split_lines(&input).len());
+            assert!(lines.windows(2).all(|pair| pair[0].number < pair[1].number));
+            #[cfg(feature = "convert")]
+            {
+                crate::markdown_values::ValueMap::parse(&input);
+                let _ = crate::json::parse_json(&input);
+            }
+        }
+    }
+
+    #[test]
+    fn test_bom_and_line_endings() {
+        let input = "\u{feff}# c\r\na.com b.com\rc.com d.com\r\n";
+        let lines = parse_lines(input);
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0].item, LineItem::Comment("c".into()));
+        assert_eq!(lines[0].span, Span::new(3, 6));
+        match &lines[1].item {
+            LineItem::Rule(rule) => assert_eq!(rule.target.host, "b.com"),
+            item => panic!("expected a rule, got {item:?}"),
+        }
+        assert_eq!(lines[2].span, Span::new(20, 31));
+        assert_eq!(lines[3].item, LineItem::Blank);
+    }
+
+    #[test]
+    fn test_set_enabled() {
+        let input = "# mocks\r\n  a.com b.com\r\n#off c.com d.com\r\n# e.com f.com\r\n# see docs here";
+        let mut file = RuleFile::parse(input);
+        let states: Vec<_> = file.rules().map(|(id, _, enabled)| (id, enabled)).collect();
+        assert_eq!(states, vec![(RuleId(1), true), (RuleId(2), false)]);
+
+        assert!(file.set_enabled(RuleId(1), false));
+        assert!(file.set_enabled(RuleId(2), true));
+        assert!(file.set_enabled(RuleId(3), true));
+        assert!(!file.set_enabled(RuleId(4), true));
+        assert!(!file.set_enabled(RuleId(0), true));
+        assert!(!file.set_enabled(RuleId(9), true));
+        assert_eq!(
+            file.text(),
+            "# mocks\r\n  #off a.com b.com\r\nc.com d.com\r\ne.com f.com\r\n# see docs here"
+        );
+        assert!(matches!(&file.lines()[1].item, LineItem::Disabled(rule) if rule.source.host == "a.com"));
+
+        assert!(file.set_enabled(RuleId(1), true));
+        assert!(file.text().starts_with("# mocks\r\n  a.com b.com\r\n"));
+    }
+
+    #[test]
+    fn test_update_rule() {
+        let mut file = RuleFile::parse("# x\n  a.com   file://x  log://1\n#off b.com file://y\n");
+        let edit = file.update_rule(RuleId(1), |rule| {
+            rule.remove_ops_by_name("log");
+        });
+        assert_eq!(edit, Some(TextEdit::new(Span::new(6, 31), "a.com file://x")));
+        assert!(file.update_rule(RuleId(1), |_| {}).is_none());
+        assert!(file.update_rule(RuleId(0), |_| {}).is_none());
+        file.update_rule(RuleId(2), |rule| rule.set_pattern("c.com").unwrap());
+        assert_eq!(file.text(), "# x\n  a.com file://x\n#off c.com file://y\n");
+        assert!(matches!(&file.lines()[2].item, LineItem::Disabled(rule) if rule.source.host == "c.com"));
+
+        let mut file = RuleFile::parse("a.com file://x log://1 # mocks");
+        assert_eq!(file.op_spans(RuleId(0)).len(), 2);
+        file.update_rule(RuleId(0), |rule| {
+            rule.remove_ops_by_name("log");
+        });
+        assert_eq!(file.text(), "a.com file://x # mocks");
+
+        let mut file = RuleFile::parse("a.com b.com");
+        let edit = file.update_rule(RuleId(0), |rule| rule.set_pattern("A.COM").unwrap());
+        assert_eq!(edit, Some(TextEdit::new(Span::new(0, 11), "A.COM b.com")));
+        assert_eq!(file.text(), "A.COM b.com");
+    }
+
+    #[test]
+    fn test_node_at_position() {
+        let file = RuleFile::parse("# c\r\n#off a.com file://{x}\r\nb.com c.com");
+        let node = file.node_at_position(file.text().find("{x}").unwrap() + 1).unwrap();
+        assert_eq!(node, Node { rule: RuleId(1), kind: NodeKind::Value, span: Span::new(23, 26) });
+        assert_eq!(file.offset_to_position(node.span.start), Position::new(1, 18));
+        assert_eq!(file.position_to_offset(Position::new(2, 6)), Some(34));
+        assert_eq!(file.node_at_position(34).map(|node| node.kind), Some(NodeKind::Value));
+        assert_eq!(file.node_at_position(2), None);
+    }
+
+    #[test]
+    fn test_rename_host() {
+        let file = RuleFile::parse("a.com b.com\nhttp://a.com/x redirect://a.com/y file://a.com\n#off a.com.cn a.com");
+        let edits = file.rename_host("a.com", "c.dev");
+        assert_eq!(
+            crate::edit::apply_edits(file.text(), &edits),
+            "c.dev b.com\nhttp://c.dev/x redirect://c.dev/y file://a.com\n#off a.com.cn c.dev"
+        );
+    }
+
+    #[test]
+    fn test_dedupe_and_sort() {
+        let input = "c.com ws://x\nb.com file://y\nc.com   ws://x\n#off c.com ws://x\n\n# z\nz.com 127.0.0.1\na.com b.com";
+        let file = RuleFile::parse(input);
+        assert_eq!(
+            file.dedupe().text(),
+            "c.com ws://x\nb.com file://y\n#off c.com ws://x\n\n# z\nz.com 127.0.0.1\na.com b.com"
+        );
+        assert_eq!(
+            file.sort_by(SortKey::Host).text(),
+            "b.com file://y\nc.com ws://x\nc.com   ws://x\n#off c.com ws://x\n\n# z\na.com b.com\nz.com 127.0.0.1"
+        );
+        assert_eq!(
+            file.sort_by(SortKey::Protocol).text(),
+            "b.com file://y\nc.com ws://x\nc.com   ws://x\n#off c.com ws://x\n\n# z\nz.com 127.0.0.1\na.com b.com"
+        );
+        assert_eq!(file.sort_by(SortKey::Original).text(), input);
+    }
+
+    #[test]
+    fn test_parse_grouped() {
+        let input = "a.com b.com\n\n# Mocks\nc.com file://x\n\nd.com file://y\n# not a group\n\n\n#  Hosts \r\ne.com 127.0.0.1\n";
+        let groups = parse_grouped(input);
+        let found: Vec<_> = groups.iter().map(|(name, file)| (name.as_str(), file.text())).collect();
+        assert_eq!(
+            found,
+            vec![
+                ("Default", "a.com b.com"),
+                ("Mocks", "c.com file://x\n\nd.com file://y\n# not a group"),
+                ("Hosts", "e.com 127.0.0.1"),
+            ]
+        );
+        let text = to_grouped_text(&groups);
+        assert!(text.starts_with("# Default\na.com b.com\n\n# Mocks\n"));
+        let again: Vec<_> = parse_grouped(&text).into_iter().map(|(name, file)| (name, file.text().to_string())).collect();
+        let before: Vec<_> = groups.into_iter().map(|(name, file)| (name, file.text().to_string())).collect();
+        assert_eq!(again, before);
+        assert_eq!(parse_grouped("")[0].0, DEFAULT_GROUP);
+    }
+
+    #[test]
+    fn test_include_and_value_block() {
+        let input = "@https://a.com/rules.txt\n```mock.json\r\n{\"a\": 1}\r\n\r\n```\na.com file://{mock.json}\n```open\nx";
+        let file = RuleFile::parse(input);
+        let items: Vec<_> = file.lines().iter().map(|line| (line.number, &line.item)).collect();
+        let include = crate::parse_uri("https://a.com/rules.txt").unwrap().1;
+        assert_eq!(items[0], (0, &LineItem::Include(include)));
+        assert_eq!(
+            items[1],
+            (1, &LineItem::ValueBlock { key: "mock.json".into(), body: "{\"a\": 1}\n".into() })
+        );
+        assert_eq!(&input[file.lines()[1].span.start..file.lines()[1].span.end], "```mock.json\r\n{\"a\": 1}\r\n\r\n```");
+        assert!(matches!(items[2], (5, LineItem::Rule(_))));
+        assert_eq!(items[3], (6, &LineItem::ValueBlock { key: "open".into(), body: "x".into() }));
+        assert_eq!(file.rules().next().map(|(id, _, _)| id), Some(RuleId(5)));
+        assert_eq!(file.op_spans(RuleId(5)), vec![Span::new(61, 79)]);
+        let error = WhistleParseError::Syntax { span: Span::new(1, 1), kind: nom::error::ErrorKind::Eof };
+        assert_eq!(RuleFile::parse("@").lines()[0].item, LineItem::Invalid { text: "@".into(), error });
+
+        let body = "```\nx\n```";
+        let fence = value_fence(body);
+        assert_eq!(fence, "````");
+        let file = RuleFile::parse(format!("{fence}a.md type=md\n{body}\n{fence}\n"));
+        assert_eq!(file.lines()[0].item, LineItem::ValueBlock { key: "a.md".into(), body: body.into() });
+    }
+}
@@ -0,0 +1,261 @@
+//! The rules of a document, without its comments and layout.
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Instant;
+
+use crate::matcher::{MatchObserver, MatchOptions, Request};
+use crate::rule_file::RuleFile;
+use crate::{parse_rule, OpValue, ProxyRule, Rule};
+
+/// Name counted for targets without a scheme, `a.com 127.0.0.1`.
+pub const HOST_PROTOCOL: &str = "host";
+
+/// Protocols that route a request somewhere else, drawn as edges by the
+/// graph export. Targets without a scheme count as [`HOST_PROTOCOL`].
+pub const ROUTING_PROTOCOLS: &[&str] = &[
+    HOST_PROTOCOL, "http", "https", "ws", "wss", "tunnel", "redirect", "locationHref",
+    "proxy", "http-proxy", "https-proxy", "socks", "pac",
+];
+
+impl ProxyRule {
+    /// The protocol of the target, [`HOST_PROTOCOL`] when it has no scheme.
+    pub fn target_protocol(&self) -> &str {
+        match self.target.scheme.as_str() {
+            "" => HOST_PROTOCOL,
+            scheme => scheme,
+        }
+    }
+
+    /// The target as written after its `scheme://`.
+    pub fn target_value(&self) -> String {
+        format!("{}{}{}", self.target.host, self.target.path, self.target.query)
+    }
+
+    /// The target and the operations after it, a target without a scheme
+    /// read as `host://`.
+    pub fn operations(&self) -> Vec<Rule> {
+        let target = self.target_rule().unwrap_or_else(|| Rule {
+            name: HOST_PROTOCOL.to_string(),
+            value: OpValue::Raw(self.target_value()),
+        });
+        std::iter::once(target).chain(self.rules.iter().cloned()).collect()
+    }
+
+    /// The target read as an operation, as whistle does for
+    /// `a.com resCors://*`. `None` for targets without a scheme.
+    pub fn target_rule(&self) -> Option<Rule> {
+        if self.target.scheme.is_empty() {
+            return None;
+        }
+        let value = self.target_value();
+        let text = format!("{}://{}", self.target.scheme, crate::quote_if_needed(&value));
+        parse_rule(&text).ok().map(|(_, rule)| rule)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RuleSet {
+    pub rules: Vec<ProxyRule>,
+}
+
+impl RuleSet {
+    pub fn new(rules: Vec<ProxyRule>) -> Self {
+        RuleSet { rules }
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, ProxyRule> {
+        self.rules.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// The rules applying to `request`, in order, with their
+    /// [filters](crate::filter::Filter) honored.
+    pub fn resolve(&self, request: &Request) -> Vec<&ProxyRule> {
+        self.resolve_with(request, &MatchOptions::default())
+    }
+
+    pub fn resolve_with(&self, request: &Request, options: &MatchOptions) -> Vec<&ProxyRule> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!("resolve", url = request.url).entered();
+        let matches: Vec<&ProxyRule> = self.rules.iter().filter(|rule| rule.applies_to(request, options)).collect();
+        #[cfg(feature = "tracing")]
+        matches.iter().for_each(|rule| crate::compiled::trace_match(request.url, rule));
+        matches
+    }
+
+    /// Like [`RuleSet::resolve_with`], telling `observer` about every rule
+    /// checked along the way.
+    pub fn resolve_observed(
+        &self,
+        request: &Request,
+        options: &MatchOptions,
+        observer: &impl MatchObserver,
+    ) -> Vec<&ProxyRule> {
+        let mut matches = vec![];
+        for (index, rule) in self.rules.iter().enumerate() {
+            let start = Instant::now();
+            let matched = rule.applies_to(request, options);
+            observer.observe(index, matched, start.elapsed());
+            if matched {
+                matches.push(rule);
+            }
+        }
+        matches
+    }
+
+    /// The rules applying to `url`, in order, each matched only once the
+    /// iterator gets to it. For the first match only, take `.next()`.
+    pub fn matching<'a>(&'a self, url: &'a str) -> impl Iterator<Item = &'a ProxyRule> + 'a {
+        let request = Request::new(url);
+        let options = MatchOptions::default();
+        self.rules.iter().filter(move |rule| rule.applies_to(&request, &options))
+    }
+
+    /// Like [`RuleSet::matching`], for a whole request and other options.
+    pub fn matching_with<'a>(
+        &'a self,
+        request: &'a Request,
+        options: &'a MatchOptions,
+    ) -> impl Iterator<Item = &'a ProxyRule> + 'a {
+        self.rules.iter().filter(move |rule| rule.applies_to(request, options))
+    }
+
+    pub fn stats(&self) -> RuleStats {
+        let mut stats = RuleStats { rules: self.rules.len(), ..Default::default() };
+        for rule in &self.rules {
+            if rule.pattern().is_wildcard() {
+                stats.wildcard_patterns += 1;
+            } else {
+                stats.exact_patterns += 1;
+            }
+            if !rule.source.host.is_empty() {
+                stats.hosts.insert(rule.source.host.clone());
+            }
+            if rule.target.scheme.is_empty() {
+                *stats.protocols.entry(HOST_PROTOCOL.to_string()).or_default() += 1;
+            }
+            for op in rule.target_rule().iter().chain(&rule.rules) {
+                *stats.protocols.entry(op.name.clone()).or_default() += 1;
+                match &op.value {
+                    OpValue::TemplateString(_) => stats.templates += 1,
+                    OpValue::Value(key) => *stats.value_refs.entry(key.clone()).or_default() += 1,
+                    _ => {}
+                }
+            }
+        }
+        stats
+    }
+}
+
+impl<'a> IntoIterator for &'a RuleSet {
+    type Item = &'a ProxyRule;
+    type IntoIter = std::slice::Iter<'a, ProxyRule>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl FromIterator<ProxyRule> for RuleSet {
+    fn from_iter<I: IntoIterator<Item = ProxyRule>>(iter: I) -> Self {
+        RuleSet::new(iter.into_iter().collect())
+    }
+}
+
+impl RuleFile {
+    /// The enabled rules of the file.
+    pub fn rule_set(&self) -> RuleSet {
+        self.rules()
+            .filter(|(_, _, enabled)| *enabled)
+            .map(|(_, rule, _)| rule.clone())
+            .collect()
+    }
+}
+
+/// What a rule set does, as counted by [`RuleSet::stats`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RuleStats {
+    pub rules: usize,
+    /// Operations per protocol, the target counted as [`HOST_PROTOCOL`] when
+    /// it has no scheme.
+    pub protocols: BTreeMap<String, usize>,
+    /// Distinct pattern hosts.
+    pub hosts: BTreeSet<String>,
+    pub wildcard_patterns: usize,
+    pub exact_patterns: usize,
+    /// Operations with a `` `…` `` template value.
+    pub templates: usize,
+    /// Uses of each `{key}` value.
+    pub value_refs: BTreeMap<String, usize>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_stats() {
+        let file = RuleFile::parse(
+            "a.com 127.0.0.1\n*.a.com file://{mock} resHeaders://{headers}\n\
+             #off b.com file://x\na.com/api file://x resBody://`${name}` reqHeaders://{headers}",
+        );
+        let stats = file.rule_set().stats();
+        assert_eq!(stats.rules, 3);
+        assert_eq!(
+            stats.protocols,
+            BTreeMap::from([
+                ("file".to_string(), 2),
+                ("host".to_string(), 1),
+                ("reqHeaders".to_string(), 1),
+                ("resBody".to_string(), 1),
+                ("resHeaders".to_string(), 1),
+            ])
+        );
+        assert_eq!(stats.hosts, BTreeSet::from(["*.a.com".to_string(), "a.com".to_string()]));
+        assert_eq!((stats.wildcard_patterns, stats.exact_patterns), (1, 2));
+        assert_eq!(stats.templates, 1);
+        assert_eq!(
+            stats.value_refs,
+            BTreeMap::from([("headers".to_string(), 2), ("mock".to_string(), 1)])
+        );
+    }
+
+    #[test]
+    fn test_matching() {
+        let set = RuleFile::parse("a.com/api file://x\nb.com file://y\na.com ua://z\n*.a.com file://w").rule_set();
+        let mut matches = set.matching("http://a.com/api/users");
+        assert_eq!(matches.next().map(|rule| rule.target.host.as_str()), Some("x"));
+        assert_eq!(matches.next().map(|rule| rule.target.host.as_str()), Some("z"));
+        assert_eq!(matches.next(), None);
+        assert_eq!(set.matching("http://c.com/").count(), 0);
+        assert_eq!(set.matching("http://a.com/api").collect::<Vec<_>>(), set.resolve(&Request::new("http://a.com/api")));
+    }
+
+    #[test]
+    fn test_resolve_observed() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        let set = RuleFile::parse("a.com file://x\nb.com file://y\na.com/api ua://z").rule_set();
+        let hits: Vec<AtomicUsize> = (0..set.len()).map(|_| AtomicUsize::new(0)).collect();
+        let checked = AtomicUsize::new(0);
+        let observer = |rule: usize, matched: bool, _elapsed| {
+            checked.fetch_add(1, Ordering::Relaxed);
+            if matched {
+                hits[rule].fetch_add(1, Ordering::Relaxed);
+            }
+        };
+        for url in ["http://a.com/api/1", "http://a.com/", "http://b.com/"] {
+            let request = Request::new(url);
+            let matches = set.resolve_observed(&request, &MatchOptions::default(), &observer);
+            assert_eq!(matches, set.resolve(&request));
+        }
+        assert_eq!(checked.load(Ordering::Relaxed), 9);
+        let hits: Vec<usize> = hits.iter().map(|h| h.load(Ordering::Relaxed)).collect();
+        assert_eq!(hits, vec![2, 1, 1]);
+    }
+}
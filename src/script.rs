@@ -0,0 +1,92 @@
+//! Scripts embedded in rules, `reqScript://{check.js}` and friends, for
+//! tooling that lints or syntax-checks them next to the rules.
+use crate::markdown_values::ValueMap;
+use crate::{OpValue, ProxyRule};
+
+/// Protocols whose value is the source of a script.
+pub const SCRIPT_VALUE_PROTOCOLS: &[&str] = &[
+    "reqScript", "resScript", "rulesScript", "frameScript", "jsPrepend", "jsAppend", "jsBody",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmbeddedScript {
+    pub protocol: String,
+    /// The `{key}` the source comes from, `None` for inline `(…)` scripts.
+    pub key: Option<String>,
+    /// The extension of the key, `js` when it has none.
+    pub language: String,
+    /// `None` when the key isn't in the values document.
+    pub source: Option<String>,
+}
+
+impl ProxyRule {
+    /// The scripts of the rule's inline and `{key}` script values, reading
+    /// keys from `values`. Raw values name a file and have no source here.
+    pub fn scripts(&self, values: &ValueMap) -> Vec<EmbeddedScript> {
+        self.operations()
+            .into_iter()
+            .filter(|op| SCRIPT_VALUE_PROTOCOLS.contains(&op.name.as_str()))
+            .filter_map(|op| {
+                let (key, source) = match op.value {
+                    OpValue::Value(key) => {
+                        let source = values.resolve(&key).ok();
+                        (Some(key), source)
+                    }
+                    OpValue::Inline(source) => (None, Some(source)),
+                    _ => return None,
+                };
+                let language = key
+                    .as_deref()
+                    .and_then(|key| key.rsplit_once('.'))
+                    .map_or("js", |(_, ext)| ext)
+                    .to_string();
+                Some(EmbeddedScript { protocol: op.name, key, language, source })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_scripts() {
+        let values = ValueMap::parse("```check.ts\nexport {};\n```\n```log\nconsole.log(1)\n```\n");
+        let rule = crate::parse_proxy_rule(
+            "a.com reqScript://{check.ts} resScript://(rules.length=0) jsAppend://{log} frameScript://{nope} reqScript:///tmp/a.js",
+        )
+        .unwrap()
+        .1;
+        let scripts = rule.scripts(&values);
+        assert_eq!(
+            scripts,
+            vec![
+                EmbeddedScript {
+                    protocol: "reqScript".into(),
+                    key: Some("check.ts".into()),
+                    language: "ts".into(),
+                    source: Some("export {};".into()),
+                },
+                EmbeddedScript {
+                    protocol: "resScript".into(),
+                    key: None,
+                    language: "js".into(),
+                    source: Some("rules.length=0".into()),
+                },
+                EmbeddedScript {
+                    protocol: "jsAppend".into(),
+                    key: Some("log".into()),
+                    language: "js".into(),
+                    source: Some("console.log(1)".into()),
+                },
+                EmbeddedScript {
+                    protocol: "frameScript".into(),
+                    key: Some("nope".into()),
+                    language: "js".into(),
+                    source: None,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! Find text in the patterns, protocol names and values of a [`RuleFile`].
+use crate::rule_file::{NodeKind, RuleFile, RuleId};
+use crate::Span;
+
+#[derive(Debug, Clone)]
+pub enum Query {
+    /// Every occurrence of the text.
+    Substring(String),
+    /// A whole field matching `*` and `?` wildcards.
+    Glob(String),
+    /// Every match of the expression.
+    #[cfg(feature = "regex")]
+    Regex(regex::Regex),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchHit {
+    pub rule: RuleId,
+    pub field: NodeKind,
+    /// The matched text in the document.
+    pub span: Span,
+}
+
+impl Query {
+    fn matches(&self, text: &str) -> Vec<Span> {
+        match self {
+            Query::Substring(needle) if needle.is_empty() => vec![],
+            Query::Substring(needle) => text
+                .match_indices(needle.as_str())
+                .map(|(at, m)| Span::new(at, at + m.len()))
+                .collect(),
+            Query::Glob(glob) if glob_match(glob, text) => vec![Span::new(0, text.len())],
+            Query::Glob(_) => vec![],
+            #[cfg(feature = "regex")]
+            Query::Regex(re) => re
+                .find_iter(text)
+                .filter(|m| !m.is_empty())
+                .map(|m| Span::new(m.start(), m.end()))
+                .collect(),
+        }
+    }
+}
+
+impl RuleFile {
+    /// Every hit of `query` in the rules of the file, disabled rules
+    /// included, in document order.
+    pub fn search(&self, query: &Query) -> Vec<SearchHit> {
+        let mut hits = vec![];
+        for line in self.lines() {
+            for node in self.nodes(RuleId(line.number)) {
+                let text = &self.text()[node.span.start..node.span.end];
+                hits.extend(query.matches(text).into_iter().map(|span| SearchHit {
+                    rule: node.rule,
+                    field: node.kind,
+                    span: span.shifted(node.span.start),
+                }));
+            }
+        }
+        hits
+    }
+}
+
+/// Whether all of `text` matches `glob`, where `*` is any run of characters
+/// and `?` any one character.
+pub fn glob_match(glob: &str, text: &str) -> bool {
+    let next = |s: &str, at: usize| s[at..].chars().next();
+    // byte offsets into both, always on a char boundary
+    let (mut g, mut t) = (0, 0);
+    // where the last `*` was and how much of the text it has taken so far
+    let mut star = None;
+    while let Some(c) = next(text, t) {
+        match next(glob, g) {
+            Some('*') => {
+                star = Some((g, t));
+                g += 1;
+            }
+            Some(want) if want == '?' || want == c => {
+                g += want.len_utf8();
+                t += c.len_utf8();
+            }
+            _ => match star {
+                Some((star_g, star_t)) => {
+                    g = star_g + 1;
+                    t = star_t + next(text, star_t).map_or(0, char::len_utf8);
+                    star = Some((star_g, t));
+                }
+                None => return false,
+            },
+        }
+    }
+    glob[g..].chars().all(|c| c == '*')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*.a.com", "www.a.com"));
+        assert!(glob_match("res*", "resHeaders"));
+        assert!(glob_match("a?c*", "abcdef"));
+        assert!(!glob_match("*.a.com", "a.com"));
+        assert!(!glob_match("file", "files"));
+    }
+
+    #[test]
+    fn test_search() {
+        let input = "a.com file:///mock/a resHeaders://{a}\n# a.com\n  #off b.com reqHeaders://(a)";
+        let file = RuleFile::parse(input);
+        let hits = file.search(&Query::Substring("a".into()));
+        let found: Vec<_> = hits.iter().map(|hit| (hit.rule.0, hit.field, &input[hit.span.start..hit.span.end])).collect();
+        assert_eq!(
+            found,
+            vec![
+                (0, NodeKind::Pattern, "a"),
+                (0, NodeKind::Value, "a"),
+                (0, NodeKind::Protocol, "a"),
+                (0, NodeKind::Value, "a"),
+                (2, NodeKind::Protocol, "a"),
+                (2, NodeKind::Value, "a"),
+            ]
+        );
+
+        let hits = file.search(&Query::Glob("*Headers".into()));
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[1].span, Span::new(59, 69));
+        assert_eq!(hits[1].field, NodeKind::Protocol);
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_search_regex() {
+        let file = RuleFile::parse("a.com file:///mock/a.json\nc.com resBody://(x.json)");
+        let hits = file.search(&Query::Regex(regex::Regex::new(r"\w+\.json").unwrap()));
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].span, Span::new(19, 25));
+        assert_eq!(hits[1].span, Span::new(43, 49));
+    }
+}
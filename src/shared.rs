@@ -0,0 +1,126 @@
+//! A [`RuleSet`] that long-running proxies can swap while requests read it.
+//!
+//! Readers get an `Arc` snapshot without taking a lock, so a reload never
+//! blocks the request path; requests in flight keep the set they started
+//! with.
+use std::path::Path;
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+
+use crate::rule_file::{Line, LineItem, RuleFile};
+use crate::rule_set::RuleSet;
+use crate::{ParserOptions, WhistleParseError};
+
+#[derive(Debug)]
+pub enum ReloadError {
+    Io(std::io::Error),
+    Parse(WhistleParseError),
+    /// The new text has invalid lines, so the current rules were kept.
+    Invalid(Vec<Line>),
+}
+
+impl From<std::io::Error> for ReloadError {
+    fn from(e: std::io::Error) -> Self {
+        ReloadError::Io(e)
+    }
+}
+
+#[derive(Debug)]
+pub struct SharedRuleSet {
+    current: ArcSwap<RuleSet>,
+    options: ParserOptions,
+}
+
+impl SharedRuleSet {
+    pub fn new(rules: RuleSet) -> Self {
+        SharedRuleSet::with_options(rules, ParserOptions::default())
+    }
+
+    /// A set reloaded with `options`.
+    pub fn with_options(rules: RuleSet, options: ParserOptions) -> Self {
+        SharedRuleSet { current: ArcSwap::from_pointee(rules), options }
+    }
+
+    /// The rules as of now.
+    pub fn load(&self) -> Arc<RuleSet> {
+        self.current.load_full()
+    }
+
+    pub fn store(&self, rules: RuleSet) {
+        self.current.store(Arc::new(rules));
+    }
+
+    /// Replace the rules with the enabled rules of `text`. Nothing changes
+    /// when a line is invalid, so a half-saved file never takes effect.
+    pub fn reload_from_str(&self, text: &str) -> Result<(), ReloadError> {
+        let file = RuleFile::parse_with(text, self.options.clone()).map_err(ReloadError::Parse)?;
+        let invalid: Vec<Line> = file
+            .lines()
+            .iter()
+            .filter(|line| matches!(line.item, LineItem::Invalid { .. }))
+            .cloned()
+            .collect();
+        if !invalid.is_empty() {
+            return Err(ReloadError::Invalid(invalid));
+        }
+        self.store(file.rule_set());
+        Ok(())
+    }
+
+    pub fn reload_from_path(&self, path: impl AsRef<Path>) -> Result<(), ReloadError> {
+        self.reload_from_str(&std::fs::read_to_string(path)?)
+    }
+
+    /// Reload from `path` whenever it changes, until the returned watcher
+    /// is dropped. Failed reloads keep the current rules and are passed to
+    /// `on_error`.
+    #[cfg(feature = "watch")]
+    pub fn watch<F>(self: &Arc<Self>, path: impl AsRef<Path>, on_error: F) -> notify::Result<notify::RecommendedWatcher>
+    where
+        F: Fn(ReloadError) + Send + 'static,
+    {
+        use notify::{EventKind, RecursiveMode, Watcher};
+
+        let path = path.as_ref().to_path_buf();
+        let shared = Arc::clone(self);
+        let file = path.clone();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let changed = match event {
+                Ok(event) => matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)),
+                Err(e) => {
+                    on_error(ReloadError::Io(std::io::Error::other(e)));
+                    false
+                }
+            };
+            if changed {
+                if let Err(e) = shared.reload_from_path(&file) {
+                    on_error(e);
+                }
+            }
+        })?;
+        watcher.watch(&path, RecursiveMode::NonRecursive)?;
+        Ok(watcher)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_reload() {
+        let shared = SharedRuleSet::new(RuleSet::default());
+        let before = shared.load();
+        shared.reload_from_str("a.com b.com\n#off c.com d.com").unwrap();
+        assert!(before.is_empty());
+        assert_eq!(shared.load().len(), 1);
+
+        match shared.reload_from_str("a.com b.com oops\nc.com d.com") {
+            Err(ReloadError::Invalid(lines)) => assert_eq!(lines[0].number, 0),
+            other => panic!("expected invalid lines, got {other:?}"),
+        }
+        assert_eq!(shared.load().rules[0].target.host, "b.com");
+        assert!(matches!(shared.reload_from_path("/nonexistent/rules.txt"), Err(ReloadError::Io(_))));
+    }
+}
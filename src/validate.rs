@@ -0,0 +1,134 @@
+//! Checks behind [`ParserOptions::strict_uri`](crate::ParserOptions::strict_uri).
+use crate::{Span, Uri, WhistleParseError};
+
+/// Schemes whose host part is a hostname and must be present.
+pub const HOST_SCHEMES: &[&str] = &["http", "https", "ws", "wss", "tunnel"];
+
+/// Check `uri`, parsed from a token starting at byte `offset` of the line.
+///
+/// The host of a pattern is always checked; the host of an operation only for
+/// url-like schemes since e.g. `file://D:\mock` puts a path there.
+pub fn validate_uri(uri: &Uri, offset: usize, is_pattern: bool) -> Result<(), WhistleParseError> {
+    let scheme = uri.scheme.as_str();
+    let any_scheme = is_pattern && scheme == "*";
+    if !scheme.is_empty() && !any_scheme && !is_valid_scheme(scheme) {
+        return Err(WhistleParseError::InvalidScheme {
+            scheme: scheme.to_string(),
+            span: Span::new(offset, offset + scheme.len()),
+        });
+    }
+    let host_at = if scheme.is_empty() { offset } else { offset + scheme.len() + 3 };
+    let url_like = HOST_SCHEMES.contains(&scheme);
+    if uri.host.is_empty() {
+        if url_like {
+            return Err(WhistleParseError::MissingHost { span: Span::new(host_at, host_at) });
+        }
+        return Ok(());
+    }
+    if is_pattern || url_like || scheme.is_empty() {
+        validate_host(&uri.host, host_at, is_pattern)?;
+    }
+    Ok(())
+}
+
+fn is_valid_scheme(scheme: &str) -> bool {
+    scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+}
+
+fn validate_host(host: &str, offset: usize, is_pattern: bool) -> Result<(), WhistleParseError> {
+    let invalid_host = || WhistleParseError::InvalidHost {
+        host: host.to_string(),
+        span: Span::new(offset, offset + host.len()),
+    };
+    // `!pattern`, `^wildcard` and `$exact` markers of whistle patterns
+    let name = match is_pattern {
+        true => host.strip_prefix(['!', '^', '$']).unwrap_or(host),
+        false => host,
+    };
+
+    let (name, port, is_ipv6) = if let Some(ipv6) = name.strip_prefix('[') {
+        let (addr, rest) = ipv6.split_once(']').ok_or_else(invalid_host)?;
+        if addr.is_empty() || !addr.chars().all(|c| c.is_ascii_hexdigit() || c == ':' || c == '.') {
+            return Err(invalid_host());
+        }
+        match rest {
+            "" => (addr, None, true),
+            _ => (addr, Some(rest.strip_prefix(':').ok_or_else(invalid_host)?), true),
+        }
+    } else {
+        match name.rsplit_once(':') {
+            Some((name, port)) => (name, Some(port), false),
+            None => (name, None, false),
+        }
+    };
+
+    if !is_ipv6 {
+        // a leading dot (`.example.com`) means the domain and its subdomains
+        let labels = name.strip_prefix('.').unwrap_or(name);
+        let valid_label = |label: &str| {
+            !label.is_empty()
+                && label
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '*'))
+        };
+        if !labels.split('.').all(valid_label) {
+            return Err(invalid_host());
+        }
+    }
+
+    if let Some(port) = port {
+        let is_port = |p: &str| !p.is_empty() && p.parse::<u16>().is_ok();
+        let valid = port == "*"
+            || is_port(port)
+            || port.split_once('-').is_some_and(|(a, b)| is_port(a) && is_port(b));
+        if !valid {
+            let port_at = offset + host.len() - port.len();
+            return Err(WhistleParseError::InvalidPort {
+                port: port.to_string(),
+                span: Span::new(port_at, offset + host.len()),
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parse_proxy_rule_with, ParserOptions};
+
+    #[test]
+    fn test_strict_uri() {
+        let strict = ParserOptions::strict();
+        for line in [
+            "www.example.com 127.0.0.1",
+            "*.example.com:8080 file:///mock",
+            "^**.example.com/api/*** http://[::1]:3000/x",
+            "example.com:8000-8999 file://D:\\mock\\a.html",
+            ".example.com reqHeaders://{h}",
+        ] {
+            assert!(parse_proxy_rule_with(line, &strict).is_ok(), "{line}");
+        }
+
+        assert_eq!(
+            parse_proxy_rule_with("  %%%///!!! 127.0.0.1", &strict).unwrap_err(),
+            WhistleParseError::InvalidHost { host: "%%%".into(), span: Span::new(2, 5) }
+        );
+        assert_eq!(
+            parse_proxy_rule_with("a.com http:///x", &strict).unwrap_err(),
+            WhistleParseError::MissingHost { span: Span::new(13, 13) }
+        );
+        assert_eq!(
+            parse_proxy_rule_with("a.com:99999 b.com", &strict).unwrap_err(),
+            WhistleParseError::InvalidPort { port: "99999".into(), span: Span::new(6, 11) }
+        );
+        assert_eq!(
+            parse_proxy_rule_with("a.com b..com", &strict).unwrap_err(),
+            WhistleParseError::InvalidHost { host: "b..com".into(), span: Span::new(6, 12) }
+        );
+        assert!(parse_proxy_rule_with("???///!!! 127.0.0.1", &ParserOptions::default()).is_ok());
+    }
+}
@@ -0,0 +1,126 @@
+//! Several values documents read as one, so a values file shared by a team
+//! can be overridden by a local one. Each [`ValueMap`] is a layer and later
+//! layers take precedence: a key is looked up from the last layer down, and
+//! references inside any layer resolve against the whole store.
+use std::fmt;
+
+use crate::markdown_values::{resolve_chain, ValueError, ValueMap};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValueLayer {
+    /// Where the layer came from, a file name or `"local"`.
+    pub name: String,
+    pub values: ValueMap,
+}
+
+/// A key defined in a layer and again in a later one, which wins.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Shadowed {
+    pub key: String,
+    /// The layer whose value is hidden.
+    pub layer: String,
+    /// The last layer defining the key, whose value is used.
+    pub by: String,
+}
+
+impl fmt::Display for Shadowed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "value `{}` from {} is overridden by {}", self.key, self.layer, self.by)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ValueStore {
+    layers: Vec<ValueLayer>,
+}
+
+impl ValueStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a layer above the existing ones.
+    pub fn push(&mut self, name: impl Into<String>, values: ValueMap) {
+        self.layers.push(ValueLayer { name: name.into(), values });
+    }
+
+    pub fn with(mut self, name: impl Into<String>, values: ValueMap) -> Self {
+        self.push(name, values);
+        self
+    }
+
+    /// The layers, lowest precedence first.
+    pub fn layers(&self) -> &[ValueLayer] {
+        &self.layers
+    }
+
+    /// The raw value from the last layer defining `key`.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.layers.iter().rev().find_map(|layer| layer.values.get(key))
+    }
+
+    /// The name of the layer [`ValueStore::get`] takes `key` from.
+    pub fn source_of(&self, key: &str) -> Option<&str> {
+        self.layers
+            .iter()
+            .rev()
+            .find(|layer| layer.values.get(key).is_some())
+            .map(|layer| layer.name.as_str())
+    }
+
+    /// Like [`ValueMap::resolve`], with every reference looked up in the
+    /// whole store, so an override also changes the values built from it.
+    pub fn resolve(&self, key: &str) -> Result<String, ValueError> {
+        resolve_chain(&|key| self.get(key), key, &mut vec![])
+    }
+
+    /// Every key with a value hidden by a later layer, by layer and then in
+    /// the layer's order.
+    pub fn shadowed(&self) -> Vec<Shadowed> {
+        let mut shadowed = vec![];
+        for (i, layer) in self.layers.iter().enumerate() {
+            for (key, _) in layer.values.iter() {
+                let by = self.layers[i + 1..].iter().rev().find(|later| later.values.get(key).is_some());
+                if let Some(by) = by {
+                    shadowed.push(Shadowed { key: key.to_string(), layer: layer.name.clone(), by: by.name.clone() });
+                }
+            }
+        }
+        shadowed
+    }
+
+    /// The values the store ends up with as one map, keys in the order they
+    /// first appear from the lowest layer up.
+    pub fn merged(&self) -> ValueMap {
+        let mut merged = ValueMap::new();
+        for layer in &self.layers {
+            for (key, value) in layer.values.iter() {
+                merged.insert(key, value);
+            }
+        }
+        merged
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_layers() {
+        let shared = ValueMap::parse("```host\nteam.dev\n```\n```url\nhttp://${host}/api\n```\n```h\n1\n```\n");
+        let local = ValueMap::parse("```host\nlocalhost:3000\n```\n");
+        let store = ValueStore::new().with("shared.md", shared).with("local", local);
+
+        assert_eq!(store.get("host"), Some("localhost:3000"));
+        assert_eq!(store.source_of("url"), Some("shared.md"));
+        assert_eq!(store.resolve("url"), Ok("http://localhost:3000/api".to_string()));
+        assert_eq!(store.resolve("nope"), Err(ValueError::Missing("nope".into())));
+
+        let shadowed = store.shadowed();
+        assert_eq!(shadowed.len(), 1);
+        assert_eq!(shadowed[0].to_string(), "value `host` from shared.md is overridden by local");
+        let merged: Vec<_> = store.merged().iter().map(|(k, v)| format!("{k}={v}")).collect();
+        assert_eq!(merged, ["host=localhost:3000", "url=http://${host}/api", "h=1"]);
+    }
+}
@@ -0,0 +1,409 @@
+//! A rules document together with its values document, for navigation
+//! between `{key}` references and the code blocks defining them, and for
+//! checking, matching and formatting both at once.
+use std::collections::HashSet;
+
+#[cfg(feature = "typed-ops")]
+use crate::diagnostic::{diagnostics, Diagnostic};
+use crate::edit::TextEdit;
+use crate::line_index::LineIndex;
+use crate::markdown_values::{value_blocks, value_reference, ValueBlock, ValueError, ValueMap};
+use crate::matcher::{MatchOptions, Request};
+#[cfg(feature = "typed-ops")]
+use crate::meta::Date;
+use crate::rule_file::{LineItem, NodeKind, RuleFile, RuleId};
+use crate::{ProxyRule, Span};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Document {
+    Rules,
+    Values,
+}
+
+/// A use of a value: `{key}` in a rule, or `{key}` and `${key}` in another
+/// value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reference {
+    pub key: String,
+    pub document: Document,
+    /// The key, without the braces around it.
+    pub span: Span,
+}
+
+/// Edits to both documents of a [`Workspace`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WorkspaceEdit {
+    pub rules: Vec<TextEdit>,
+    pub values: Vec<TextEdit>,
+}
+
+/// Where the two documents of a [`Workspace`] disagree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValueProblem {
+    /// A reference to a key no block defines.
+    Undefined(Reference),
+    /// A block no reference uses, at its key.
+    Unused { key: String, span: Span },
+    /// A referenced value whose references loop or nest too deeply, at the
+    /// key of its block.
+    Unresolvable { key: String, span: Span, error: ValueError },
+}
+
+impl ValueProblem {
+    pub fn document(&self) -> Document {
+        match self {
+            ValueProblem::Undefined(reference) => reference.document,
+            _ => Document::Values,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            ValueProblem::Undefined(reference) => reference.span,
+            ValueProblem::Unused { span, .. } | ValueProblem::Unresolvable { span, .. } => *span,
+        }
+    }
+}
+
+/// Diagnostics for both documents of a [`Workspace`].
+#[cfg(feature = "typed-ops")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WorkspaceDiagnostics {
+    pub rules: Vec<Diagnostic>,
+    pub values: Vec<Diagnostic>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    pub rules: RuleFile,
+    values_text: String,
+    values: ValueMap,
+    blocks: Vec<ValueBlock>,
+    values_index: LineIndex,
+}
+
+impl Workspace {
+    pub fn new(rules: RuleFile, values: impl Into<String>) -> Workspace {
+        let values_text = values.into();
+        Workspace {
+            rules,
+            values: ValueMap::parse(&values_text),
+            blocks: value_blocks(&values_text),
+            values_index: LineIndex::new(&values_text),
+            values_text,
+        }
+    }
+
+    /// Parse both documents, the way whistle reads its Rules and Values
+    /// tabs.
+    pub fn parse(rules_text: impl Into<String>, values_text: impl Into<String>) -> Workspace {
+        Workspace::new(RuleFile::parse(rules_text), values_text)
+    }
+
+    pub fn values(&self) -> &ValueMap {
+        &self.values
+    }
+
+    pub fn values_text(&self) -> &str {
+        &self.values_text
+    }
+
+    pub fn value_blocks(&self) -> &[ValueBlock] {
+        &self.blocks
+    }
+
+    pub fn values_index(&self) -> &LineIndex {
+        &self.values_index
+    }
+
+    /// The block defining the value of `reference`. When a key is defined
+    /// twice the later block wins, as in [`ValueMap::parse`].
+    pub fn definition_of(&self, reference: &Reference) -> Option<&ValueBlock> {
+        self.blocks.iter().rev().find(|block| block.key == reference.key)
+    }
+
+    /// Every reference to `key` in both documents, rules first.
+    pub fn references_of(&self, key: &str) -> Vec<Reference> {
+        self.references().into_iter().filter(|r| r.key == key).collect()
+    }
+
+    /// The reference under the cursor at `offset` of `document`.
+    pub fn reference_at(&self, document: Document, offset: usize) -> Option<Reference> {
+        let within = |r: &Reference| r.span.start <= offset && offset <= r.span.end;
+        match document {
+            Document::Rules => self.rule_references(self.rules.node_at_position(offset).map(|n| n.rule)?),
+            Document::Values => self.value_references(),
+        }
+        .into_iter()
+        .find(within)
+    }
+
+    /// The edits renaming value `old` to `new`: every reference and the key
+    /// of every block defining it. `None` when `new` isn't a valid key.
+    pub fn rename_value_key(&self, old: &str, new: &str) -> Option<WorkspaceEdit> {
+        value_reference(&format!("{{{new}}}"))?;
+        let mut edit = WorkspaceEdit::default();
+        for reference in self.references_of(old) {
+            let text_edit = TextEdit::new(reference.span, new);
+            match reference.document {
+                Document::Rules => edit.rules.push(text_edit),
+                Document::Values => edit.values.push(text_edit),
+            }
+        }
+        edit.values.extend(
+            self.blocks
+                .iter()
+                .filter(|block| block.key == old)
+                .map(|block| TextEdit::new(block.key_span, new)),
+        );
+        edit.values.sort_by_key(|edit| edit.span.start);
+        Some(edit)
+    }
+
+    /// The value of `key` with its references expanded, see
+    /// [`ValueMap::resolve`].
+    pub fn resolve(&self, key: &str) -> Result<String, ValueError> {
+        self.values.resolve(key)
+    }
+
+    /// The enabled rules applying to `request`, in order.
+    pub fn matching(&self, request: &Request) -> Vec<(RuleId, &ProxyRule)> {
+        let options = MatchOptions::default();
+        self.rules
+            .rules()
+            .filter(|(_, rule, enabled)| *enabled && rule.applies_to(request, &options))
+            .map(|(id, rule, _)| (id, rule))
+            .collect()
+    }
+
+    /// What the documents get wrong about each other, in the order of
+    /// [`Workspace::references`] and then of the blocks.
+    pub fn validate(&self) -> Vec<ValueProblem> {
+        let references = self.references();
+        let mut problems: Vec<_> = references
+            .iter()
+            .filter(|r| self.values.get(&r.key).is_none())
+            .cloned()
+            .map(ValueProblem::Undefined)
+            .collect();
+        let used: HashSet<&str> = references.iter().map(|r| r.key.as_str()).collect();
+        let mut checked = HashSet::new();
+        for block in &self.blocks {
+            let key = block.key.clone();
+            if !used.contains(key.as_str()) {
+                problems.push(ValueProblem::Unused { key, span: block.key_span });
+                continue;
+            }
+            if !checked.insert(key.clone()) {
+                continue;
+            }
+            // missing keys along the way are already undefined references
+            if let Err(error @ (ValueError::CircularReference { .. } | ValueError::TooDeep { .. })) = self.resolve(&key) {
+                problems.push(ValueProblem::Unresolvable { key, span: block.key_span, error });
+            }
+        }
+        problems
+    }
+
+    /// Parse errors, lint warnings and audit findings of the rules together
+    /// with the [problems](Workspace::validate) between the documents, the
+    /// duplicated keys of the values and their checksum mismatches. Rules
+    /// [expire](crate::lint::expired) by `today`.
+    #[cfg(feature = "typed-ops")]
+    pub fn lint(&self, today: Date) -> WorkspaceDiagnostics {
+        let mut out = WorkspaceDiagnostics { rules: diagnostics(&self.rules, today), values: vec![] };
+        out.values.extend(self.values.duplicates().iter().map(Diagnostic::from));
+        out.values.extend(self.values.checksum_mismatches().iter().map(Diagnostic::from));
+        for problem in self.validate() {
+            match problem.document() {
+                Document::Rules => out.rules.push(Diagnostic::from(&problem)),
+                Document::Values => out.values.push(Diagnostic::from(&problem)),
+            }
+        }
+        out.rules.sort_by_key(|d| d.span.start);
+        out.values.sort_by_key(|d| d.span.start);
+        out
+    }
+
+    /// Edits writing each rule, enabled or not, the way [`ProxyRule`]
+    /// displays it, one space between its parts. Values are left as they
+    /// are, along with comments and the text around the blocks.
+    pub fn format(&self) -> Vec<TextEdit> {
+        let mut edits = vec![];
+        for line in self.rules.lines() {
+            let (LineItem::Rule(rule) | LineItem::Disabled(rule)) = &line.item else {
+                continue;
+            };
+            let Some(span) = self.rules.rule_span(RuleId(line.number)) else {
+                continue;
+            };
+            let formatted = rule.to_string();
+            if self.rules.text()[span.start..span.end] != formatted {
+                edits.push(TextEdit::new(span, formatted));
+            }
+        }
+        edits
+    }
+
+    /// Every reference in both documents, rules first.
+    pub fn references(&self) -> Vec<Reference> {
+        let mut references: Vec<_> =
+            self.rules.lines().iter().flat_map(|line| self.rule_references(RuleId(line.number))).collect();
+        references.extend(self.value_references());
+        references
+    }
+
+    fn rule_references(&self, rule: RuleId) -> Vec<Reference> {
+        self.rules
+            .nodes(rule)
+            .into_iter()
+            .filter(|node| node.kind == NodeKind::Value)
+            .filter_map(|node| {
+                let text = &self.rules.text()[node.span.start..node.span.end];
+                let key = value_reference(text).filter(|key| text.len() == key.len() + 2)?;
+                Some(Reference {
+                    key: key.to_string(),
+                    document: Document::Rules,
+                    span: Span::new(node.span.start + 1, node.span.end - 1),
+                })
+            })
+            .collect()
+    }
+
+    fn value_references(&self) -> Vec<Reference> {
+        let mut references = vec![];
+        for block in &self.blocks {
+            let body = &self.values_text[block.body.start..block.body.end];
+            let reference = |start: usize, key: &str| Reference {
+                key: key.to_string(),
+                document: Document::Values,
+                span: Span::new(start, start + key.len()).shifted(block.body.start),
+            };
+            if let Some(key) = value_reference(body) {
+                references.push(reference(body.find('{').unwrap_or_default() + 1, key));
+                continue;
+            }
+            let mut pos = 0;
+            while let Some(found) = body[pos..].find("${") {
+                let start = pos + found + 2;
+                let Some(len) = body[start..].find('}') else {
+                    break;
+                };
+                let key = &body[start..start + len];
+                if value_reference(&format!("{{{key}}}")).is_some() {
+                    references.push(reference(start, key));
+                }
+                pos = start + len;
+            }
+        }
+        references
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::edit::apply_edits;
+
+    #[test]
+    fn test_workspace() {
+        let rules = RuleFile::parse("a.com file://{mock} resHeaders://{h}\n#off b.com resBody://{mock}");
+        let values = "```mock\n{base}\n```\n```base\n${h}-x-${h}\n```\n```h\n1\n```\n";
+        let ws = Workspace::new(rules, values);
+
+        let spans = |key| -> Vec<_> { ws.references_of(key).iter().map(|r| (r.document, r.span)).collect() };
+        assert_eq!(
+            spans("mock"),
+            vec![(Document::Rules, Span::new(14, 18)), (Document::Rules, Span::new(59, 63))]
+        );
+        assert_eq!(
+            spans("h"),
+            vec![
+                (Document::Rules, Span::new(34, 35)),
+                (Document::Values, Span::new(29, 30)),
+                (Document::Values, Span::new(36, 37)),
+            ]
+        );
+
+        let reference = ws.reference_at(Document::Rules, 16).unwrap();
+        assert_eq!(reference.key, "mock");
+        let block = ws.definition_of(&reference).unwrap();
+        assert_eq!(&ws.values_text()[block.body.start..block.body.end], "{base}");
+        let reference = ws.reference_at(Document::Values, 10).unwrap();
+        assert_eq!(ws.definition_of(&reference).unwrap().key, "base");
+        assert_eq!(ws.reference_at(Document::Rules, 2), None);
+    }
+
+    #[test]
+    fn test_parse_and_validate() {
+        let ws = Workspace::parse(
+            "a.com   file://{mock}\n#off b.com  reqHeaders://{gone}\nc.com file://{loop}",
+            "```mock\n${h}\n```\n```unused\nx\n```\n```loop\n{loop}\n```\n",
+        );
+        assert!(ws.resolve("loop").is_err());
+        let found: Vec<_> = ws.validate().iter().map(|p| (p.document(), p.span())).collect();
+        assert_eq!(
+            found,
+            vec![
+                (Document::Rules, Span::new(48, 52)),
+                (Document::Values, Span::new(10, 11)),
+                (Document::Values, Span::new(20, 26)),
+                (Document::Values, Span::new(36, 40)),
+            ]
+        );
+        assert!(matches!(&ws.validate()[3], ValueProblem::Unresolvable { error: ValueError::CircularReference { .. }, .. }));
+
+        let matched: Vec<_> = ws.matching(&Request::new("http://a.com/x")).into_iter().map(|(id, _)| id).collect();
+        assert_eq!(matched, vec![RuleId(0)]);
+        assert_eq!(
+            apply_edits(ws.rules.text(), &ws.format()),
+            "a.com file://{mock}\n#off b.com reqHeaders://{gone}\nc.com file://{loop}"
+        );
+    }
+
+    #[cfg(feature = "typed-ops")]
+    #[test]
+    fn test_lint() {
+        let ws = Workspace::parse("a.com file://{y} resBody://{x}", "```x\n{x}\n```\n```x sha256=00\n1\n```\n");
+        let lint = ws.lint(Date { year: 2025, month: 3, day: 1 });
+        let codes = |diagnostics: &[Diagnostic]| -> Vec<_> { diagnostics.iter().map(|d| d.code).collect() };
+        assert_eq!(codes(&lint.rules), vec!["undefined-value"]);
+        assert_eq!(codes(&lint.values), vec!["duplicate-value", "checksum-mismatch"]);
+        assert_eq!(lint.rules[0].message, "no value is defined as `y`");
+    }
+
+    #[test]
+    fn test_rename_value_key() {
+        let rules = RuleFile::parse("a.com file://{h}  resHeaders://{h}\nb.com reqHeaders://{hh}");
+        let values = "```h\n1\n```\n```x\n${h}{h}\n```\n";
+        let ws = Workspace::new(rules, values);
+        let edit = ws.rename_value_key("h", "headers").unwrap();
+        assert_eq!(
+            apply_edits(ws.rules.text(), &edit.rules),
+            "a.com file://{headers}  resHeaders://{headers}\nb.com reqHeaders://{hh}"
+        );
+        assert_eq!(apply_edits(ws.values_text(), &edit.values), "```headers\n1\n```\n```x\n${headers}{h}\n```\n");
+        assert_eq!(ws.rename_value_key("h", "a b"), None);
+    }
+
+    #[test]
+    fn test_references_after_value_block() {
+        let ws = Workspace::parse("```inline
+x
+```
+a.com file://{mock}
+b.com file://{nope}", "```mock
+1
+```
+");
+        let spans: Vec<_> = ws.references_of("mock").iter().map(|r| r.span).collect();
+        assert_eq!(spans, vec![Span::new(30, 34)]);
+        let found: Vec<_> = ws.validate().iter().map(|p| (p.document(), p.span())).collect();
+        assert_eq!(found, vec![(Document::Rules, Span::new(50, 54))]);
+        let edit = ws.rename_value_key("mock", "m").unwrap();
+        assert_eq!(
+            apply_edits(ws.rules.text(), &edit.rules),
+            "```inline\nx\n```\na.com file://{m}\nb.com file://{nope}"
+        );
+    }
+}
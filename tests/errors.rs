@@ -0,0 +1,38 @@
+//! The error types as a downstream crate sees them: matches need a `_` arm
+//! and codes keep their names between releases.
+use whistle_proxy_rule_parser::{parse_proxy_rule_complete, ParserOptions, Span, WhistleParseError};
+
+fn class(error: &WhistleParseError) -> &'static str {
+    match error {
+        WhistleParseError::InvalidHost { .. } | WhistleParseError::InvalidPort { .. } => "uri",
+        WhistleParseError::TrailingInput { .. } => "operation",
+        _ => "other",
+    }
+}
+
+#[test]
+fn test_error_codes() {
+    let options = ParserOptions { strict_uri: true, ..Default::default() };
+    for (line, code, span) in [
+        ("a.com file://x oops", "trailing-input", Span::new(15, 19)),
+        ("a.com b.com c.com", "extra-pattern", Span::new(12, 17)),
+        ("http://a.com:x file://y", "invalid-port", Span::new(13, 14)),
+    ] {
+        let error = parse_proxy_rule_complete(line, &options).unwrap_err();
+        assert_eq!((error.code(), error.span()), (code, span), "{line}");
+    }
+    let error = parse_proxy_rule_complete("a.com file://x oops", &options).unwrap_err();
+    assert_eq!(class(&error), "operation");
+}
+
+#[cfg(feature = "typed-ops")]
+#[test]
+fn test_diagnostic_codes() {
+    use whistle_proxy_rule_parser::diagnostic::{diagnostics, Severity};
+    use whistle_proxy_rule_parser::meta::Date;
+    use whistle_proxy_rule_parser::rule_file::RuleFile;
+
+    let file = RuleFile::parse("a.com file://x oops\n");
+    let found: Vec<_> = diagnostics(&file, Date { year: 2025, month: 3, day: 1 }).into_iter().map(|d| (d.code, d.severity)).collect();
+    assert_eq!(found, vec![("trailing-input", Severity::Error)]);
+}
@@ -0,0 +1,16 @@
+//! What a `default-features = false` build offers: parsing rule files and
+//! matching requests against them. Kept to that API so the test runs in
+//! the minimal build too.
+use whistle_proxy_rule_parser::matcher::Request;
+use whistle_proxy_rule_parser::rule_file::RuleFile;
+
+#[test]
+fn test_match_without_features() {
+    let file = RuleFile::parse("# api\n*.a.com/api file://`/mock/${name}`\nb.com 127.0.0.1:8080\n");
+    let matcher = file.rule_set().compile();
+    let matched = matcher.match_request(&Request::new("https://x.a.com/api/users"));
+    assert_eq!(matched.len(), 1);
+    assert_eq!(matched[0].target.scheme, "file");
+    assert_eq!(matcher.match_url("http://b.com/")[0].target_value(), "127.0.0.1:8080");
+    assert!(matcher.match_url("http://c.com/").is_empty());
+}